@@ -0,0 +1,53 @@
+use blockchain_simulation_rust::block::HeaderHasher;
+use blockchain_simulation_rust::{Block, Transaction};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+/// Nonces hashed per iteration of the hashing benchmarks
+const NONCES: u64 = 10_000;
+
+fn block() -> Block {
+    let transactions = (0..10)
+        .map(|i| Transaction::new("Alice", "Bob", i as f64 + 1.0))
+        .collect();
+    Block::new(1, "00".repeat(32), transactions)
+}
+
+/// Hashing one header at many nonces: re-encoding and hex-formatting every attempt,
+/// as mining used to, against resuming from the header's midstate
+fn header_hashing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("header_hashing");
+    group.throughput(Throughput::Elements(NONCES));
+
+    group.bench_function("calculate_hash", |b| {
+        let mut block = block();
+        b.iter(|| {
+            for nonce in 0..NONCES {
+                block.nonce = nonce;
+                black_box(block.calculate_hash());
+            }
+        })
+    });
+    group.bench_function("header_hasher", |b| {
+        let block = block();
+        b.iter(|| {
+            let hasher = HeaderHasher::new(&block);
+            for nonce in 0..NONCES {
+                black_box(hasher.digest(nonce));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn mining(c: &mut Criterion) {
+    c.bench_function("mine_block_difficulty_3", |b| {
+        b.iter_batched(
+            block,
+            |mut block| block.mine_block_with_difficulty(3).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, header_hashing, mining);
+criterion_main!(benches);
@@ -0,0 +1,326 @@
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Deref;
+
+/// Every wallet address starts with this; other account names are plain simulation names
+pub const ADDRESS_PREFIX: &str = "bsr";
+
+/// Version byte of the addresses this build creates
+pub const ADDRESS_VERSION: u8 = 1;
+
+const HASH_BYTES: usize = 20; // Truncated public key hash
+const CHECKSUM_BYTES: usize = 4;
+const ENCODED_BYTES: usize = 1 + HASH_BYTES + CHECKSUM_BYTES;
+
+/// Reasons a string is not a valid wallet address
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressError {
+    /// The string doesn't start with `ADDRESS_PREFIX`
+    MissingPrefix(String),
+    /// A character after the prefix isn't in the base58 alphabet
+    BadCharset { address: String, character: char },
+    /// The part after the prefix decodes to the wrong number of bytes
+    BadLength { address: String, bytes: usize },
+    /// The version byte isn't one this build knows
+    UnknownVersion { address: String, version: u8 },
+    /// The checksum doesn't match, usually because of a typo
+    BadChecksum(String),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressError::MissingPrefix(address) => write!(
+                f,
+                "{:?} is not a wallet address (they start with {})",
+                address, ADDRESS_PREFIX
+            ),
+            AddressError::BadCharset { address, character } => write!(
+                f,
+                "{} contains {:?}, which is not a base58 character",
+                address, character
+            ),
+            AddressError::BadLength { address, bytes } => write!(
+                f,
+                "{} decodes to {} bytes instead of {}",
+                address, bytes, ENCODED_BYTES
+            ),
+            AddressError::UnknownVersion { address, version } => {
+                write!(f, "{} has unknown address version {}", address, version)
+            }
+            AddressError::BadChecksum(address) => {
+                write!(f, "{} has a bad checksum (typo?)", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// An account that can send and receive coins.
+///
+/// Wallet addresses are the prefix followed by base58 of a version byte, the first
+/// 20 bytes of the SHA-256 of the public key, and a 4-byte double SHA-256 checksum
+/// of the two. The simulation also uses plain names such as `Alice`, which carry no
+/// checksum; `parse` refuses them and `parse_or_name` lets them through.
+/// Serialized as the string itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Address(String);
+
+impl Address {
+    /// Address of the account controlled by `public_key`
+    pub fn from_public_key(public_key: &VerifyingKey) -> Address {
+        let hash = Sha256::digest(public_key.as_bytes());
+        let mut bytes = vec![ADDRESS_VERSION];
+        bytes.extend_from_slice(&hash[..HASH_BYTES]);
+        bytes.extend_from_slice(&checksum(&bytes));
+        Address(format!(
+            "{}{}",
+            ADDRESS_PREFIX,
+            bs58::encode(bytes).into_string()
+        ))
+    }
+
+    /// Parses a wallet address, checking its alphabet, length, version and checksum
+    pub fn parse(s: &str) -> Result<Address, AddressError> {
+        let encoded = s
+            .strip_prefix(ADDRESS_PREFIX)
+            .ok_or_else(|| AddressError::MissingPrefix(s.to_string()))?;
+        let bytes = bs58::decode(encoded).into_vec().map_err(|e| match e {
+            bs58::decode::Error::InvalidCharacter { character, .. } => AddressError::BadCharset {
+                address: s.to_string(),
+                character,
+            },
+            _ => AddressError::BadLength {
+                address: s.to_string(),
+                bytes: 0,
+            },
+        })?;
+        if bytes.len() != ENCODED_BYTES {
+            return Err(AddressError::BadLength {
+                address: s.to_string(),
+                bytes: bytes.len(),
+            });
+        }
+
+        let (payload, found) = bytes.split_at(1 + HASH_BYTES);
+        if checksum(payload) != found {
+            return Err(AddressError::BadChecksum(s.to_string()));
+        }
+        if payload[0] != ADDRESS_VERSION {
+            return Err(AddressError::UnknownVersion {
+                address: s.to_string(),
+                version: payload[0],
+            });
+        }
+        Ok(Address(s.to_string()))
+    }
+
+    /// Parses a wallet address like `parse`, but takes anything without the prefix
+    /// as a plain simulation name
+    pub fn parse_or_name(s: &str) -> Result<Address, AddressError> {
+        if is_wallet_address(s) {
+            Address::parse(s)
+        } else {
+            Ok(Address(s.to_string()))
+        }
+    }
+
+    /// Wraps `s` without checking it. Transactions built this way are still checked
+    /// by the mempool.
+    pub fn unchecked(s: &str) -> Address {
+        Address(s.to_string())
+    }
+
+    /// Returns true for wallet addresses, false for plain names
+    pub fn is_wallet(&self) -> bool {
+        is_wallet_address(&self.0)
+    }
+
+    /// Checks the address again, e.g. after building it with `unchecked`
+    pub fn check(&self) -> Result<(), AddressError> {
+        Address::parse_or_name(&self.0).map(|_| ())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Returns true if `address` claims to be a wallet address rather than a plain name
+pub fn is_wallet_address(address: &str) -> bool {
+    address.starts_with(ADDRESS_PREFIX)
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_BYTES] {
+    let hash = Sha256::digest(Sha256::digest(payload));
+    let mut checksum = [0u8; CHECKSUM_BYTES];
+    checksum.copy_from_slice(&hash[..CHECKSUM_BYTES]);
+    checksum
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for Address {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Address {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Address {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Address {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Address {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Address {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+impl PartialEq<Address> for str {
+    fn eq(&self, other: &Address) -> bool {
+        self == other.0
+    }
+}
+
+impl PartialEq<Address> for &str {
+    fn eq(&self, other: &Address) -> bool {
+        *self == other.0
+    }
+}
+
+impl PartialEq<Address> for String {
+    fn eq(&self, other: &Address) -> bool {
+        *self == other.0
+    }
+}
+
+impl TryFrom<String> for Address {
+    type Error = AddressError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Address::parse_or_name(&s)
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> Self {
+        address.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Public key of the ed25519 secret key made of 32 `0x01` bytes,
+    /// 8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c
+    fn known_key() -> VerifyingKey {
+        ed25519_dalek::SigningKey::from_bytes(&[1; 32]).verifying_key()
+    }
+
+    #[test]
+    fn known_keys_have_known_addresses() {
+        let address = Address::from_public_key(&known_key());
+        assert_eq!(address, "bsrV7yMcvcTpgCQeg9syaSEmXqQCe4bVxJNn");
+        assert_eq!(Address::parse(&address), Ok(address.clone()));
+    }
+
+    #[test]
+    fn single_character_typos_fail_the_checksum() {
+        let address = Address::from_public_key(&known_key()).to_string();
+        let position = ADDRESS_PREFIX.len() + 10;
+        let mut typo: Vec<char> = address.chars().collect();
+        typo[position] = if typo[position] == 'a' { 'b' } else { 'a' };
+        let typo: String = typo.into_iter().collect();
+        assert_eq!(
+            Address::parse(&typo),
+            Err(AddressError::BadChecksum(typo.clone()))
+        );
+    }
+
+    #[test]
+    fn malformed_addresses_say_what_is_wrong() {
+        let address = Address::from_public_key(&known_key()).to_string();
+        assert_eq!(
+            Address::parse("Alice"),
+            Err(AddressError::MissingPrefix("Alice".to_string()))
+        );
+        let zero = format!("{}0", &address[..address.len() - 1]);
+        assert_eq!(
+            Address::parse(&zero),
+            Err(AddressError::BadCharset {
+                address: zero.clone(),
+                character: '0'
+            })
+        );
+        assert!(matches!(
+            Address::parse(&address[..address.len() - 2]),
+            Err(AddressError::BadLength { bytes: 23, .. })
+        ));
+
+        let mut bytes = vec![ADDRESS_VERSION + 1];
+        bytes.extend_from_slice(&[7; HASH_BYTES]);
+        bytes.extend_from_slice(&checksum(&bytes));
+        let future = format!("{}{}", ADDRESS_PREFIX, bs58::encode(bytes).into_string());
+        assert!(matches!(
+            Address::parse(&future),
+            Err(AddressError::UnknownVersion { version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn names_pass_only_when_allowed() {
+        assert_eq!(
+            Address::parse_or_name("Alice"),
+            Ok(Address::unchecked("Alice"))
+        );
+        assert!(!Address::unchecked("Alice").is_wallet());
+        assert!(Address::parse_or_name("bsrAlice").is_err());
+    }
+
+    #[test]
+    fn serde_uses_the_encoded_string() {
+        let address = Address::from_public_key(&known_key());
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, format!("\"{}\"", address));
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+        assert_eq!(
+            serde_json::from_str::<Address>("\"Bob\"").unwrap(),
+            Address::unchecked("Bob")
+        );
+
+        let typo = json.replace(&address[address.len() - 3..], "zzz");
+        assert!(serde_json::from_str::<Address>(&typo).is_err());
+    }
+}
@@ -0,0 +1,1021 @@
+// Import necessary dependencies
+use crate::clock::{Clock, SystemClock};
+use crate::consensus::AuthoritySeal;
+use crate::encoding::{self, DecodeError, Encoder};
+use crate::merkle::merkle_root;
+use crate::target::{difficulty_to_bits, difficulty_to_target, Target};
+use crate::time::format_timestamp;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256}; // For cryptographic hashing
+use std::fmt; // For custom display formatting
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering}; // Shared state between mining workers
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant}; // For hash rates
+
+/// Default mining difficulty - number of leading zeros required in hash
+pub const DIFFICULTY: usize = 2;
+
+/// Version of new blocks: the hash covers the canonical binary header, compact
+/// target included, and proof of work compares the hash with that target
+pub const BLOCK_VERSION: u32 = 3;
+
+/// Version of blocks hashed over the canonical binary header whose proof of work
+/// counts the leading zeros of the hash
+pub const ZEROS_BLOCK_VERSION: u32 = 2;
+
+/// Version of blocks saved before versioning, hashed over the concatenated header
+/// fields. Those concatenations are ambiguous, so only old chains use it.
+pub const LEGACY_BLOCK_VERSION: u32 = 1;
+
+/// Hashes between calls to `MiningObserver::on_progress` unless the observer asks otherwise
+pub const PROGRESS_INTERVAL: u64 = 10_000;
+
+/// Bounds on the nonce search in `Block::mine_block_with_limits`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiningLimits {
+    pub max_nonce: u64, // Highest nonce tried before restarting with a new timestamp
+    pub max_restarts: u32, // Restarts allowed before giving up
+}
+
+impl Default for MiningLimits {
+    fn default() -> Self {
+        MiningLimits {
+            max_nonce: u64::MAX,
+            max_restarts: 16,
+        }
+    }
+}
+
+/// Outcome of a successful `Block::mine_block`, kept on the block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MiningStats {
+    pub hashes_tried: u64,
+    pub restarts: u32, // Times the nonce space ran out and the timestamp was bumped
+    pub elapsed: Duration,
+}
+
+/// Reasons mining can give up without finding a valid hash
+#[derive(Debug, Clone, PartialEq)]
+pub enum MiningError {
+    /// Every nonce up to `max_nonce` failed after every allowed restart
+    NonceSpaceExhausted { restarts: u32, hashes_tried: u64 },
+    /// The observer stopped the search
+    Cancelled,
+    /// The block's `bits` don't encode a target, so no hash can meet it
+    InvalidTarget { bits: u32 },
+}
+
+impl fmt::Display for MiningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MiningError::NonceSpaceExhausted {
+                restarts,
+                hashes_tried,
+            } => write!(
+                f,
+                "no valid nonce found after {} hashes and {} restarts",
+                hashes_tried, restarts
+            ),
+            MiningError::Cancelled => write!(f, "mining was cancelled"),
+            MiningError::InvalidTarget { bits } => {
+                write!(f, "bits {:#010x} are not a valid compact target", bits)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MiningError {}
+
+/// Watches a running nonce search and may cancel it
+pub trait MiningObserver {
+    /// Called every `progress_interval` hashes with the lowest hash seen so far.
+    /// Returning `ControlFlow::Break` stops mining with `MiningError::Cancelled`.
+    fn on_progress(
+        &mut self,
+        hashes_tried: u64,
+        elapsed: Duration,
+        best_hash: &str,
+    ) -> ControlFlow<()>;
+
+    /// Hashes between `on_progress` calls
+    fn progress_interval(&self) -> u64 {
+        PROGRESS_INTERVAL
+    }
+}
+
+/// Observer that never reports and never cancels
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SilentObserver;
+
+impl MiningObserver for SilentObserver {
+    fn on_progress(&mut self, _: u64, _: Duration, _: &str) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    fn progress_interval(&self) -> u64 {
+        u64::MAX
+    }
+}
+
+/// Outcome of `Block::mine_block_parallel`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParallelMiningResult {
+    pub nonce: u64,
+    pub hash: String,
+    pub hashes_tried: u64, // Hashes computed across all workers
+    pub elapsed: Duration,
+}
+
+impl ParallelMiningResult {
+    /// Hashes per second across all workers
+    pub fn hash_rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.hashes_tried as f64 / seconds
+        } else {
+            self.hashes_tried as f64
+        }
+    }
+}
+
+/// Represents a single block in the blockchain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    #[serde(default = "legacy_block_version")]
+    pub version: u32, // Hashing scheme; every block of a chain shares the genesis version
+    pub index: u32,                     // Index of the block in the chain
+    pub previous_hash: String,          // Hash of the previous block
+    pub timestamp: u64,                 // When the block was created (UNIX timestamp)
+    pub merkle_root: String,            // Merkle root of the transaction hashes
+    pub transactions: Vec<Transaction>, // Transactions stored in the block
+    pub difficulty: usize,              // Leading zeros this block was mined at
+    /// Compact target the hash must not exceed (see `Target::from_compact`). Only
+    /// blocks newer than `ZEROS_BLOCK_VERSION` carry one; it is 0 on older blocks.
+    #[serde(default, skip_serializing_if = "no_bits")]
+    pub bits: u32,
+    pub nonce: u64,   // Number used once for mining
+    pub hash: String, // This block's hash
+    pub mined: bool,  // Whether mining found a valid hash
+    /// Signature sealing the block under proof of authority; `None` for mined blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authority_seal: Option<AuthoritySeal>,
+    /// How much work mining took, if this block was mined by proof of work. Not covered
+    /// by the hash: it describes the mining run rather than the block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mining_stats: Option<MiningStats>,
+}
+
+/// The hashed fields of a block without its transactions, which is all a light
+/// client keeps. The merkle root still commits to the transactions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub index: u32,
+    pub previous_hash: String,
+    pub timestamp: u64,
+    pub merkle_root: String,
+    pub difficulty: usize,
+    #[serde(default, skip_serializing_if = "no_bits")]
+    pub bits: u32,
+    pub nonce: u64,
+    pub hash: String, // Hash of the block the header came from
+}
+
+impl BlockHeader {
+    /// Recomputes the hash the way `Block::calculate_hash` does
+    pub fn calculate_hash(&self) -> String {
+        self.as_block().calculate_hash()
+    }
+
+    /// Target the hash must not exceed, as in `Block::target`
+    pub fn target(&self) -> Option<Target> {
+        self.as_block().target()
+    }
+
+    /// Returns true if the stored hash meets the header's target
+    pub fn hash_meets_target(&self) -> bool {
+        self.as_block().hash_meets_target()
+    }
+
+    /// A block without transactions; the hash only covers header fields, so it
+    /// hashes the same as the block the header came from
+    fn as_block(&self) -> Block {
+        Block {
+            version: self.version,
+            index: self.index,
+            previous_hash: self.previous_hash.clone(),
+            timestamp: self.timestamp,
+            merkle_root: self.merkle_root.clone(),
+            transactions: Vec::new(),
+            difficulty: self.difficulty,
+            bits: self.bits,
+            nonce: self.nonce,
+            hash: self.hash.clone(),
+            mined: true,
+            authority_seal: None,
+            mining_stats: None,
+        }
+    }
+}
+
+impl Block {
+    /// Creates a new block with the given parameters, stamped with the current time
+    pub fn new(index: u32, previous_hash: String, transactions: Vec<Transaction>) -> Block {
+        Block::new_with_clock(index, previous_hash, transactions, &SystemClock)
+    }
+
+    /// Creates a new block stamped with `clock`'s time
+    pub fn new_with_clock(
+        index: u32,
+        previous_hash: String,
+        transactions: Vec<Transaction>,
+        clock: &dyn Clock,
+    ) -> Block {
+        let timestamp = clock.now_secs();
+        let merkle_root = merkle_root(&transaction_hashes(&transactions));
+
+        Block {
+            version: BLOCK_VERSION,
+            index,
+            previous_hash,
+            timestamp,
+            merkle_root,
+            transactions,
+            difficulty: DIFFICULTY,
+            bits: difficulty_to_bits(DIFFICULTY),
+            nonce: 0,            // Initialize nonce to 0
+            hash: String::new(), // Hash will be calculated during mining
+            mined: false,
+            authority_seal: None,
+            mining_stats: None,
+        }
+    }
+
+    /// Calculates the SHA-256 hash of the block header; the merkle root commits to the
+    /// transactions
+    pub fn calculate_hash(&self) -> String {
+        if self.version == LEGACY_BLOCK_VERSION {
+            return self.calculate_legacy_hash();
+        }
+        format!("{:x}", Sha256::digest(self.header_bytes()))
+    }
+
+    /// Canonical encoding of the hashed header fields: fixed-width big-endian
+    /// integers and length-prefixed strings, so distinct headers never share bytes
+    pub fn header_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header_prefix();
+        bytes.extend_from_slice(&self.nonce.to_be_bytes());
+        bytes
+    }
+
+    /// `header_bytes` up to the nonce, which comes last
+    fn header_prefix(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.u32(self.version);
+        encoder.u32(self.index);
+        encoder.str(&self.previous_hash);
+        encoder.u64(self.timestamp);
+        encoder.str(&self.merkle_root);
+        encoder.u64(self.difficulty as u64);
+        if self.uses_target() {
+            encoder.u32(self.bits);
+        }
+        encoder.into_bytes()
+    }
+
+    /// Returns true if the block's proof of work is checked against `bits` rather
+    /// than by counting leading zeros
+    pub fn uses_target(&self) -> bool {
+        self.version > ZEROS_BLOCK_VERSION
+    }
+
+    /// Target the block's hash must not exceed: its compact `bits`, or for older
+    /// blocks the target equivalent to `difficulty` leading zeros. `None` if `bits`
+    /// isn't a valid compact target.
+    pub fn target(&self) -> Option<Target> {
+        if self.uses_target() {
+            Target::from_compact(self.bits)
+        } else {
+            Some(difficulty_to_target(self.difficulty))
+        }
+    }
+
+    /// Returns true if the stored hash meets the block's target
+    pub fn hash_meets_target(&self) -> bool {
+        match (self.target(), hex::decode(&self.hash)) {
+            (Some(target), Ok(digest)) => target.is_met_by(&digest),
+            _ => false,
+        }
+    }
+
+    /// Records `difficulty` on the block. Blocks that use a target keep their `bits`,
+    /// which the chain may have retargeted between two whole difficulties.
+    pub fn set_difficulty(&mut self, difficulty: usize) {
+        self.difficulty = difficulty;
+    }
+
+    /// Records `difficulty` and, if the block uses a target, replaces its `bits`
+    /// with the compact target of exactly that many leading zeros
+    pub fn set_difficulty_target(&mut self, difficulty: usize) {
+        self.difficulty = difficulty;
+        if self.uses_target() {
+            self.bits = difficulty_to_bits(difficulty);
+        }
+    }
+
+    /// Hash used by `LEGACY_BLOCK_VERSION` blocks
+    fn calculate_legacy_hash(&self) -> String {
+        // Combine block fields into a single string
+        let data = format!("{}{}", self.legacy_header_prefix(), self.nonce);
+
+        // Create SHA-256 hasher
+        let mut hasher = Sha256::new();
+        hasher.update(data.as_bytes());
+
+        // Finalize hash and convert to hexadecimal string
+        let result = hasher.finalize();
+        format!("{:x}", result)
+    }
+
+    /// Header fields hashed by legacy blocks before the nonce, concatenated
+    fn legacy_header_prefix(&self) -> String {
+        format!(
+            "{}{}{}{}{}",
+            self.index, self.previous_hash, self.timestamp, self.merkle_root, self.difficulty
+        )
+    }
+
+    /// Compact binary encoding of the whole block, transactions included
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encoding::to_bytes(self)
+    }
+
+    /// Reads a block written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Block, DecodeError> {
+        encoding::from_bytes(bytes)
+    }
+
+    /// Hashes of the block's transactions, in block order
+    pub fn transaction_hashes(&self) -> Vec<String> {
+        transaction_hashes(&self.transactions)
+    }
+
+    /// Recomputes the merkle root from the block's current transactions
+    pub fn calculate_merkle_root(&self) -> String {
+        merkle_root(&self.transaction_hashes())
+    }
+
+    /// The block's header, for clients that don't need its transactions
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            version: self.version,
+            index: self.index,
+            previous_hash: self.previous_hash.clone(),
+            timestamp: self.timestamp,
+            merkle_root: self.merkle_root.clone(),
+            difficulty: self.difficulty,
+            bits: self.bits,
+            nonce: self.nonce,
+            hash: self.hash.clone(),
+        }
+    }
+
+    /// Mines the block at its recorded `difficulty` with the default limits
+    pub fn mine_block(&mut self) -> Result<MiningStats, MiningError> {
+        self.mine_block_with_limits(&MiningLimits::default())
+    }
+
+    /// Records `difficulty` on the block (see `set_difficulty_target`) and mines it
+    /// by finding a nonce whose hash has that many leading zeros
+    pub fn mine_block_with_difficulty(
+        &mut self,
+        difficulty: usize,
+    ) -> Result<MiningStats, MiningError> {
+        self.set_difficulty_target(difficulty);
+        self.mine_block()
+    }
+
+    /// Records `difficulty` on the block (see `set_difficulty_target`) and mines it
+    /// with the default limits, reporting progress to `observer`. If the observer
+    /// cancels, or the search fails, the block is left exactly as it was.
+    pub fn mine_block_with(
+        &mut self,
+        difficulty: usize,
+        observer: &mut dyn MiningObserver,
+    ) -> Result<MiningStats, MiningError> {
+        let mut candidate = self.clone();
+        candidate.set_difficulty_target(difficulty);
+        let stats = candidate.search(&MiningLimits::default(), observer)?;
+        *self = candidate;
+        Ok(stats)
+    }
+
+    /// Mines the block at its recorded target with the default limits, reporting
+    /// progress to `observer`. Unlike `mine_block_with` it keeps the block's `bits`,
+    /// so blocks prepared by a retargeting chain stay valid for it.
+    pub fn mine_block_observed(
+        &mut self,
+        observer: &mut dyn MiningObserver,
+    ) -> Result<MiningStats, MiningError> {
+        let mut candidate = self.clone();
+        let stats = candidate.search(&MiningLimits::default(), observer)?;
+        *self = candidate;
+        Ok(stats)
+    }
+
+    /// Mines the block at its recorded `difficulty`, trying nonces 0 to `max_nonce`.
+    /// When they run out the timestamp moves forward a second, giving a fresh search
+    /// space, and the search restarts, up to `max_restarts` times.
+    pub fn mine_block_with_limits(
+        &mut self,
+        limits: &MiningLimits,
+    ) -> Result<MiningStats, MiningError> {
+        let mut candidate = self.clone();
+        let stats = candidate.search(limits, &mut SilentObserver)?;
+        *self = candidate;
+        Ok(stats)
+    }
+
+    /// Nonce search shared by the sequential mining methods
+    fn search(
+        &mut self,
+        limits: &MiningLimits,
+        observer: &mut dyn MiningObserver,
+    ) -> Result<MiningStats, MiningError> {
+        let target = self
+            .target()
+            .ok_or(MiningError::InvalidTarget { bits: self.bits })?;
+        let started = Instant::now();
+        let interval = observer.progress_interval().max(1);
+        let mut best_digest = [u8::MAX; 32];
+        let mut hashes_tried: u64 = 0;
+        let mut restarts = 0;
+        loop {
+            let hasher = HeaderHasher::new(self);
+            for nonce in 0..=limits.max_nonce {
+                let digest = hasher.digest(nonce);
+                hashes_tried += 1;
+
+                // Check if the hash is at or below the target
+                if target.is_met_by(&digest) {
+                    let stats = MiningStats {
+                        hashes_tried,
+                        restarts,
+                        elapsed: started.elapsed(),
+                    };
+                    self.nonce = nonce;
+                    self.hash = hex::encode(digest);
+                    self.mined = true;
+                    self.mining_stats = Some(stats.clone());
+                    return Ok(stats);
+                }
+
+                // The lowest digest has the most leading zeros
+                best_digest = best_digest.min(digest);
+                if hashes_tried.is_multiple_of(interval)
+                    && observer
+                        .on_progress(hashes_tried, started.elapsed(), &hex::encode(best_digest))
+                        .is_break()
+                {
+                    return Err(MiningError::Cancelled);
+                }
+            }
+
+            if restarts == limits.max_restarts {
+                return Err(MiningError::NonceSpaceExhausted {
+                    restarts,
+                    hashes_tried,
+                });
+            }
+            restarts += 1;
+            self.timestamp += 1;
+        }
+    }
+
+    /// Mines the block at its recorded target on `threads` worker threads.
+    ///
+    /// Worker k tries nonces k, k + threads, k + 2 * threads, ... and the first
+    /// valid nonce reported stops the others. Different runs may find different
+    /// nonces, but the result always meets the target.
+    pub fn mine_block_parallel(
+        &mut self,
+        threads: usize,
+    ) -> Result<ParallelMiningResult, MiningError> {
+        if self.target().is_none() {
+            return Err(MiningError::InvalidTarget { bits: self.bits });
+        }
+        let threads = threads.max(1) as u64;
+        let stop = AtomicBool::new(false);
+        let hashes_tried = AtomicU64::new(0);
+        let (sender, receiver) = mpsc::channel();
+        let started = Instant::now();
+
+        thread::scope(|scope| {
+            for worker in 0..threads {
+                let sender = sender.clone();
+                let (block, stop, hashes_tried) = (&*self, &stop, &hashes_tried);
+                scope.spawn(move || {
+                    if let Some(found) = block.search_nonces(worker, threads, stop, hashes_tried) {
+                        stop.store(true, Ordering::Relaxed);
+                        // The receiver only keeps the first result
+                        let _ = sender.send(found);
+                    }
+                });
+            }
+        });
+        drop(sender);
+
+        // Every worker gave up only if the whole nonce space failed
+        let (nonce, hash) = receiver
+            .recv()
+            .map_err(|_| MiningError::NonceSpaceExhausted {
+                restarts: 0,
+                hashes_tried: hashes_tried.load(Ordering::Relaxed),
+            })?;
+        let result = ParallelMiningResult {
+            nonce,
+            hash,
+            hashes_tried: hashes_tried.load(Ordering::Relaxed),
+            elapsed: started.elapsed(),
+        };
+        self.nonce = nonce;
+        self.hash = result.hash.clone();
+        self.mined = true;
+        self.mining_stats = Some(MiningStats {
+            hashes_tried: result.hashes_tried,
+            restarts: 0,
+            elapsed: result.elapsed,
+        });
+        Ok(result)
+    }
+
+    /// Tries nonces `start`, `start + step`, ... until one meets the block's difficulty
+    /// or `stop` is set, adding the number of hashes computed to `hashes_tried`
+    pub fn search_nonces(
+        &self,
+        start: u64,
+        step: u64,
+        stop: &AtomicBool,
+        hashes_tried: &AtomicU64,
+    ) -> Option<(u64, String)> {
+        let target = self.target()?;
+        let hasher = HeaderHasher::new(self);
+        let mut nonce = start;
+        let mut tried = 0;
+
+        let found = loop {
+            if stop.load(Ordering::Relaxed) {
+                break None;
+            }
+
+            let digest = hasher.digest(nonce);
+            tried += 1;
+            if target.is_met_by(&digest) {
+                break Some((nonce, hex::encode(digest)));
+            }
+
+            nonce = match nonce.checked_add(step) {
+                Some(nonce) => nonce,
+                None => break None,
+            };
+        };
+
+        hashes_tried.fetch_add(tried, Ordering::Relaxed);
+        found
+    }
+}
+
+/// Hashes one block header at many nonces. Everything before the nonce is absorbed
+/// once, and each attempt resumes from that midstate, so a nonce costs a hash of its
+/// own bytes and no allocation. Digests equal `Block::calculate_hash` at that nonce.
+#[derive(Debug, Clone)]
+pub struct HeaderHasher {
+    midstate: Sha256,
+    legacy: bool, // Legacy headers end in the nonce's decimal digits, not its bytes
+}
+
+impl HeaderHasher {
+    /// Absorbs the header fields of `block` other than its nonce
+    pub fn new(block: &Block) -> HeaderHasher {
+        let legacy = block.version == LEGACY_BLOCK_VERSION;
+        let mut midstate = Sha256::new();
+        if legacy {
+            midstate.update(block.legacy_header_prefix());
+        } else {
+            midstate.update(block.header_prefix());
+        }
+        HeaderHasher { midstate, legacy }
+    }
+
+    /// Raw SHA-256 digest of the header with `nonce`
+    pub fn digest(&self, nonce: u64) -> [u8; 32] {
+        let mut hasher = self.midstate.clone();
+        if self.legacy {
+            let mut digits = [0u8; 20];
+            hasher.update(decimal_digits(nonce, &mut digits));
+        } else {
+            hasher.update(nonce.to_be_bytes());
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// Writes `value` in decimal to the end of `buffer` and returns the digits
+fn decimal_digits(mut value: u64, buffer: &mut [u8; 20]) -> &[u8] {
+    let mut start = buffer.len();
+    loop {
+        start -= 1;
+        buffer[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            return &buffer[start..];
+        }
+    }
+}
+
+fn legacy_block_version() -> u32 {
+    LEGACY_BLOCK_VERSION
+}
+
+fn no_bits(bits: &u32) -> bool {
+    *bits == 0
+}
+
+fn transaction_hashes(transactions: &[Transaction]) -> Vec<String> {
+    transactions
+        .iter()
+        .map(Transaction::calculate_hash)
+        .collect()
+}
+
+/// Returns true if the hex hash starts with `difficulty` zero characters
+pub fn hash_meets_difficulty(hash: &str, difficulty: usize) -> bool {
+    hash.len() >= difficulty && hash.bytes().take(difficulty).all(|b| b == b'0')
+}
+
+/// `hash_meets_difficulty` on the raw digest: each byte is two hex digits, so
+/// `difficulty` zero digits are that many zero nibbles
+pub fn digest_meets_difficulty(digest: &[u8], difficulty: usize) -> bool {
+    let (whole_bytes, half_byte) = (difficulty / 2, difficulty % 2 == 1);
+    digest.len() * 2 >= difficulty
+        && digest[..whole_bytes].iter().all(|&byte| byte == 0)
+        && (!half_byte || digest[whole_bytes] < 0x10)
+}
+
+// Implement custom display formatting for Block
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Block {}: {} transactions at {}",
+            self.index,
+            self.transactions.len(),
+            format_timestamp(self.timestamp)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle;
+
+    fn mined_block(difficulty: usize) -> Block {
+        let transactions = vec![Transaction::new("Alice", "Bob", 5.0)];
+        let mut block = Block::new(1, String::from("previous"), transactions);
+        block.mine_block_with_difficulty(difficulty).unwrap();
+        block
+    }
+
+    #[test]
+    fn header_hasher_matches_calculate_hash() {
+        let mut block = Block::new(7, "ab".repeat(32), vec![Transaction::new("A", "B", 1.0)]);
+        for version in [BLOCK_VERSION, LEGACY_BLOCK_VERSION] {
+            block.version = version;
+            let hasher = HeaderHasher::new(&block);
+            for nonce in [0, 9, 10, 12_345, u64::MAX] {
+                block.nonce = nonce;
+                assert_eq!(hex::encode(hasher.digest(nonce)), block.calculate_hash());
+            }
+        }
+    }
+
+    #[test]
+    fn digest_difficulty_counts_hex_digits() {
+        let hasher = HeaderHasher::new(&Block::new(1, String::new(), Vec::new()));
+        for nonce in 0..2_000 {
+            let digest = hasher.digest(nonce);
+            for difficulty in 0..=4 {
+                assert_eq!(
+                    digest_meets_difficulty(&digest, difficulty),
+                    hash_meets_difficulty(&hex::encode(digest), difficulty)
+                );
+            }
+        }
+        assert!(digest_meets_difficulty(&[0, 0x0f], 3));
+        assert!(!digest_meets_difficulty(&[0, 0x10], 3));
+        assert!(!digest_meets_difficulty(&[0, 0], 5));
+    }
+
+    #[test]
+    fn new_block_is_not_mined() {
+        let block = Block::new(1, String::new(), Vec::new());
+        assert!(!block.mined);
+        assert!(block.hash.is_empty());
+    }
+
+    #[test]
+    fn mining_honors_difficulty() {
+        for difficulty in 1..=3 {
+            let block = mined_block(difficulty);
+            assert!(block.mined);
+            assert_eq!(block.difficulty, difficulty);
+            assert!(block.hash.starts_with(&"0".repeat(difficulty)));
+            assert_eq!(block.hash, block.calculate_hash());
+        }
+    }
+
+    #[test]
+    fn hash_meets_difficulty_counts_leading_zeros() {
+        assert!(hash_meets_difficulty("00ab", 2));
+        assert!(!hash_meets_difficulty("0a0b", 2));
+        assert!(hash_meets_difficulty("abcd", 0));
+        assert!(!hash_meets_difficulty("0", 2));
+    }
+
+    #[test]
+    fn merkle_root_commits_to_transactions() {
+        let block = mined_block(1);
+        assert_eq!(block.merkle_root, block.calculate_merkle_root());
+
+        let mut tampered = block.clone();
+        tampered.transactions[0].amount = 500.0;
+        assert_ne!(tampered.calculate_merkle_root(), block.merkle_root);
+
+        tampered.merkle_root = tampered.calculate_merkle_root();
+        assert_ne!(tampered.calculate_hash(), block.hash);
+    }
+
+    #[test]
+    fn exhausted_nonce_space_restarts_with_a_new_timestamp() {
+        let mut block = Block::new(1, String::from("previous"), Vec::new());
+        block.set_difficulty_target(3);
+        let timestamp = block.timestamp;
+        let limits = MiningLimits {
+            max_nonce: 10,
+            max_restarts: 10_000,
+        };
+
+        let stats = block.mine_block_with_limits(&limits).unwrap();
+        assert!(stats.restarts > 0);
+        assert_eq!(block.timestamp, timestamp + stats.restarts as u64);
+        assert_eq!(
+            stats.hashes_tried,
+            stats.restarts as u64 * 11 + block.nonce + 1
+        );
+        assert!(block.nonce <= 10);
+        assert!(block.mined);
+        assert_eq!(block.hash, block.calculate_hash());
+        assert!(hash_meets_difficulty(&block.hash, 3));
+    }
+
+    #[test]
+    fn mining_gives_up_after_the_restart_limit() {
+        // No hash can have 64 leading zeros in practice
+        let mut block = Block::new(1, String::new(), Vec::new());
+        block.set_difficulty_target(64);
+        let limits = MiningLimits {
+            max_nonce: 10,
+            max_restarts: 2,
+        };
+        assert_eq!(
+            block.mine_block_with_limits(&limits),
+            Err(MiningError::NonceSpaceExhausted {
+                restarts: 2,
+                hashes_tried: 33
+            })
+        );
+        assert!(!block.mined);
+    }
+
+    /// Cancels once `cancel_after` hashes have been tried, recording every call
+    struct RecordingObserver {
+        interval: u64,
+        cancel_after: u64,
+        calls: Vec<(u64, String)>,
+    }
+
+    impl MiningObserver for RecordingObserver {
+        fn on_progress(
+            &mut self,
+            hashes_tried: u64,
+            _: Duration,
+            best_hash: &str,
+        ) -> ControlFlow<()> {
+            self.calls.push((hashes_tried, best_hash.to_string()));
+            if hashes_tried >= self.cancel_after {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+
+        fn progress_interval(&self) -> u64 {
+            self.interval
+        }
+    }
+
+    #[test]
+    fn cancelling_leaves_the_block_untouched() {
+        let mut block = Block::new(1, String::from("previous"), Vec::new());
+        let original = block.clone();
+        let mut observer = RecordingObserver {
+            interval: 100,
+            cancel_after: 1000,
+            calls: Vec::new(),
+        };
+
+        // No hash can have 64 leading zeros in practice
+        assert_eq!(
+            block.mine_block_with(64, &mut observer),
+            Err(MiningError::Cancelled)
+        );
+        assert_eq!(block, original);
+        assert!(!block.mined);
+        assert_eq!(observer.calls.last().unwrap().0, 1000);
+    }
+
+    #[test]
+    fn observer_sees_regular_progress_with_improving_best_hash() {
+        let mut block = Block::new(1, String::from("previous"), Vec::new());
+        let mut observer = RecordingObserver {
+            interval: 10,
+            cancel_after: u64::MAX,
+            calls: Vec::new(),
+        };
+
+        let stats = block.mine_block_with(3, &mut observer).unwrap();
+        assert!(block.mined);
+        assert_eq!(block.difficulty, 3);
+        // The winning hash returns before any callback
+        assert_eq!(observer.calls.len() as u64, (stats.hashes_tried - 1) / 10);
+        for (position, (hashes_tried, _)) in observer.calls.iter().enumerate() {
+            assert_eq!(*hashes_tried, (position as u64 + 1) * 10);
+        }
+        assert!(observer.calls.windows(2).all(|pair| pair[1].1 <= pair[0].1));
+    }
+
+    #[test]
+    fn header_hash_matches_test_vector() {
+        let mut block = Block::new(1, String::from("00ab"), Vec::new());
+        block.version = ZEROS_BLOCK_VERSION;
+        block.timestamp = 1_700_000_000;
+        block.difficulty = 2;
+        block.nonce = 42;
+        assert_eq!(block.merkle_root, merkle::EMPTY_MERKLE_ROOT);
+
+        let mut expected = vec![0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 4];
+        expected.extend_from_slice(b"00ab");
+        expected.extend_from_slice(&1_700_000_000u64.to_be_bytes());
+        expected.extend_from_slice(&[0, 0, 0, 64]);
+        expected.extend_from_slice(merkle::EMPTY_MERKLE_ROOT.as_bytes());
+        expected.extend_from_slice(&2u64.to_be_bytes());
+        expected.extend_from_slice(&42u64.to_be_bytes());
+        assert_eq!(block.header_bytes(), expected);
+        assert_eq!(
+            block.calculate_hash(),
+            "7134061563a4638f893e1d2e568756d7027fa5b8c254e365c65a5a9cc3b3d2a1"
+        );
+    }
+
+    #[test]
+    fn target_headers_cover_the_compact_bits() {
+        let mut block = Block::new(1, String::from("00ab"), Vec::new());
+        block.timestamp = 1_700_000_000;
+        block.set_difficulty_target(2);
+        block.nonce = 42;
+        assert_eq!(block.bits, 0x2000_ffff);
+
+        let mut zeros = block.clone();
+        zeros.version = ZEROS_BLOCK_VERSION;
+        let mut expected = zeros.header_bytes();
+        expected[3] = BLOCK_VERSION as u8;
+        expected.splice(
+            expected.len() - 8..expected.len() - 8,
+            0x2000_ffffu32.to_be_bytes(),
+        );
+        assert_eq!(block.header_bytes(), expected);
+        let hash = block.calculate_hash();
+        assert_eq!(
+            hash,
+            "02389f5314e60d736c4d715cea44d36e058c32cbbeefc2368e0c807c3ed235fe"
+        );
+
+        block.bits += 1;
+        assert_ne!(block.calculate_hash(), hash);
+    }
+
+    #[test]
+    fn hashes_exactly_at_the_target_are_valid() {
+        let mut block = mined_block(1);
+        let target = block.target().unwrap();
+        // Pretend the hash landed on the target, then one above it
+        block.hash = target.to_string();
+        assert!(block.hash_meets_target());
+        let mut above = target.to_be_bytes();
+        above[31] = above[31].wrapping_add(1);
+        block.hash = hex::encode(above);
+        assert!(!block.hash_meets_target());
+
+        block.bits = 0x0492_3456; // Negative
+        assert_eq!(block.target(), None);
+        assert_eq!(
+            block.mine_block(),
+            Err(MiningError::InvalidTarget { bits: 0x0492_3456 })
+        );
+    }
+
+    #[test]
+    fn shifted_field_boundaries_no_longer_collide() {
+        // Index 12 with previous hash "3abc" concatenates like index 1 with "23abc"
+        let mut first = Block::new(12, String::from("3abc"), Vec::new());
+        let mut second = Block::new(1, String::from("23abc"), Vec::new());
+        second.timestamp = first.timestamp;
+
+        first.version = LEGACY_BLOCK_VERSION;
+        second.version = LEGACY_BLOCK_VERSION;
+        assert_eq!(first.calculate_hash(), second.calculate_hash());
+
+        first.version = BLOCK_VERSION;
+        second.version = BLOCK_VERSION;
+        assert_ne!(first.calculate_hash(), second.calculate_hash());
+    }
+
+    #[test]
+    fn blocks_round_trip_through_bytes() {
+        let mut block = mined_block(2);
+        block.authority_seal = Some(AuthoritySeal {
+            signer: "ab".repeat(32),
+            signature: "cd".repeat(64),
+        });
+        let bytes = block.to_bytes();
+        assert_eq!(Block::from_bytes(&bytes), Ok(block.clone()));
+        assert!(bytes.len() < serde_json::to_vec(&block).unwrap().len());
+        assert_eq!(
+            Block::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn parallel_mining_produces_valid_blocks() {
+        for threads in [1, 4] {
+            let mut block = Block::new(1, String::from("previous"), Vec::new());
+            block.set_difficulty_target(4);
+            let result = block.mine_block_parallel(threads).unwrap();
+
+            assert!(block.mined);
+            assert_eq!(block.nonce, result.nonce);
+            assert_eq!(block.hash, result.hash);
+            assert_eq!(block.hash, block.calculate_hash());
+            assert!(hash_meets_difficulty(&block.hash, 4));
+            assert!(result.hashes_tried > 0);
+            assert!(result.hash_rate() > 0.0);
+        }
+    }
+
+    #[test]
+    fn stop_flag_halts_workers() {
+        // No hash can have 64 leading zeros in practice, so only the flag ends the search
+        let mut block = Block::new(1, String::new(), Vec::new());
+        block.set_difficulty_target(64);
+        let stop = AtomicBool::new(false);
+        let hashes_tried = AtomicU64::new(0);
+
+        let found = thread::scope(|scope| {
+            let worker = scope.spawn(|| block.search_nonces(0, 1, &stop, &hashes_tried));
+            thread::sleep(Duration::from_millis(50));
+            stop.store(true, Ordering::Relaxed);
+            worker.join().unwrap()
+        });
+
+        assert_eq!(found, None);
+        assert!(hashes_tried.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn transaction_inclusion_is_provable() {
+        let transactions: Vec<Transaction> = (1..=5)
+            .map(|i| Transaction::new("Alice", "Bob", i as f64))
+            .collect();
+        let mut block = Block::new(1, String::new(), transactions);
+        block.mine_block_with_difficulty(1).unwrap();
+
+        let hashes = block.transaction_hashes();
+        let proof = merkle::merkle_proof(&hashes, 3);
+        assert!(merkle::verify_proof(&hashes[3], &proof, &block.merkle_root));
+    }
+}
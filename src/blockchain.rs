@@ -0,0 +1,3215 @@
+use crate::block::{Block, BlockHeader};
+use crate::clock::{Clock, SystemClock};
+use crate::consensus::{Consensus, ProofOfWork, SealError};
+use crate::difficulty::{chain_work, DifficultyConfig, DifficultyConfigError};
+use crate::encoding::{Decode, DecodeError, Decoder, Encode, Encoder};
+use crate::events::{ChainEvent, EventBus};
+use crate::genesis::GenesisConfig;
+use crate::limits::{BlockLimits, LimitError};
+use crate::merkle::{self, MerkleProof};
+use crate::orphan::OrphanPool;
+use crate::reward::{CoinbaseError, RewardSchedule};
+use crate::snapshot::{self, SnapshotError, SnapshotManifest};
+use crate::stats::ChainStats;
+use crate::store::ChainStore;
+use crate::transaction::{Mempool, MempoolError, Transaction};
+use crate::utxo::{UtxoError, UtxoSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+
+/// Number of recent blocks whose median timestamp a new block must exceed
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Default for how far ahead of the node's clock a block timestamp may be
+pub const MAX_FUTURE_DRIFT_SECS: u64 = 2 * 60 * 60;
+
+/// First bytes of a chain file in the binary format
+pub const CHAIN_FILE_MAGIC: [u8; 4] = *b"BSRC";
+
+/// Version byte following the magic in binary chain files. Version 1 files predate
+/// block size limits and load with the default ones.
+pub const CHAIN_FILE_VERSION: u8 = 2;
+
+/// On-disk formats for a saved chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChainFormat {
+    /// Pretty-printed JSON
+    #[default]
+    Json,
+    /// Magic header, version byte and the canonical binary encoding
+    Binary,
+}
+
+impl ChainFormat {
+    /// Format of a saved chain, judged by its first bytes
+    pub fn detect(bytes: &[u8]) -> ChainFormat {
+        if bytes.starts_with(&CHAIN_FILE_MAGIC) {
+            ChainFormat::Binary
+        } else {
+            ChainFormat::Json
+        }
+    }
+
+    /// Format of the chain file at `path`; JSON if it can't be read
+    pub fn of_file(path: &Path) -> ChainFormat {
+        let mut magic = [0u8; CHAIN_FILE_MAGIC.len()];
+        match fs::File::open(path).and_then(|mut file| io::Read::read_exact(&mut file, &mut magic))
+        {
+            Ok(()) => ChainFormat::detect(&magic),
+            Err(_) => ChainFormat::Json,
+        }
+    }
+}
+
+/// Reasons a block can be refused by `Blockchain::add_block`
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockchainError {
+    /// The block's hash is missing or stale, or its seal (work or signature) is invalid
+    MiningFailed { index: u32 },
+    /// A block from another node arrived without a seal
+    Unsealed { index: u32 },
+    /// The consensus engine could not seal the block
+    SealFailed { index: u32, reason: SealError },
+    /// The block's merkle root doesn't match its transactions
+    InvalidMerkleRoot { index: u32 },
+    /// The block holds more transactions or bytes than the chain's limits allow
+    BlockTooLarge { index: u32, reason: LimitError },
+    /// The coinbase is missing, misplaced, repeated or pays out too much
+    InvalidCoinbase { index: u32, reason: CoinbaseError },
+    /// The block was mined at a different difficulty than the chain requires
+    InvalidDifficulty { expected: usize, found: usize },
+    /// The block's compact target differs from the one retargeting requires
+    InvalidTarget { expected: u32, found: u32 },
+    /// The block's version differs from its parent's
+    InvalidVersion { expected: u32, found: u32 },
+    /// The block's index doesn't follow the current tip
+    InvalidIndex { expected: u32, found: u32 },
+    /// A block with this hash is already known
+    DuplicateBlock { hash: String },
+    /// The timestamp isn't later than the median of the previous blocks
+    TimestampTooOld {
+        index: u32,
+        timestamp: u64,
+        median_time_past: u64,
+    },
+    /// The timestamp is further ahead of the node's clock than the allowed drift
+    TimestampTooFarAhead {
+        index: u32,
+        timestamp: u64,
+        max_allowed: u64,
+    },
+    /// The block repeats a transaction that is already confirmed or appears twice
+    DuplicateTransaction { index: u32, tx_id: String },
+    /// A transaction in the block is unsigned or badly signed
+    InvalidSignature { index: u32, tx_id: String },
+    /// A UTXO transaction in the block spends outputs it may not
+    InvalidSpend {
+        index: u32,
+        tx_id: String,
+        reason: UtxoError,
+    },
+    /// A transaction in the block costs more than its sender's account balance
+    Overdraft {
+        index: u32,
+        tx_id: String,
+        balance: f64,
+        required: f64,
+    },
+    /// A transaction in the block expired before the block's timestamp
+    ExpiredTransaction {
+        index: u32,
+        tx_id: String,
+        expires_at: u64,
+    },
+    /// The block is valid but couldn't be written to the attached store
+    Storage { index: u32, message: String },
+}
+
+impl fmt::Display for BlockchainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockchainError::MiningFailed { index } => {
+                write!(f, "block {} does not have a valid proof of work", index)
+            }
+            BlockchainError::Unsealed { index } => {
+                write!(f, "block {} arrived unsealed", index)
+            }
+            BlockchainError::SealFailed { index, reason } => {
+                write!(f, "block {} could not be sealed: {}", index, reason)
+            }
+            BlockchainError::InvalidMerkleRoot { index } => {
+                write!(
+                    f,
+                    "block {} merkle root does not match its transactions",
+                    index
+                )
+            }
+            BlockchainError::BlockTooLarge { index, reason } => {
+                write!(f, "block {} is too large: {}", index, reason)
+            }
+            BlockchainError::InvalidCoinbase { index, reason } => {
+                write!(f, "block {} has an invalid coinbase: {}", index, reason)
+            }
+            BlockchainError::InvalidDifficulty { expected, found } => {
+                write!(f, "expected difficulty {}, found {}", expected, found)
+            }
+            BlockchainError::InvalidTarget { expected, found } => {
+                write!(
+                    f,
+                    "expected target bits {:#010x}, found {:#010x}",
+                    expected, found
+                )
+            }
+            BlockchainError::InvalidVersion { expected, found } => {
+                write!(f, "expected block version {}, found {}", expected, found)
+            }
+            BlockchainError::InvalidIndex { expected, found } => {
+                write!(f, "expected block index {}, found {}", expected, found)
+            }
+            BlockchainError::DuplicateBlock { hash } => {
+                write!(f, "block {} is already known", hash)
+            }
+            BlockchainError::TimestampTooOld {
+                index,
+                timestamp,
+                median_time_past,
+            } => write!(
+                f,
+                "block {} timestamp {} is not after the median time past {}",
+                index, timestamp, median_time_past
+            ),
+            BlockchainError::TimestampTooFarAhead {
+                index,
+                timestamp,
+                max_allowed,
+            } => write!(
+                f,
+                "block {} timestamp {} is later than the allowed {}",
+                index, timestamp, max_allowed
+            ),
+            BlockchainError::DuplicateTransaction { index, tx_id } => write!(
+                f,
+                "block {} repeats already confirmed transaction {}",
+                index, tx_id
+            ),
+            BlockchainError::InvalidSignature { index, tx_id } => write!(
+                f,
+                "block {} transaction {} has an invalid signature",
+                index, tx_id
+            ),
+            BlockchainError::InvalidSpend {
+                index,
+                tx_id,
+                reason,
+            } => write!(f, "block {} transaction {}: {}", index, tx_id, reason),
+            BlockchainError::Overdraft {
+                index,
+                tx_id,
+                balance,
+                required,
+            } => write!(
+                f,
+                "block {} transaction {} needs {} but its sender holds {}",
+                index, tx_id, required, balance
+            ),
+            BlockchainError::ExpiredTransaction {
+                index,
+                tx_id,
+                expires_at,
+            } => write!(
+                f,
+                "block {} transaction {} expired at {}",
+                index, tx_id, expires_at
+            ),
+            BlockchainError::Storage { index, message } => {
+                write!(f, "block {} could not be stored: {}", index, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockchainError {}
+
+/// The first problem found by `Blockchain::validate`, with the chain position of the bad block
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainValidationError {
+    /// The chain has no blocks, not even a genesis block
+    MissingGenesis,
+    /// The chain's retargeting settings can't be used
+    InvalidConfig(DifficultyConfigError),
+    /// The stored hash doesn't match the hash recomputed from the block's fields
+    HashMismatch { index: u32 },
+    /// The stored merkle root doesn't match the block's transactions
+    MerkleRootMismatch { index: u32 },
+    /// The block holds more transactions or bytes than the chain's limits allow
+    BlockTooLarge { index: u32, reason: LimitError },
+    /// The coinbase is missing, misplaced, repeated or pays out too much
+    InvalidCoinbase { index: u32, reason: CoinbaseError },
+    /// The block records a different difficulty than retargeting requires
+    InvalidDifficulty {
+        index: u32,
+        expected: usize,
+        found: usize,
+    },
+    /// The block records a different compact target than retargeting requires
+    InvalidTarget {
+        index: u32,
+        expected: u32,
+        found: u32,
+    },
+    /// The hash is above the block's target
+    InsufficientWork { index: u32 },
+    /// The block's version differs from the genesis block's
+    InvalidVersion {
+        index: u32,
+        expected: u32,
+        found: u32,
+    },
+    /// The consensus engine rejected the block's seal
+    InvalidSeal { index: u32 },
+    /// `previous_hash` doesn't match the hash of the block before it
+    BrokenLink { index: u32 },
+    /// The block's index isn't its position in the chain
+    InvalidIndex { index: u32, found: u32 },
+    /// The block contains a transaction already confirmed earlier in the chain
+    DuplicateTransaction { index: u32, tx_id: String },
+    /// A transaction in the block is unsigned or badly signed
+    InvalidSignature { index: u32, tx_id: String },
+    /// A UTXO transaction in the block spends outputs it may not
+    InvalidSpend {
+        index: u32,
+        tx_id: String,
+        reason: UtxoError,
+    },
+    /// A transaction in the block costs more than its sender's account balance
+    Overdraft {
+        index: u32,
+        tx_id: String,
+        balance: f64,
+        required: f64,
+    },
+    /// A transaction in the block expired before the block's timestamp
+    ExpiredTransaction {
+        index: u32,
+        tx_id: String,
+        expires_at: u64,
+    },
+    /// The timestamp isn't later than the median of the blocks before it
+    TimestampTooOld {
+        index: u32,
+        timestamp: u64,
+        median_time_past: u64,
+    },
+    /// The timestamp is further ahead of the node's clock than the allowed drift
+    TimestampTooFarAhead {
+        index: u32,
+        timestamp: u64,
+        max_allowed: u64,
+    },
+}
+
+impl ChainValidationError {
+    /// Position in the chain of the block that failed validation
+    pub fn index(&self) -> u32 {
+        match self {
+            ChainValidationError::MissingGenesis | ChainValidationError::InvalidConfig(_) => 0,
+            ChainValidationError::HashMismatch { index }
+            | ChainValidationError::MerkleRootMismatch { index }
+            | ChainValidationError::BlockTooLarge { index, .. }
+            | ChainValidationError::InvalidCoinbase { index, .. }
+            | ChainValidationError::InvalidDifficulty { index, .. }
+            | ChainValidationError::InvalidTarget { index, .. }
+            | ChainValidationError::InsufficientWork { index }
+            | ChainValidationError::InvalidVersion { index, .. }
+            | ChainValidationError::InvalidSeal { index }
+            | ChainValidationError::BrokenLink { index }
+            | ChainValidationError::InvalidIndex { index, .. }
+            | ChainValidationError::DuplicateTransaction { index, .. }
+            | ChainValidationError::InvalidSignature { index, .. }
+            | ChainValidationError::InvalidSpend { index, .. }
+            | ChainValidationError::Overdraft { index, .. }
+            | ChainValidationError::ExpiredTransaction { index, .. }
+            | ChainValidationError::TimestampTooOld { index, .. }
+            | ChainValidationError::TimestampTooFarAhead { index, .. } => *index,
+        }
+    }
+}
+
+impl fmt::Display for ChainValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainValidationError::MissingGenesis => write!(f, "chain has no genesis block"),
+            ChainValidationError::InvalidConfig(e) => {
+                write!(f, "invalid difficulty settings: {}", e)
+            }
+            ChainValidationError::HashMismatch { index } => {
+                write!(
+                    f,
+                    "block {}: stored hash does not match its contents",
+                    index
+                )
+            }
+            ChainValidationError::MerkleRootMismatch { index } => {
+                write!(
+                    f,
+                    "block {}: merkle root does not match its transactions",
+                    index
+                )
+            }
+            ChainValidationError::BlockTooLarge { index, reason } => {
+                write!(f, "block {}: too large: {}", index, reason)
+            }
+            ChainValidationError::InvalidCoinbase { index, reason } => {
+                write!(f, "block {}: {}", index, reason)
+            }
+            ChainValidationError::InvalidDifficulty {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "block {}: mined at difficulty {} but {} was required",
+                index, found, expected
+            ),
+            ChainValidationError::InvalidTarget {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "block {}: target bits {:#010x} but {:#010x} were required",
+                index, found, expected
+            ),
+            ChainValidationError::InsufficientWork { index } => {
+                write!(f, "block {}: hash does not meet the target", index)
+            }
+            ChainValidationError::InvalidVersion {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "block {}: version {} but the chain uses {}",
+                index, found, expected
+            ),
+            ChainValidationError::InvalidSeal { index } => {
+                write!(f, "block {}: seal rejected by the consensus rules", index)
+            }
+            ChainValidationError::BrokenLink { index } => {
+                write!(
+                    f,
+                    "block {}: previous hash does not match block {}",
+                    index,
+                    index - 1
+                )
+            }
+            ChainValidationError::InvalidIndex { index, found } => {
+                write!(f, "block {}: has index {}", index, found)
+            }
+            ChainValidationError::DuplicateTransaction { index, tx_id } => {
+                write!(f, "block {}: repeats transaction {}", index, tx_id)
+            }
+            ChainValidationError::InvalidSignature { index, tx_id } => {
+                write!(f, "block {}: transaction {} is badly signed", index, tx_id)
+            }
+            ChainValidationError::InvalidSpend {
+                index,
+                tx_id,
+                reason,
+            } => write!(f, "block {}: transaction {}: {}", index, tx_id, reason),
+            ChainValidationError::Overdraft {
+                index,
+                tx_id,
+                balance,
+                required,
+            } => write!(
+                f,
+                "block {}: transaction {} needs {} but its sender holds {}",
+                index, tx_id, required, balance
+            ),
+            ChainValidationError::ExpiredTransaction {
+                index,
+                tx_id,
+                expires_at,
+            } => write!(
+                f,
+                "block {}: transaction {} expired at {}",
+                index, tx_id, expires_at
+            ),
+            ChainValidationError::TimestampTooOld {
+                index,
+                timestamp,
+                median_time_past,
+            } => write!(
+                f,
+                "block {}: timestamp {} is not after the median time past {}",
+                index, timestamp, median_time_past
+            ),
+            ChainValidationError::TimestampTooFarAhead {
+                index,
+                timestamp,
+                max_allowed,
+            } => write!(
+                f,
+                "block {}: timestamp {} is later than the allowed {}",
+                index, timestamp, max_allowed
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChainValidationError {}
+
+/// Reasons `Blockchain::load_from_file`, `ChainStore::load` or `Mempool::load_from_file`
+/// can fail
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be read
+    Io(io::Error),
+    /// The file isn't valid JSON of the expected shape
+    Parse(serde_json::Error),
+    /// The binary file is truncated, corrupt or of an unknown version
+    Decode(DecodeError),
+    /// The file parsed but the chain failed validation
+    Invalid(ChainValidationError),
+    /// A block in the block log was refused when replayed
+    Rejected(BlockchainError),
+    /// The file parsed but a stored transaction was refused by the mempool
+    Mempool(MempoolError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "could not read chain file: {}", e),
+            LoadError::Parse(e) => write!(f, "could not parse chain file: {}", e),
+            LoadError::Decode(e) => write!(f, "could not decode chain file: {}", e),
+            LoadError::Invalid(e) => write!(f, "chain file is invalid: {}", e),
+            LoadError::Rejected(e) => write!(f, "block log is invalid: {}", e),
+            LoadError::Mempool(e) => write!(f, "mempool file is invalid: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(e: serde_json::Error) -> Self {
+        LoadError::Parse(e)
+    }
+}
+
+impl From<DecodeError> for LoadError {
+    fn from(e: DecodeError) -> Self {
+        LoadError::Decode(e)
+    }
+}
+
+impl From<ChainValidationError> for LoadError {
+    fn from(e: ChainValidationError) -> Self {
+        LoadError::Invalid(e)
+    }
+}
+
+impl From<MempoolError> for LoadError {
+    fn from(e: MempoolError) -> Self {
+        LoadError::Mempool(e)
+    }
+}
+
+/// What `Blockchain::add_block` did with an accepted block
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainUpdate {
+    /// The block became the new tip of the best chain
+    Extended,
+    /// The block was stored on a branch with no more work than the best chain
+    SideChain,
+    /// The block's branch overtook the best chain
+    Reorganized(Reorg),
+    /// The block's parent is unknown, so it waits in the orphan pool
+    Orphaned,
+}
+
+/// A switch of the best chain onto a competing branch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reorg {
+    pub old_tip: String,
+    pub new_tip: String,
+    pub depth: usize, // Blocks abandoned from the old best chain
+    /// Transactions confirmed only on the abandoned branch, to put back in the mempool
+    pub orphaned_transactions: Vec<Transaction>,
+}
+
+/// Represents the blockchain: the best chain of blocks plus competing branches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blockchain {
+    chain: Vec<Block>, // Best chain, genesis first
+    #[serde(default)]
+    pub difficulty_config: DifficultyConfig,
+    #[serde(default)]
+    pub reward_schedule: RewardSchedule,
+    #[serde(default)]
+    pub block_limits: BlockLimits,
+    /// Known blocks that are not on the best chain, by hash
+    #[serde(default)]
+    side_blocks: HashMap<String, Block>,
+    /// How far ahead of this node's clock a block timestamp may be; local policy, not saved
+    #[serde(skip, default = "default_max_future_drift")]
+    pub max_future_drift_secs: u64,
+    /// Engine that seals and verifies blocks; `None` means proof of work at the retargeted
+    /// difficulty. Not saved, so chains loaded from a file use proof of work.
+    #[serde(skip)]
+    consensus: Option<Arc<dyn Consensus>>,
+    /// Position on the best chain of each block, by hash; rebuilt from `chain` on load
+    #[serde(skip)]
+    block_positions: HashMap<String, usize>,
+    /// Ids of every confirmed transaction, rebuilt from `chain` on load
+    #[serde(skip)]
+    confirmed_transactions: HashSet<String>,
+    /// Unspent outputs of the best chain, rebuilt from `chain` on load
+    #[serde(skip)]
+    utxo_set: UtxoSet,
+    /// Confirmed balance of every address on the best chain, rebuilt from `chain` on load
+    #[serde(skip)]
+    balances: HashMap<String, f64>,
+    /// Blocks waiting for a parent this node hasn't seen; local state, not saved
+    #[serde(skip)]
+    orphans: OrphanPool,
+    /// Time source for new blocks and the future drift limit; not saved
+    #[serde(skip, default = "default_clock")]
+    clock: Arc<dyn Clock>,
+    /// Block log every accepted block is appended to, shared with clones
+    #[serde(skip)]
+    store: Option<Arc<Mutex<ChainStore>>>,
+    /// Subscribers to new blocks and reorgs, shared with clones
+    #[serde(skip)]
+    events: EventBus,
+}
+
+impl Blockchain {
+    /// Creates a new blockchain with a mined genesis block and default retargeting
+    pub fn new() -> Blockchain {
+        Blockchain::with_difficulty_config(DifficultyConfig::default())
+    }
+
+    /// Creates a new blockchain whose genesis block and later blocks are stamped by
+    /// `clock`. Retargeting follows block timestamps, so it follows the clock too.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Blockchain {
+        let mut blockchain = Blockchain::with_genesis(GenesisConfig {
+            timestamp: clock.now_secs(),
+            ..GenesisConfig::default()
+        });
+        blockchain.clock = clock;
+        blockchain
+    }
+
+    /// Replaces the time source for new blocks and the future drift limit
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Returns a receiver for the blocks joining the best chain and the reorgs from
+    /// now on; see `EventBus` for what happens when it falls behind
+    pub fn subscribe(&self) -> Receiver<ChainEvent> {
+        self.events.subscribe()
+    }
+
+    /// The bus chain events are published on, e.g. to share with a `Mempool`
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Writes the chain into `store`, which must be empty, and from then on appends
+    /// every accepted block to it. Use `ChainStore::load` to reopen it.
+    pub fn attach_store(&mut self, mut store: ChainStore) -> io::Result<()> {
+        store.write_chain(self)?;
+        self.set_store(store);
+        Ok(())
+    }
+
+    pub(crate) fn set_store(&mut self, store: ChainStore) {
+        self.store = Some(Arc::new(Mutex::new(store)));
+    }
+
+    /// Creates a new blockchain whose difficulty is retargeted according to `config`
+    pub fn with_difficulty_config(config: DifficultyConfig) -> Blockchain {
+        let genesis = GenesisConfig {
+            difficulty: config.initial_difficulty,
+            ..GenesisConfig::default()
+        };
+        Blockchain::from_genesis_block(genesis.build_block(), config)
+    }
+
+    /// Creates a new blockchain starting from the genesis block described by `config`,
+    /// with default retargeting from the genesis difficulty
+    pub fn with_genesis(config: GenesisConfig) -> Blockchain {
+        let difficulty_config = DifficultyConfig {
+            initial_difficulty: config.difficulty,
+            ..DifficultyConfig::default()
+        };
+        Blockchain::from_genesis_block(config.build_block(), difficulty_config)
+    }
+
+    /// Creates a new blockchain whose blocks, genesis included, are sealed and verified
+    /// by `consensus` instead of retargeted proof of work
+    pub fn with_consensus(
+        genesis: GenesisConfig,
+        consensus: impl Consensus + 'static,
+    ) -> Result<Blockchain, SealError> {
+        let mut genesis_block = genesis.unsealed_block();
+        consensus.seal(&mut genesis_block)?;
+
+        let mut blockchain =
+            Blockchain::from_genesis_block(genesis_block, DifficultyConfig::default());
+        blockchain.consensus = Some(Arc::new(consensus));
+        Ok(blockchain)
+    }
+
+    pub(crate) fn from_genesis_block(
+        genesis_block: Block,
+        difficulty_config: DifficultyConfig,
+    ) -> Blockchain {
+        let mut blockchain = Blockchain {
+            chain: vec![genesis_block], // Initialize with genesis block
+            difficulty_config,
+            reward_schedule: RewardSchedule::default(),
+            block_limits: BlockLimits::default(),
+            side_blocks: HashMap::new(),
+            max_future_drift_secs: MAX_FUTURE_DRIFT_SECS,
+            consensus: None,
+            block_positions: HashMap::new(),
+            confirmed_transactions: HashSet::new(),
+            utxo_set: UtxoSet::new(),
+            balances: HashMap::new(),
+            orphans: OrphanPool::default(),
+            clock: default_clock(),
+            store: None,
+            events: EventBus::new(),
+        };
+        blockchain.rebuild_indexes();
+        blockchain
+    }
+
+    /// Recomputes the block hash index, the set of confirmed transaction ids, the
+    /// UTXO set and the confirmed balances from `chain`
+    fn rebuild_indexes(&mut self) {
+        self.block_positions = self
+            .chain
+            .iter()
+            .enumerate()
+            .map(|(position, block)| (block.hash.clone(), position))
+            .collect();
+        self.confirmed_transactions = self
+            .chain
+            .iter()
+            .flat_map(|block| block.transaction_hashes())
+            .collect();
+        self.utxo_set = UtxoSet::from_blocks(&self.chain);
+        self.balances = balances_in(&self.chain);
+    }
+
+    /// Unspent outputs of the best chain
+    pub fn utxo_set(&self) -> &UtxoSet {
+        &self.utxo_set
+    }
+
+    /// Returns true if a transaction with this id is already in the chain
+    pub fn contains_transaction(&self, tx_id: &str) -> bool {
+        self.confirmed_transactions.contains(tx_id)
+    }
+
+    /// Difficulty the next block must be mined at
+    pub fn current_difficulty(&self) -> usize {
+        self.difficulty_config.next_difficulty(&self.chain)
+    }
+
+    /// Compact target the next block's hash must not exceed, if the chain uses targets
+    pub fn current_bits(&self) -> u32 {
+        self.difficulty_config.next_bits(&self.chain)
+    }
+
+    /// Engine for the block that follows `parents`
+    fn engine(&self, parents: &[Block]) -> Arc<dyn Consensus> {
+        match &self.consensus {
+            Some(consensus) => Arc::clone(consensus),
+            None => Arc::new(ProofOfWork {
+                difficulty: self.difficulty_config.next_difficulty(parents),
+                bits: self.difficulty_config.next_bits(parents),
+            }),
+        }
+    }
+
+    /// Latest timestamp a block may carry right now
+    pub fn max_allowed_timestamp(&self) -> u64 {
+        self.clock
+            .now_secs()
+            .saturating_add(self.max_future_drift_secs)
+    }
+
+    /// Mining statistics of the best chain
+    pub fn stats(&self) -> ChainStats {
+        ChainStats::from_blocks(&self.chain)
+    }
+
+    /// Returns the best (highest cumulative work) branch, genesis first
+    pub fn best_chain(&self) -> &[Block] {
+        &self.chain
+    }
+
+    /// Returns the last block of the best chain
+    pub fn tip(&self) -> &Block {
+        self.chain
+            .last()
+            .expect("chain always contains a genesis block")
+    }
+
+    /// Returns the last block of the best chain
+    pub fn latest_block(&self) -> &Block {
+        self.tip()
+    }
+
+    /// Returns the block at `index` on the best chain
+    pub fn get_block_by_index(&self, index: u32) -> Option<&Block> {
+        self.chain.get(index as usize)
+    }
+
+    /// Returns the block on the best chain with this hash
+    pub fn get_block_by_hash(&self, hash: &str) -> Option<&Block> {
+        self.position_of(hash).map(|position| &self.chain[position])
+    }
+
+    /// Iterates over the best chain, genesis first
+    pub fn iter(&self) -> std::slice::Iter<'_, Block> {
+        self.chain.iter()
+    }
+
+    /// Finds the confirmed transaction with this id and the block that contains it
+    pub fn find_transaction(&self, tx_id: &str) -> Option<(&Block, &Transaction)> {
+        if !self.contains_transaction(tx_id) {
+            return None;
+        }
+        self.chain.iter().find_map(|block| {
+            block
+                .transactions
+                .iter()
+                .find(|tx| tx.calculate_hash() == tx_id)
+                .map(|tx| (block, tx))
+        })
+    }
+
+    /// Header of the block at `index` on the best chain, for light clients
+    pub fn get_header(&self, index: u32) -> Option<BlockHeader> {
+        self.get_block_by_index(index).map(Block::header)
+    }
+
+    /// Index of the block holding the confirmed transaction `tx_id` and the proof
+    /// that its merkle root includes it, for light clients
+    pub fn get_merkle_proof(&self, tx_id: &str) -> Option<(u32, MerkleProof)> {
+        let (block, _) = self.find_transaction(tx_id)?;
+        let hashes = block.transaction_hashes();
+        let position = hashes.iter().position(|hash| hash == tx_id)?;
+        Some((block.index, merkle::merkle_proof(&hashes, position)))
+    }
+
+    /// Every stored block, best chain and side branches, with parents before children
+    pub(crate) fn blocks_by_height(&self) -> Vec<&Block> {
+        let mut blocks: Vec<&Block> = self.chain.iter().chain(self.side_blocks.values()).collect();
+        blocks.sort_by_key(|block| block.index);
+        blocks
+    }
+
+    /// Number of known blocks that are not on the best chain
+    pub fn side_block_count(&self) -> usize {
+        self.side_blocks.len()
+    }
+
+    /// Number of blocks waiting in the orphan pool for their parent
+    pub fn orphan_count(&self) -> usize {
+        self.orphans.len()
+    }
+
+    /// Blocks waiting for their parent, e.g. to inspect while debugging a sync
+    pub fn orphans(&self) -> &OrphanPool {
+        &self.orphans
+    }
+
+    /// Limits of the orphan pool, such as its size and how long orphans wait
+    pub fn orphans_mut(&mut self) -> &mut OrphanPool {
+        &mut self.orphans
+    }
+
+    /// Mines (if needed), validates and stores a block whose `previous_hash` points
+    /// at any known block. A block with an empty `previous_hash` is linked to the tip.
+    ///
+    /// Blocks on a competing branch are kept aside until that branch has more
+    /// cumulative work than the best chain, at which point the chain reorganizes
+    /// onto it. Ties keep the current tip.
+    ///
+    /// A block whose parent is unknown is kept unchecked in the orphan pool. Once
+    /// its parent is stored it is validated and connected like any other block, and
+    /// the returned update covers every orphan connected along the way.
+    ///
+    /// Sealing is for the node's own blocks; blocks from other nodes go through
+    /// `accept_block`.
+    pub fn add_block(&mut self, mut new_block: Block) -> Result<ChainUpdate, BlockchainError> {
+        let tip_hash = self.tip().hash.clone();
+        let tip_height = self.tip().index;
+        if new_block.previous_hash.is_empty() {
+            new_block.previous_hash = tip_hash.clone();
+        }
+
+        if self.orphans.contains(&new_block.hash) {
+            return Err(BlockchainError::DuplicateBlock {
+                hash: new_block.hash,
+            });
+        }
+        if self.position_of(&new_block.previous_hash).is_none()
+            && !self.side_blocks.contains_key(&new_block.previous_hash)
+        {
+            self.orphans.insert(new_block, self.clock.now_secs());
+            return Ok(ChainUpdate::Orphaned);
+        }
+
+        let hash = self.connect_block(new_block)?;
+        self.connect_orphans(hash);
+        let update = self.update_since(&tip_hash);
+        self.publish(tip_height, &update);
+        Ok(update)
+    }
+
+    /// Validates and stores a block received from another node. Unlike `add_block`
+    /// it never seals: an unmined block is refused rather than mined, or signed with
+    /// this node's authority key.
+    pub fn accept_block(&mut self, block: Block) -> Result<ChainUpdate, BlockchainError> {
+        if !block.mined {
+            return Err(BlockchainError::Unsealed { index: block.index });
+        }
+        self.add_block(block)
+    }
+
+    /// Publishes `update` to subscribers: the reorg, if any, then every block that
+    /// joined the best chain above the old tip's height minus the reorg depth
+    fn publish(&self, old_height: u32, update: &ChainUpdate) {
+        let first_new = match update {
+            ChainUpdate::Extended => old_height + 1,
+            ChainUpdate::Reorganized(reorg) => {
+                self.events.publish(ChainEvent::Reorg {
+                    old_tip: reorg.old_tip.clone(),
+                    new_tip: reorg.new_tip.clone(),
+                    depth: reorg.depth,
+                });
+                old_height + 1 - reorg.depth as u32
+            }
+            ChainUpdate::SideChain | ChainUpdate::Orphaned => return,
+        };
+        for block in &self.chain[first_new as usize..] {
+            self.events.publish(ChainEvent::BlockAdded {
+                height: block.index,
+                hash: block.hash.clone(),
+            });
+        }
+    }
+
+    /// Validates and stores a block whose parent is known, returning its hash
+    fn connect_block(&mut self, mut new_block: Block) -> Result<String, BlockchainError> {
+        let tip_hash = self.tip().hash.clone();
+        if self.position_of(&new_block.hash).is_some()
+            || self.side_blocks.contains_key(&new_block.hash)
+        {
+            return Err(BlockchainError::DuplicateBlock {
+                hash: new_block.hash,
+            });
+        }
+
+        // Fast path: the block extends the best chain
+        if new_block.previous_hash == tip_hash {
+            self.check_block(
+                &mut new_block,
+                &self.chain,
+                &self.confirmed_transactions,
+                &self.utxo_set,
+                &self.balances,
+            )?;
+            self.persist(&new_block, &new_block)?;
+            self.confirmed_transactions
+                .extend(new_block.transaction_hashes());
+            self.utxo_set.apply_block(&new_block);
+            for transaction in &new_block.transactions {
+                apply_to_balances(&mut self.balances, transaction);
+            }
+            let hash = new_block.hash.clone();
+            self.block_positions.insert(hash.clone(), self.chain.len());
+            self.chain.push(new_block);
+            return Ok(hash);
+        }
+
+        let mut branch = self
+            .branch_to(&new_block.previous_hash)
+            .expect("the parent is a known block");
+        let branch_transactions: HashSet<String> = branch
+            .iter()
+            .flat_map(|block| block.transaction_hashes())
+            .collect();
+        let branch_utxos = UtxoSet::from_blocks(&branch);
+        let branch_balances = balances_in(&branch);
+        self.check_block(
+            &mut new_block,
+            &branch,
+            &branch_transactions,
+            &branch_utxos,
+            &branch_balances,
+        )?;
+        let hash = new_block.hash.clone();
+        branch.push(new_block);
+
+        let overtakes = chain_work(&branch) > chain_work(&self.chain);
+        let new_block = branch.last().expect("block was just pushed");
+        self.persist(new_block, if overtakes { new_block } else { self.tip() })?;
+        if overtakes {
+            self.reorganize(branch);
+        } else {
+            let block = branch.pop().expect("block was just pushed");
+            self.side_blocks.insert(block.hash.clone(), block);
+        }
+        Ok(hash)
+    }
+
+    /// Appends a checked block to the attached store, if any, before it's connected,
+    /// and records `tip` as the tip it leads to
+    fn persist(&self, block: &Block, tip: &Block) -> Result<(), BlockchainError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let mut store = store.lock().expect("chain store lock poisoned");
+        store
+            .append(block)
+            .and_then(|()| store.write_head(tip))
+            .map_err(|e| BlockchainError::Storage {
+                index: block.index,
+                message: e.to_string(),
+            })
+    }
+
+    /// Connects the orphans waiting on `hash`, then the orphans waiting on those.
+    /// Orphans were stored unchecked, so the ones that fail validation are dropped.
+    fn connect_orphans(&mut self, hash: String) {
+        let mut parents = vec![hash];
+        while let Some(parent) = parents.pop() {
+            for child in self.orphans.take_children(&parent) {
+                if let Ok(hash) = self.connect_block(child) {
+                    parents.push(hash);
+                }
+            }
+        }
+    }
+
+    /// Describes how the best chain changed since `old_tip` was its tip
+    fn update_since(&self, old_tip: &str) -> ChainUpdate {
+        if self.tip().hash == old_tip {
+            ChainUpdate::SideChain
+        } else if self.position_of(old_tip).is_some() {
+            ChainUpdate::Extended
+        } else {
+            ChainUpdate::Reorganized(self.reorg_from(old_tip))
+        }
+    }
+
+    /// Checks (mining first if needed) that `block` can follow the last block of
+    /// `parents`, where `confirmed` holds the transaction ids already in `parents`,
+    /// `utxos` their unspent outputs and `balances` the balances they leave
+    fn check_block(
+        &self,
+        block: &mut Block,
+        parents: &[Block],
+        confirmed: &HashSet<String>,
+        utxos: &UtxoSet,
+        balances: &HashMap<String, f64>,
+    ) -> Result<(), BlockchainError> {
+        let parent = parents
+            .last()
+            .expect("a branch starts at the genesis block");
+        let difficulty = self.difficulty_config.next_difficulty(parents);
+        let bits = self.difficulty_config.next_bits(parents);
+
+        if block.index != parent.index + 1 {
+            return Err(BlockchainError::InvalidIndex {
+                expected: parent.index + 1,
+                found: block.index,
+            });
+        }
+
+        if block.version != parent.version {
+            return Err(BlockchainError::InvalidVersion {
+                expected: parent.version,
+                found: block.version,
+            });
+        }
+
+        if block.merkle_root != block.calculate_merkle_root() {
+            return Err(BlockchainError::InvalidMerkleRoot { index: block.index });
+        }
+        self.block_limits
+            .check(block)
+            .map_err(|reason| BlockchainError::BlockTooLarge {
+                index: block.index,
+                reason,
+            })?;
+        self.reward_schedule
+            .check_coinbase(block)
+            .map_err(|reason| BlockchainError::InvalidCoinbase {
+                index: block.index,
+                reason,
+            })?;
+
+        if let Some(transaction) = block
+            .transactions
+            .iter()
+            .find(|tx| tx.verify_signature().is_err())
+        {
+            return Err(BlockchainError::InvalidSignature {
+                index: block.index,
+                tx_id: transaction.calculate_hash(),
+            });
+        }
+        utxos
+            .check_block(block)
+            .map_err(|(tx_id, reason)| BlockchainError::InvalidSpend {
+                index: block.index,
+                tx_id,
+                reason,
+            })?;
+        if let Some((tx_id, balance, required)) = first_overdraft(block, |address| {
+            balances.get(address).copied().unwrap_or(0.0)
+        }) {
+            return Err(BlockchainError::Overdraft {
+                index: block.index,
+                tx_id,
+                balance,
+                required,
+            });
+        }
+
+        // An unmined block is ours to stamp, so move it past the median instead of refusing it
+        let median = median_time_past(parents);
+        if !block.mined {
+            block.timestamp = block.timestamp.max(median + 1);
+        }
+        if block.timestamp <= median {
+            return Err(BlockchainError::TimestampTooOld {
+                index: block.index,
+                timestamp: block.timestamp,
+                median_time_past: median,
+            });
+        }
+        let max_allowed = self.max_allowed_timestamp();
+        if block.timestamp > max_allowed {
+            return Err(BlockchainError::TimestampTooFarAhead {
+                index: block.index,
+                timestamp: block.timestamp,
+                max_allowed,
+            });
+        }
+        if let Some((tx_id, expires_at)) = first_expired(block) {
+            return Err(BlockchainError::ExpiredTransaction {
+                index: block.index,
+                tx_id,
+                expires_at,
+            });
+        }
+
+        // Seal the new block unless it arrives already sealed
+        let engine = self.engine(parents);
+        if !block.mined {
+            engine
+                .seal(block)
+                .map_err(|reason| BlockchainError::SealFailed {
+                    index: block.index,
+                    reason,
+                })?;
+        }
+
+        // Only retargeted proof of work ties the difficulty to the chain
+        if self.consensus.is_none() && block.difficulty != difficulty {
+            return Err(BlockchainError::InvalidDifficulty {
+                expected: difficulty,
+                found: block.difficulty,
+            });
+        }
+        if self.consensus.is_none() && block.uses_target() && block.bits != bits {
+            return Err(BlockchainError::InvalidTarget {
+                expected: bits,
+                found: block.bits,
+            });
+        }
+
+        if !block.mined || !engine.verify(block) {
+            return Err(BlockchainError::MiningFailed { index: block.index });
+        }
+
+        let mut block_ids = HashSet::new();
+        for tx_id in block.transaction_hashes() {
+            if confirmed.contains(&tx_id) || !block_ids.insert(tx_id.clone()) {
+                return Err(BlockchainError::DuplicateTransaction {
+                    index: block.index,
+                    tx_id,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Position of the block with this hash on the best chain
+    fn position_of(&self, hash: &str) -> Option<usize> {
+        self.block_positions.get(hash).copied().filter(|&position| {
+            self.chain
+                .get(position)
+                .is_some_and(|block| block.hash == hash)
+        })
+    }
+
+    /// Every block from genesis up to and including the known block `hash`
+    fn branch_to(&self, hash: &str) -> Option<Vec<Block>> {
+        let mut side = Vec::new();
+        let mut current = hash;
+        loop {
+            if let Some(position) = self.position_of(current) {
+                let mut branch = self.chain[..=position].to_vec();
+                branch.extend(side.into_iter().rev());
+                return Some(branch);
+            }
+            let block = self.side_blocks.get(current)?;
+            side.push(block.clone());
+            current = &block.previous_hash;
+        }
+    }
+
+    /// Makes `branch` the best chain, moving the abandoned blocks aside
+    fn reorganize(&mut self, branch: Vec<Block>) {
+        let fork = self
+            .chain
+            .iter()
+            .zip(&branch)
+            .take_while(|(old, new)| old.hash == new.hash)
+            .count();
+
+        let abandoned: Vec<Block> = self.chain.drain(fork..).collect();
+        for block in &branch[fork..] {
+            self.side_blocks.remove(&block.hash);
+        }
+        self.chain = branch;
+        self.rebuild_indexes();
+        for block in abandoned {
+            self.side_blocks.insert(block.hash.clone(), block);
+        }
+    }
+
+    /// Describes the switch away from `old_tip`, whose branch now lies among the side blocks
+    fn reorg_from(&self, old_tip: &str) -> Reorg {
+        let mut abandoned = Vec::new();
+        let mut current = old_tip;
+        while self.position_of(current).is_none() {
+            let block = &self.side_blocks[current];
+            abandoned.push(block);
+            current = &block.previous_hash;
+        }
+        abandoned.reverse();
+
+        // Transactions only the abandoned branch confirmed need to be re-queued
+        let orphaned_transactions = abandoned
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .filter(|tx| !tx.is_coinbase() && !self.contains_transaction(&tx.calculate_hash()))
+            .cloned()
+            .collect();
+
+        Reorg {
+            old_tip: old_tip.to_string(),
+            new_tip: self.tip().hash.clone(),
+            depth: abandoned.len(),
+            orphaned_transactions,
+        }
+    }
+
+    /// Drains the mempool into a new block, prepends the miner's reward
+    /// transaction, mines the block and appends it to the chain.
+    ///
+    /// Pending transactions are applied in order against the confirmed balances;
+    /// any that would overdraw their sender, or that are already confirmed, are dropped.
+    pub fn mine_pending_transactions(
+        &mut self,
+        mempool: &mut Mempool,
+        miner_address: &str,
+    ) -> Result<&Block, BlockchainError> {
+        let block = self.prepare_block(mempool, miner_address);
+        self.add_block(block)?;
+        Ok(self.tip())
+    }
+
+    /// Drains the mempool, highest fee first, into an unmined block on top of the
+    /// current tip, ready to be mined (e.g. with `Block::mine_block_parallel`) and
+    /// passed to `add_block`. The coinbase pays the block reward plus all fees.
+    ///
+    /// Only as many transactions as `block_limits` allow are taken; the rest go back
+    /// to the mempool for later blocks.
+    pub fn prepare_block(&self, mempool: &mut Mempool, miner_address: &str) -> Block {
+        let tip = self.tip();
+        let height = tip.index + 1;
+
+        let timestamp = self.clock.now_secs().max(median_time_past(&self.chain) + 1);
+
+        // Replays of confirmed transactions are dropped rather than paid twice, and the
+        // block may be stamped past the clock, so anything it would outlive is dropped too
+        let pending: Vec<Transaction> = mempool
+            .get_transactions(mempool.len())
+            .into_iter()
+            .filter(|tx| !self.contains_transaction(&tx.calculate_hash()))
+            .filter(|tx| !tx.is_expired_at(timestamp))
+            .collect();
+        let spendable = self.spendable_transactions(self.affordable_transactions(pending));
+        let (included, deferred) = self.block_limits.fit(spendable);
+        mempool.requeue(deferred);
+        let fees: f64 = included.iter().map(|tx| tx.fee).sum();
+
+        let mut coinbase = Transaction::coinbase(
+            miner_address,
+            self.reward_schedule.reward_at(height) + fees,
+            height,
+        );
+        coinbase.timestamp = timestamp;
+        let mut transactions = vec![coinbase];
+        transactions.extend(included);
+
+        let mut block = Block::new(height, tip.hash.clone(), transactions);
+        block.version = tip.version;
+        block.timestamp = timestamp;
+        block.difficulty = self.current_difficulty();
+        block.bits = if block.uses_target() {
+            self.current_bits()
+        } else {
+            0
+        };
+        block
+    }
+
+    /// Keeps the account-model transactions and the UTXO transactions whose inputs
+    /// are still unspent, counting spends earlier in the same batch
+    fn spendable_transactions(&self, pending: Vec<Transaction>) -> Vec<Transaction> {
+        let mut utxos = self.utxo_set.clone();
+        let mut spendable = Vec::new();
+        for transaction in pending {
+            if transaction.is_utxo() && utxos.check_transaction(&transaction).is_err() {
+                continue;
+            }
+            utxos.apply_transaction(&transaction);
+            spendable.push(transaction);
+        }
+        spendable
+    }
+
+    /// Keeps the transactions whose sender can cover them, counting earlier
+    /// transactions in the same batch
+    fn affordable_transactions(&self, pending: Vec<Transaction>) -> Vec<Transaction> {
+        let mut balances: HashMap<String, f64> = HashMap::new();
+        let mut affordable = Vec::new();
+
+        for transaction in pending {
+            let sender_balance = *balances
+                .entry(transaction.sender.to_string())
+                .or_insert_with(|| self.get_balance(&transaction.sender));
+            if transaction.amount <= 0.0 || sender_balance < transaction.total_cost() {
+                continue;
+            }
+
+            balances.insert(
+                transaction.sender.to_string(),
+                sender_balance - transaction.total_cost(),
+            );
+            let recipient_balance = balances
+                .entry(transaction.recipient.to_string())
+                .or_insert_with(|| self.get_balance(&transaction.recipient));
+            *recipient_balance += transaction.amount;
+            affordable.push(transaction);
+        }
+        affordable
+    }
+
+    /// Confirmed balance of an address; same as `get_confirmed_balance`
+    pub fn get_balance(&self, address: &str) -> f64 {
+        self.get_confirmed_balance(address)
+    }
+
+    /// What `address` can still spend: its confirmed balance less the amounts and
+    /// fees it has pending in `mempool`. Pending credits aren't counted, since they
+    /// may never confirm.
+    pub fn get_available_balance(&self, address: &str, mempool: &Mempool) -> f64 {
+        let (outgoing, _) = mempool.pending_for(address);
+        self.get_confirmed_balance(address) - outgoing
+    }
+
+    /// Returns the confirmed balance of an address: everything received
+    /// (including mining rewards and fees) minus everything sent and paid in fees
+    pub fn get_confirmed_balance(&self, address: &str) -> f64 {
+        self.balances.get(address).copied().unwrap_or(0.0)
+    }
+
+    /// Coins in circulation on the best chain: everything minted by coinbases, less the
+    /// fees they collected, since those were paid out of existing balances
+    pub fn total_supply(&self) -> f64 {
+        let mut supply = 0.0;
+        for transaction in self.chain.iter().flat_map(|block| &block.transactions) {
+            if transaction.is_coinbase() {
+                supply += transaction.amount;
+            } else {
+                supply -= transaction.fee;
+            }
+        }
+        supply
+    }
+
+    /// Returns true if every block in the chain passes `validate`
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// Walks the chain from genesis, recomputing hashes and checking proof of work,
+    /// signatures, links and indices. Returns the first failure found.
+    pub fn validate(&self) -> Result<(), ChainValidationError> {
+        if self.chain.is_empty() {
+            return Err(ChainValidationError::MissingGenesis);
+        }
+        self.difficulty_config
+            .validate()
+            .map_err(ChainValidationError::InvalidConfig)?;
+        let max_allowed = self.max_allowed_timestamp();
+        let mut seen_transactions = HashSet::new();
+        let mut utxos = UtxoSet::new();
+        let mut balances: HashMap<String, f64> = HashMap::new();
+        for (position, block) in self.chain.iter().enumerate() {
+            let index = position as u32;
+
+            if block.index != index {
+                return Err(ChainValidationError::InvalidIndex {
+                    index,
+                    found: block.index,
+                });
+            }
+
+            if block.version != self.chain[0].version {
+                return Err(ChainValidationError::InvalidVersion {
+                    index,
+                    expected: self.chain[0].version,
+                    found: block.version,
+                });
+            }
+
+            if block.merkle_root != block.calculate_merkle_root() {
+                return Err(ChainValidationError::MerkleRootMismatch { index });
+            }
+            if let Err(reason) = self.block_limits.check(block) {
+                return Err(ChainValidationError::BlockTooLarge { index, reason });
+            }
+            if let Err(reason) = self.reward_schedule.check_coinbase(block) {
+                return Err(ChainValidationError::InvalidCoinbase { index, reason });
+            }
+
+            if let Some(transaction) = block
+                .transactions
+                .iter()
+                .find(|tx| tx.verify_signature().is_err())
+            {
+                return Err(ChainValidationError::InvalidSignature {
+                    index,
+                    tx_id: transaction.calculate_hash(),
+                });
+            }
+            if let Err((tx_id, reason)) = utxos.check_block(block) {
+                return Err(ChainValidationError::InvalidSpend {
+                    index,
+                    tx_id,
+                    reason,
+                });
+            }
+            utxos.apply_block(block);
+            if let Some((tx_id, balance, required)) = first_overdraft(block, |address| {
+                balances.get(address).copied().unwrap_or(0.0)
+            }) {
+                return Err(ChainValidationError::Overdraft {
+                    index,
+                    tx_id,
+                    balance,
+                    required,
+                });
+            }
+            for transaction in &block.transactions {
+                apply_to_balances(&mut balances, transaction);
+            }
+
+            if block.hash != block.calculate_hash() {
+                return Err(ChainValidationError::HashMismatch { index });
+            }
+
+            if position > 0 {
+                let median = median_time_past(&self.chain[..position]);
+                if block.timestamp <= median {
+                    return Err(ChainValidationError::TimestampTooOld {
+                        index,
+                        timestamp: block.timestamp,
+                        median_time_past: median,
+                    });
+                }
+            }
+            if block.timestamp > max_allowed {
+                return Err(ChainValidationError::TimestampTooFarAhead {
+                    index,
+                    timestamp: block.timestamp,
+                    max_allowed,
+                });
+            }
+            if let Some((tx_id, expires_at)) = first_expired(block) {
+                return Err(ChainValidationError::ExpiredTransaction {
+                    index,
+                    tx_id,
+                    expires_at,
+                });
+            }
+
+            let parents = &self.chain[..position];
+            if self.consensus.is_none() {
+                let expected = self.difficulty_config.next_difficulty(parents);
+                if block.difficulty != expected {
+                    return Err(ChainValidationError::InvalidDifficulty {
+                        index,
+                        expected,
+                        found: block.difficulty,
+                    });
+                }
+                let expected = self.difficulty_config.next_bits(parents);
+                if block.uses_target() && block.bits != expected {
+                    return Err(ChainValidationError::InvalidTarget {
+                        index,
+                        expected,
+                        found: block.bits,
+                    });
+                }
+                if !self.engine(parents).verify(block) {
+                    return Err(ChainValidationError::InsufficientWork { index });
+                }
+            } else if !self.engine(parents).verify(block) {
+                return Err(ChainValidationError::InvalidSeal { index });
+            }
+
+            if position > 0 && block.previous_hash != self.chain[position - 1].hash {
+                return Err(ChainValidationError::BrokenLink { index });
+            }
+
+            for tx_id in block.transaction_hashes() {
+                if !seen_transactions.insert(tx_id.clone()) {
+                    return Err(ChainValidationError::DuplicateTransaction { index, tx_id });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the chain to `path` as pretty-printed JSON
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    /// Writes the chain to `path` in `format`
+    pub fn save_to_file_as(&self, path: &Path, format: ChainFormat) -> io::Result<()> {
+        match format {
+            ChainFormat::Json => self.save_to_file(path),
+            ChainFormat::Binary => fs::write(path, self.to_bytes()),
+        }
+    }
+
+    /// Reads a chain written by `save_to_file` or `save_to_file_as`, in either
+    /// format, and validates it before returning it
+    pub fn load_from_file(path: &Path) -> Result<Blockchain, LoadError> {
+        let bytes = fs::read(path)?;
+        let mut blockchain = match ChainFormat::detect(&bytes) {
+            ChainFormat::Binary => Blockchain::from_bytes(&bytes)?,
+            ChainFormat::Json => serde_json::from_slice(&bytes)?,
+        };
+        blockchain.validate()?;
+        blockchain.rebuild_indexes();
+        blockchain.revalidate_side_blocks();
+        Ok(blockchain)
+    }
+
+    /// Checks the side blocks of a loaded chain against their parents, as
+    /// `accept_block` would have when they arrived. Side blocks that fail, or whose
+    /// parent was dropped, are dropped too, so reorgs only weigh work that was checked.
+    fn revalidate_side_blocks(&mut self) {
+        let mut side_blocks: Vec<Block> = std::mem::take(&mut self.side_blocks)
+            .into_values()
+            .collect();
+        side_blocks.sort_by_key(|block| block.index);
+        for block in side_blocks {
+            let parent_known = self.position_of(&block.previous_hash).is_some()
+                || self.side_blocks.contains_key(&block.previous_hash);
+            if parent_known {
+                let _ = self.accept_block(block);
+            }
+        }
+    }
+
+    /// Writes a snapshot of the chain into the directory `dir` for another copy of
+    /// the tool to import; see `snapshot::export`
+    pub fn export_snapshot(&self, dir: &Path) -> io::Result<SnapshotManifest> {
+        snapshot::export(self, dir)
+    }
+
+    /// Reads and fully validates a snapshot written by `export_snapshot`
+    pub fn import_snapshot(dir: &Path) -> Result<Blockchain, SnapshotError> {
+        snapshot::import(dir, None)
+    }
+
+    /// Like `import_snapshot`, but refuses a chain whose genesis block isn't
+    /// `expected_genesis`
+    pub fn import_snapshot_with_genesis(
+        dir: &Path,
+        expected_genesis: &str,
+    ) -> Result<Blockchain, SnapshotError> {
+        snapshot::import(dir, Some(expected_genesis))
+    }
+
+    /// Binary chain file contents: the magic, the format version, then the retargeting,
+    /// reward and size settings, the best chain and the side blocks in canonical encoding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.raw(&CHAIN_FILE_MAGIC);
+        encoder.u8(CHAIN_FILE_VERSION);
+        self.difficulty_config.encode(&mut encoder);
+        self.reward_schedule.encode(&mut encoder);
+        self.block_limits.encode(&mut encoder);
+        self.chain.encode(&mut encoder);
+        let mut side_blocks: Vec<Block> = self.side_blocks.values().cloned().collect();
+        side_blocks.sort_by(|a, b| a.hash.cmp(&b.hash));
+        side_blocks.encode(&mut encoder);
+        encoder.into_bytes()
+    }
+
+    /// Reads a chain written by `to_bytes`, without validating it
+    pub fn from_bytes(bytes: &[u8]) -> Result<Blockchain, DecodeError> {
+        let mut decoder = Decoder::new(bytes);
+        if decoder.take(CHAIN_FILE_MAGIC.len())? != CHAIN_FILE_MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        let version = decoder.u8()?;
+        if version != 1 && version != CHAIN_FILE_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let difficulty_config = DifficultyConfig::decode(&mut decoder)?;
+        let reward_schedule = RewardSchedule::decode(&mut decoder)?;
+        let block_limits = if version == 1 {
+            BlockLimits::default()
+        } else {
+            BlockLimits::decode(&mut decoder)?
+        };
+        let chain: Vec<Block> = Decode::decode(&mut decoder)?;
+        let side_blocks: Vec<Block> = Decode::decode(&mut decoder)?;
+        decoder.finish()?;
+        if chain.is_empty() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        let mut blockchain = Blockchain::from_genesis_block(chain[0].clone(), difficulty_config);
+        blockchain.reward_schedule = reward_schedule;
+        blockchain.block_limits = block_limits;
+        blockchain.chain = chain;
+        blockchain.side_blocks = side_blocks
+            .into_iter()
+            .map(|block| (block.hash.clone(), block))
+            .collect();
+        blockchain.rebuild_indexes();
+        Ok(blockchain)
+    }
+
+    /// Returns the total number of blocks in the blockchain
+    pub fn get_total_blocks(&self) -> usize {
+        self.chain.len()
+    }
+}
+
+/// Median timestamp of the last `MEDIAN_TIME_SPAN` blocks (or all of them if fewer);
+/// the next block's timestamp must be greater than this
+pub fn median_time_past(blocks: &[Block]) -> u64 {
+    let start = blocks.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut timestamps: Vec<u64> = blocks[start..]
+        .iter()
+        .map(|block| block.timestamp)
+        .collect();
+    if timestamps.is_empty() {
+        return 0;
+    }
+    timestamps.sort_unstable();
+    timestamps[timestamps.len() / 2]
+}
+
+fn default_max_future_drift() -> u64 {
+    MAX_FUTURE_DRIFT_SECS
+}
+
+/// Balance of every address after `blocks`: everything received minus everything
+/// sent and paid in fees
+fn balances_in(blocks: &[Block]) -> HashMap<String, f64> {
+    let mut balances = HashMap::new();
+    for transaction in blocks.iter().flat_map(|block| &block.transactions) {
+        apply_to_balances(&mut balances, transaction);
+    }
+    balances
+}
+
+/// Credits the recipient and, unless it's a coinbase, debits the sender for `transaction`
+fn apply_to_balances(balances: &mut HashMap<String, f64>, transaction: &Transaction) {
+    *balances
+        .entry(transaction.recipient.to_string())
+        .or_insert(0.0) += transaction.amount;
+    if !transaction.is_coinbase() {
+        *balances
+            .entry(transaction.sender.to_string())
+            .or_insert(0.0) -= transaction.total_cost();
+    }
+}
+
+/// Id, sender balance and cost of the first transaction in `block` whose sender
+/// can't cover it. `balance_of` gives balances before the block; earlier
+/// transactions in the block count too. UTXO spends are held to the account balance
+/// as well, so coins moved by an account transfer can't be spent again as outputs.
+fn first_overdraft(block: &Block, balance_of: impl Fn(&str) -> f64) -> Option<(String, f64, f64)> {
+    let mut balances: HashMap<String, f64> = HashMap::new();
+    for transaction in &block.transactions {
+        for address in [&transaction.sender, &transaction.recipient] {
+            if !balances.contains_key(address.as_str()) {
+                balances.insert(address.to_string(), balance_of(address));
+            }
+        }
+        let balance = balances[transaction.sender.as_str()];
+        if !transaction.is_coinbase() && balance < transaction.total_cost() {
+            return Some((
+                transaction.calculate_hash(),
+                balance,
+                transaction.total_cost(),
+            ));
+        }
+        apply_to_balances(&mut balances, transaction);
+    }
+    None
+}
+
+/// Id and expiry of the first transaction that expired before `block`'s timestamp
+fn first_expired(block: &Block) -> Option<(String, u64)> {
+    block.transactions.iter().find_map(|tx| {
+        tx.expires_at
+            .filter(|_| tx.is_expired_at(block.timestamp))
+            .map(|expires_at| (tx.calculate_hash(), expires_at))
+    })
+}
+
+fn default_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+// Compares chain data only; engines can't be compared and the indexes follow from `chain`
+impl PartialEq for Blockchain {
+    fn eq(&self, other: &Self) -> bool {
+        self.chain == other.chain
+            && self.difficulty_config == other.difficulty_config
+            && self.reward_schedule == other.reward_schedule
+            && self.block_limits == other.block_limits
+            && self.side_blocks == other.side_blocks
+            && self.max_future_drift_secs == other.max_future_drift_secs
+    }
+}
+
+impl<'a> IntoIterator for &'a Blockchain {
+    type Item = &'a Block;
+    type IntoIter = std::slice::Iter<'a, Block>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use crate::block::{hash_meets_difficulty, DIFFICULTY};
+    use crate::consensus::ProofOfAuthority;
+    use crate::difficulty::retarget;
+    use crate::reward::BLOCK_REWARD;
+    use crate::target::Target;
+    use ed25519_dalek::SigningKey;
+
+    /// An unmined block at `index` holding a coinbase to Miner, then `transactions`
+    fn new_block(index: u32, previous_hash: String, transactions: Vec<Transaction>) -> Block {
+        let mut block_transactions = vec![Transaction::coinbase("Miner", BLOCK_REWARD, index)];
+        block_transactions.extend(transactions);
+        Block::new(index, previous_hash, block_transactions)
+    }
+
+    /// An unmined block whose coinbase funds Alice for her payment to Bob
+    fn block(index: u32) -> Block {
+        Block::new(
+            index,
+            String::new(),
+            vec![
+                Transaction::coinbase("Alice", BLOCK_REWARD, index),
+                Transaction::new("Alice", "Bob", index as f64),
+            ],
+        )
+    }
+
+    fn chain_of(length: u32) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for index in 1..length {
+            blockchain.add_block(block(index)).unwrap();
+        }
+        blockchain
+    }
+
+    #[test]
+    fn genesis_block_is_mined() {
+        let blockchain = Blockchain::new();
+        let genesis = &blockchain.chain[0];
+        assert!(genesis.mined);
+        assert!(hash_meets_difficulty(&genesis.hash, DIFFICULTY));
+        assert_eq!(genesis.difficulty, DIFFICULTY);
+    }
+
+    #[test]
+    fn add_block_links_to_tip() {
+        let mut blockchain = Blockchain::new();
+        let genesis_hash = blockchain.chain[0].hash.clone();
+        assert_eq!(blockchain.add_block(block(1)), Ok(ChainUpdate::Extended));
+        let added = blockchain.tip();
+        assert_eq!(added.previous_hash, genesis_hash);
+        assert!(added.mined);
+    }
+
+    #[test]
+    fn add_block_rejects_wrong_index() {
+        let mut blockchain = Blockchain::new();
+        assert_eq!(
+            blockchain.add_block(block(2)).unwrap_err(),
+            BlockchainError::InvalidIndex {
+                expected: 1,
+                found: 2
+            }
+        );
+        assert_eq!(blockchain.get_total_blocks(), 1);
+    }
+
+    #[test]
+    fn blocks_with_unknown_parents_wait_as_orphans() {
+        let mut blockchain = Blockchain::new();
+        let stale = new_block(1, String::from("not a known block"), Vec::new());
+        assert_eq!(
+            blockchain.add_block(stale.clone()).unwrap(),
+            ChainUpdate::Orphaned
+        );
+        assert_eq!(blockchain.orphan_count(), 1);
+        assert_eq!(blockchain.get_total_blocks(), 1);
+        assert!(matches!(
+            blockchain.add_block(stale),
+            Err(BlockchainError::DuplicateBlock { .. })
+        ));
+    }
+
+    /// A chain and the blocks a copy of it mined on top, not yet delivered to it
+    fn chain_and_descendants(count: usize) -> (Blockchain, Vec<Block>) {
+        let blockchain = Blockchain::new();
+        let mut source = blockchain.clone();
+        let mut mempool = Mempool::new();
+        for _ in 0..count {
+            source
+                .mine_pending_transactions(&mut mempool, "Miner")
+                .unwrap();
+        }
+        (blockchain, source.best_chain()[1..].to_vec())
+    }
+
+    #[test]
+    fn blocks_delivered_in_reverse_connect_once_the_first_arrives() {
+        let (mut blockchain, blocks) = chain_and_descendants(3);
+        assert_eq!(
+            blockchain.add_block(blocks[2].clone()).unwrap(),
+            ChainUpdate::Orphaned
+        );
+        assert_eq!(
+            blockchain.add_block(blocks[1].clone()).unwrap(),
+            ChainUpdate::Orphaned
+        );
+        assert_eq!(blockchain.orphan_count(), 2);
+        assert!(blockchain
+            .orphans()
+            .iter()
+            .any(|orphan| orphan.block.hash == blocks[2].hash));
+
+        assert_eq!(
+            blockchain.add_block(blocks[0].clone()).unwrap(),
+            ChainUpdate::Extended
+        );
+        assert_eq!(blockchain.orphan_count(), 0);
+        assert_eq!(&blockchain.best_chain()[1..], blocks.as_slice());
+        assert_eq!(blockchain.validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_orphans_are_dropped_when_their_parent_arrives() {
+        let (mut blockchain, blocks) = chain_and_descendants(2);
+        let mut forged = blocks[1].clone();
+        forged.nonce += 1; // The stored hash no longer matches the work
+        blockchain.add_block(forged).unwrap();
+        assert_eq!(blockchain.orphan_count(), 1);
+
+        blockchain.add_block(blocks[0].clone()).unwrap();
+        assert_eq!(blockchain.orphan_count(), 0);
+        assert_eq!(blockchain.get_total_blocks(), 2);
+        assert_eq!(blockchain.tip().hash, blocks[0].hash);
+        assert_eq!(blockchain.side_block_count(), 0);
+    }
+
+    #[test]
+    fn add_block_rejects_stale_merkle_root() {
+        let mut blockchain = Blockchain::new();
+        let mut tampered = block(1);
+        tampered
+            .transactions
+            .push(Transaction::new("Mallory", "Mallory", 1.0));
+        assert_eq!(
+            blockchain.add_block(tampered).unwrap_err(),
+            BlockchainError::InvalidMerkleRoot { index: 1 }
+        );
+    }
+
+    #[test]
+    fn add_block_rejects_block_without_valid_work() {
+        let mut blockchain = Blockchain::new();
+        let mut forged = block(1);
+        forged.previous_hash = blockchain.chain[0].hash.clone();
+        forged.timestamp = blockchain.tip().timestamp + 1;
+        forged.mined = true;
+        forged.hash = "f".repeat(64);
+        assert_eq!(
+            blockchain.add_block(forged).unwrap_err(),
+            BlockchainError::MiningFailed { index: 1 }
+        );
+        assert_eq!(blockchain.get_total_blocks(), 1);
+    }
+
+    #[test]
+    fn blocks_and_transactions_can_be_looked_up() {
+        let blockchain = chain_of(20);
+        assert_eq!(blockchain.latest_block().index, 19);
+        assert_eq!(blockchain.iter().count(), 20);
+        assert!((&blockchain)
+            .into_iter()
+            .enumerate()
+            .all(|(position, block)| block.index == position as u32));
+
+        for index in [0, 7, 19] {
+            let block = blockchain.get_block_by_index(index).unwrap();
+            assert_eq!(block.index, index);
+            assert_eq!(blockchain.get_block_by_hash(&block.hash), Some(block));
+        }
+        assert_eq!(blockchain.get_block_by_index(20), None);
+        assert_eq!(blockchain.get_block_by_hash("missing"), None);
+
+        let payment = &blockchain.get_block_by_index(12).unwrap().transactions[0];
+        let (block, found) = blockchain
+            .find_transaction(&payment.calculate_hash())
+            .unwrap();
+        assert_eq!(block.index, 12);
+        assert_eq!(found, payment);
+        let unknown = Transaction::new("Alice", "Bob", 1000.0);
+        assert_eq!(blockchain.find_transaction(&unknown.calculate_hash()), None);
+    }
+
+    #[test]
+    fn fresh_chain_is_valid() {
+        let blockchain = chain_of(5);
+        assert!(blockchain.is_valid());
+        assert_eq!(blockchain.validate(), Ok(()));
+    }
+
+    #[test]
+    fn tampered_data_fails_at_that_block() {
+        let mut blockchain = chain_of(5);
+        blockchain.chain[2].transactions[0].recipient = Address::unchecked("Mallory");
+        assert!(!blockchain.is_valid());
+        let error = blockchain.validate().unwrap_err();
+        assert_eq!(error, ChainValidationError::MerkleRootMismatch { index: 2 });
+        assert_eq!(error.index(), 2);
+
+        // Fixing up the merkle root still leaves the stored hash stale
+        blockchain.chain[2].merkle_root = blockchain.chain[2].calculate_merkle_root();
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::HashMismatch { index: 2 })
+        );
+    }
+
+    #[test]
+    fn rehashed_tampered_block_breaks_the_next_link() {
+        let mut blockchain = chain_of(5);
+        let tampered = &mut blockchain.chain[2];
+        tampered.transactions[0].recipient = Address::unchecked("Mallory");
+        tampered.merkle_root = tampered.calculate_merkle_root();
+        tampered.mine_block().unwrap();
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::BrokenLink { index: 3 })
+        );
+    }
+
+    #[test]
+    fn validate_detects_insufficient_work_and_bad_index() {
+        let mut blockchain = chain_of(3);
+        blockchain.chain[1].index = 7;
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::InvalidIndex { index: 1, found: 7 })
+        );
+
+        let mut blockchain = chain_of(3);
+        let block = &mut blockchain.chain[2];
+        while hash_meets_difficulty(&block.hash, block.difficulty) {
+            block.nonce += 1;
+            block.hash = block.calculate_hash();
+        }
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::InsufficientWork { index: 2 })
+        );
+    }
+
+    /// Mines `blocks` empty blocks paying their rewards to `miner`
+    fn funded_chain(miner: &str, blocks: usize) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        let mut mempool = Mempool::new();
+        for _ in 0..blocks {
+            blockchain
+                .mine_pending_transactions(&mut mempool, miner)
+                .unwrap();
+        }
+        blockchain
+    }
+
+    #[test]
+    fn mine_pending_transactions_includes_drained_transactions_and_reward() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        let pending = vec![
+            Transaction::new("Alice", "Bob", 5.0),
+            Transaction::new("Bob", "Carol", 2.5),
+        ];
+        for transaction in &pending {
+            mempool.add_transaction(transaction.clone()).unwrap();
+        }
+
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+
+        assert_eq!(block.index, 2);
+        assert_eq!(block.transactions.len(), 3);
+        let reward = &block.transactions[0];
+        assert!(reward.is_coinbase());
+        assert_eq!(reward.recipient, "Miner");
+        assert_eq!(reward.amount, BLOCK_REWARD);
+        assert_eq!(&block.transactions[1..], &pending[..]);
+        assert!(mempool.is_empty());
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn balances_count_rewards_and_transfers() {
+        let mut blockchain = funded_chain("Alice", 2);
+        assert_eq!(blockchain.get_balance("Alice"), 2.0 * BLOCK_REWARD);
+        assert_eq!(blockchain.get_balance("Bob"), 0.0);
+
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 50.0))
+            .unwrap();
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Carol")
+            .unwrap();
+
+        assert_eq!(blockchain.get_balance("Alice"), 2.0 * BLOCK_REWARD - 50.0);
+        assert_eq!(blockchain.get_balance("Bob"), 50.0);
+        assert_eq!(blockchain.get_balance("Carol"), BLOCK_REWARD);
+    }
+
+    #[test]
+    fn identical_genesis_configs_agree_and_allocations_are_spendable() {
+        let config = GenesisConfig::new(1_700_000_000, 2).with_allocation("Alice", 1000.0);
+        let mut blockchain = Blockchain::with_genesis(config.clone());
+        let other = Blockchain::with_genesis(config);
+        assert_eq!(blockchain.chain[0].hash, other.chain[0].hash);
+        assert!(blockchain.is_valid());
+        assert_eq!(blockchain.get_balance("Alice"), 1000.0);
+
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 400.0))
+            .unwrap();
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(blockchain.get_balance("Alice"), 600.0);
+        assert_eq!(blockchain.get_balance("Bob"), 400.0);
+    }
+
+    #[test]
+    fn coinbase_follows_reward_schedule() {
+        let mut blockchain = Blockchain::new();
+        blockchain.reward_schedule = RewardSchedule {
+            initial_reward: BLOCK_REWARD,
+            halving_interval: 2,
+        };
+        let mut mempool = Mempool::new();
+        for _ in 0..3 {
+            blockchain
+                .mine_pending_transactions(&mut mempool, "Miner")
+                .unwrap();
+        }
+
+        let rewards: Vec<f64> = blockchain.chain[1..]
+            .iter()
+            .map(|block| block.transactions[0].amount)
+            .collect();
+        assert_eq!(rewards, vec![BLOCK_REWARD, 68.5, 68.5]);
+        assert_eq!(blockchain.total_supply(), rewards.iter().sum::<f64>());
+        assert_eq!(blockchain.get_balance("Miner"), blockchain.total_supply());
+    }
+
+    #[test]
+    fn coinbases_paying_more_than_the_schedule_are_rejected() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 10.0).with_fee(2.0))
+            .unwrap();
+        let honest = blockchain.prepare_block(&mut mempool, "Miner");
+        assert_eq!(honest.transactions[0].amount, BLOCK_REWARD + 2.0);
+
+        let mut greedy = honest.clone();
+        greedy.transactions[0].amount += 0.5;
+        greedy.merkle_root = greedy.calculate_merkle_root();
+        assert_eq!(
+            blockchain.add_block(greedy).unwrap_err(),
+            BlockchainError::InvalidCoinbase {
+                index: 2,
+                reason: CoinbaseError::ExcessiveReward {
+                    paid: BLOCK_REWARD + 2.5,
+                    allowed: BLOCK_REWARD + 2.0,
+                },
+            }
+        );
+        blockchain.add_block(honest).unwrap();
+    }
+
+    #[test]
+    fn fees_do_not_add_to_supply() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 10.0).with_fee(2.0))
+            .unwrap();
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+
+        let balances: f64 = ["Alice", "Bob", "Miner"]
+            .iter()
+            .map(|address| blockchain.get_balance(address))
+            .sum();
+        assert_eq!(blockchain.total_supply(), 2.0 * BLOCK_REWARD);
+        assert_eq!(blockchain.total_supply(), balances);
+    }
+
+    #[test]
+    fn overspending_transactions_are_dropped() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Bob", "Carol", 1.0))
+            .unwrap();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", BLOCK_REWARD + 1.0))
+            .unwrap();
+
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(blockchain.get_balance("Alice"), BLOCK_REWARD);
+    }
+
+    #[test]
+    fn pending_spends_in_one_batch_cannot_exceed_balance() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        let first = Transaction::new("Alice", "Bob", 100.0);
+        mempool.add_transaction(first.clone()).unwrap();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Carol", 100.0))
+            .unwrap();
+
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(&block.transactions[1..], &[first]);
+        assert_eq!(blockchain.get_balance("Alice"), BLOCK_REWARD - 100.0);
+        assert_eq!(blockchain.get_balance("Carol"), 0.0);
+    }
+
+    #[test]
+    fn spending_the_entire_balance_is_allowed() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", BLOCK_REWARD))
+            .unwrap();
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(blockchain.get_balance("Alice"), 0.0);
+        assert_eq!(blockchain.get_balance("Bob"), BLOCK_REWARD);
+    }
+
+    /// Appends an empty block stamped `spacing` seconds after the tip
+    fn add_spaced_block(blockchain: &mut Blockchain, spacing: u64) {
+        let tip = blockchain.chain.last().unwrap();
+        let mut block = new_block(tip.index + 1, String::new(), Vec::new());
+        block.timestamp = tip.timestamp + spacing;
+        blockchain.add_block(block).unwrap();
+    }
+
+    #[test]
+    fn fast_blocks_lower_the_target() {
+        let mut blockchain = Blockchain::with_difficulty_config(DifficultyConfig {
+            adjustment_interval: 4,
+            ..DifficultyConfig::default()
+        });
+        for _ in 0..3 {
+            add_spaced_block(&mut blockchain, 1);
+        }
+        // Three seconds instead of thirty, so as much harder as one adjustment allows
+        let genesis_target = blockchain.chain[0].target().unwrap();
+        let target = Target::from_compact(blockchain.current_bits()).unwrap();
+        assert_eq!(target, retarget(&genesis_target, 3, 30));
+        assert!(target < genesis_target.scale(1, 3));
+        assert!(target >= genesis_target.scale(1, 4));
+
+        add_spaced_block(&mut blockchain, 1);
+        assert_eq!(blockchain.chain[4].bits, target.to_compact());
+        assert!(blockchain.chain[4].hash_meets_target());
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn blocks_with_the_wrong_target_are_rejected() {
+        let mut blockchain = Blockchain::new();
+        let tip = blockchain.chain.last().unwrap();
+        let mut block = new_block(tip.index + 1, tip.hash.clone(), Vec::new());
+        block.timestamp = tip.timestamp + 1;
+        block.bits = 0x2000_fffe;
+        block.mine_block().unwrap();
+        assert_eq!(
+            blockchain.add_block(block.clone()),
+            Err(BlockchainError::InvalidTarget {
+                expected: 0x2000_ffff,
+                found: 0x2000_fffe
+            })
+        );
+
+        // Slipped into the chain directly, validation finds it too
+        blockchain.chain.push(block);
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::InvalidTarget {
+                index: 1,
+                expected: 0x2000_ffff,
+                found: 0x2000_fffe
+            })
+        );
+    }
+
+    #[test]
+    fn slow_blocks_lower_difficulty() {
+        let mut blockchain = Blockchain::with_difficulty_config(DifficultyConfig {
+            adjustment_interval: 4,
+            ..DifficultyConfig::default()
+        });
+        for _ in 0..3 {
+            add_spaced_block(&mut blockchain, 100);
+        }
+        assert_eq!(blockchain.current_difficulty(), DIFFICULTY - 1);
+
+        add_spaced_block(&mut blockchain, 100);
+        assert_eq!(blockchain.chain[4].difficulty, DIFFICULTY - 1);
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn blocks_at_the_wrong_difficulty_are_rejected() {
+        let mut blockchain = Blockchain::new();
+        let mut easy = block(1);
+        easy.previous_hash = blockchain.chain[0].hash.clone();
+        easy.timestamp = blockchain.tip().timestamp + 1;
+        easy.mine_block_with_difficulty(1).unwrap();
+        assert_eq!(
+            blockchain.add_block(easy).unwrap_err(),
+            BlockchainError::InvalidDifficulty {
+                expected: DIFFICULTY,
+                found: 1
+            }
+        );
+
+        let mut blockchain = chain_of(3);
+        let block = &mut blockchain.chain[2];
+        block.mine_block_with_difficulty(DIFFICULTY + 1).unwrap();
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::InvalidDifficulty {
+                index: 2,
+                expected: DIFFICULTY,
+                found: DIFFICULTY + 1
+            })
+        );
+    }
+
+    #[test]
+    fn pending_transactions_beyond_the_limit_wait_for_later_blocks() {
+        let mut blockchain = funded_chain("Alice", 1);
+        blockchain.block_limits.max_transactions_per_block = 10;
+        let mut mempool = Mempool::new();
+        let pending: Vec<Transaction> = (1..=25)
+            .map(|i| Transaction::new("Alice", "Bob", 1.0).with_fee(i as f64 / 100.0))
+            .collect();
+        for transaction in &pending {
+            mempool.add_transaction(transaction.clone()).unwrap();
+        }
+
+        let mut by_priority = pending.clone();
+        by_priority.reverse();
+        for expected in by_priority.chunks(10) {
+            let block = blockchain
+                .mine_pending_transactions(&mut mempool, "Miner")
+                .unwrap();
+            assert!(block.transactions[0].is_coinbase());
+            assert_eq!(&block.transactions[1..], expected);
+        }
+        assert!(mempool.is_empty());
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn oversized_blocks_are_rejected() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        for i in 0..3 {
+            mempool
+                .add_transaction(Transaction::new("Alice", "Bob", 1.0).with_nonce(i))
+                .unwrap();
+        }
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+
+        blockchain.block_limits.max_transactions_per_block = 2;
+        let too_many = LimitError::TooManyTransactions { count: 3, max: 2 };
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::BlockTooLarge {
+                index: 2,
+                reason: too_many.clone(),
+            })
+        );
+
+        let tip = blockchain.tip().clone();
+        let mut block = tip.clone();
+        block.index += 1;
+        block.previous_hash = tip.hash.clone();
+        block.hash = String::new();
+        block.mined = false;
+        block.merkle_root = block.calculate_merkle_root();
+        assert_eq!(
+            blockchain.add_block(block).unwrap_err(),
+            BlockchainError::BlockTooLarge {
+                index: 3,
+                reason: too_many,
+            }
+        );
+
+        blockchain.block_limits = BlockLimits {
+            max_block_bytes: 100,
+            ..BlockLimits::default()
+        };
+        assert!(matches!(
+            blockchain.validate(),
+            Err(ChainValidationError::BlockTooLarge {
+                index: 2,
+                reason: LimitError::TooManyBytes { max: 100, .. },
+            })
+        ));
+    }
+
+    #[test]
+    fn fees_are_paid_to_the_miner() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 10.0).with_fee(1.5))
+            .unwrap();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Carol", 10.0).with_fee(2.5))
+            .unwrap();
+
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(block.transactions[0].amount, BLOCK_REWARD + 4.0);
+        assert_eq!(block.transactions[1].recipient, "Carol");
+
+        assert_eq!(blockchain.get_balance("Miner"), BLOCK_REWARD + 4.0);
+        assert_eq!(blockchain.get_balance("Alice"), BLOCK_REWARD - 24.0);
+        assert_eq!(blockchain.get_balance("Bob"), 10.0);
+    }
+
+    #[test]
+    fn fee_counts_towards_affordability() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", BLOCK_REWARD).with_fee(1.0))
+            .unwrap();
+
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(block.transactions[0].amount, BLOCK_REWARD);
+    }
+
+    #[test]
+    fn replayed_transactions_are_not_confirmed_twice() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        let payment = Transaction::new("Alice", "Bob", 10.0);
+        mempool.add_transaction(payment.clone()).unwrap();
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert!(blockchain.contains_transaction(&payment.calculate_hash()));
+
+        // The mempool no longer knows the id, so the replay is admitted but never mined
+        mempool.add_transaction(payment).unwrap();
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(blockchain.get_balance("Bob"), 10.0);
+    }
+
+    #[test]
+    fn repeated_payments_with_new_nonces_are_confirmed() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        let first = Transaction::new("Alice", "Bob", 10.0).with_nonce(1);
+        let mut second = first.clone().with_nonce(2);
+        second.timestamp = first.timestamp;
+        mempool.add_transaction(first).unwrap();
+        mempool.add_transaction(second).unwrap();
+
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(blockchain.get_balance("Bob"), 20.0);
+    }
+
+    #[test]
+    fn version_one_chain_files_load_with_default_limits() {
+        let blockchain = funded_chain("Alice", 2);
+        let mut bytes = blockchain.to_bytes();
+        let limits_at = CHAIN_FILE_MAGIC.len()
+            + 1
+            + crate::encoding::to_bytes(&blockchain.difficulty_config).len()
+            + crate::encoding::to_bytes(&blockchain.reward_schedule).len();
+        let limits_len = crate::encoding::to_bytes(&blockchain.block_limits).len();
+        bytes.drain(limits_at..limits_at + limits_len);
+        bytes[CHAIN_FILE_MAGIC.len()] = 1;
+
+        let loaded = Blockchain::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.block_limits, BlockLimits::default());
+        assert_eq!(loaded, blockchain);
+    }
+
+    #[test]
+    fn chain_files_round_trip_in_binary() {
+        let mut blockchain = funded_chain("Alice", 2);
+        let tip = blockchain.tip().clone();
+        add_child(&mut blockchain, &tip, "A", Vec::new());
+        add_child(&mut blockchain, &tip, "B", Vec::new());
+        assert_eq!(blockchain.side_block_count(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chain.bin");
+        blockchain
+            .save_to_file_as(&path, ChainFormat::Binary)
+            .unwrap();
+        assert_eq!(ChainFormat::of_file(&path), ChainFormat::Binary);
+        let loaded = Blockchain::load_from_file(&path).unwrap();
+        assert_eq!(loaded, blockchain);
+        assert_eq!(loaded.utxo_set(), blockchain.utxo_set());
+
+        let json_path = dir.path().join("chain.json");
+        blockchain.save_to_file(&json_path).unwrap();
+        assert_eq!(ChainFormat::of_file(&json_path), ChainFormat::Json);
+        assert!(fs::metadata(&path).unwrap().len() < fs::metadata(&json_path).unwrap().len());
+
+        let mut bytes = blockchain.to_bytes();
+        bytes[CHAIN_FILE_MAGIC.len()] = CHAIN_FILE_VERSION + 1;
+        assert_eq!(
+            Blockchain::from_bytes(&bytes).unwrap_err(),
+            DecodeError::UnsupportedVersion(CHAIN_FILE_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn mining_stats_are_kept_with_the_chain() {
+        let blockchain = funded_chain("Alice", 3);
+        let stats = blockchain.stats();
+        assert_eq!(stats.blocks.len(), blockchain.best_chain().len());
+        assert!(stats.blocks[1..]
+            .iter()
+            .all(|block| block.attempts.is_some()));
+        assert!(stats.total_attempts > 0);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chain.json");
+        blockchain.save_to_file(&path).unwrap();
+        assert_eq!(Blockchain::load_from_file(&path).unwrap().stats(), stats);
+    }
+
+    #[test]
+    fn chain_files_saved_before_block_versions_still_load() {
+        // Build a chain the way it was hashed before blocks had versions
+        let mut genesis = GenesisConfig::default().unsealed_block();
+        genesis.version = crate::block::LEGACY_BLOCK_VERSION;
+        genesis.mine_block().unwrap();
+        let mut blockchain = Blockchain::from_genesis_block(genesis, DifficultyConfig::default());
+        blockchain
+            .mine_pending_transactions(&mut Mempool::new(), "Alice")
+            .unwrap();
+
+        let mut json = serde_json::to_value(&blockchain).unwrap();
+        for block in json["chain"].as_array_mut().unwrap() {
+            block.as_object_mut().unwrap().remove("version");
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.json");
+        fs::write(&path, json.to_string()).unwrap();
+
+        let mut loaded = Blockchain::load_from_file(&path).unwrap();
+        assert_eq!(loaded, blockchain);
+        // New blocks keep the chain's version
+        let block = loaded
+            .mine_pending_transactions(&mut Mempool::new(), "Alice")
+            .unwrap();
+        assert_eq!(block.version, crate::block::LEGACY_BLOCK_VERSION);
+
+        let mut upgraded = new_block(3, loaded.tip().hash.clone(), Vec::new());
+        upgraded.timestamp = loaded.tip().timestamp + 1;
+        assert_eq!(
+            loaded.add_block(upgraded).unwrap_err(),
+            BlockchainError::InvalidVersion {
+                expected: crate::block::LEGACY_BLOCK_VERSION,
+                found: crate::block::BLOCK_VERSION
+            }
+        );
+    }
+
+    #[test]
+    fn chains_from_before_compact_targets_keep_counting_zeros() {
+        let mut genesis = GenesisConfig::default().unsealed_block();
+        genesis.version = crate::block::ZEROS_BLOCK_VERSION;
+        genesis.bits = 0;
+        genesis.mine_block().unwrap();
+        let mut blockchain = Blockchain::from_genesis_block(genesis, DifficultyConfig::default());
+        let block = blockchain
+            .mine_pending_transactions(&mut Mempool::new(), "Alice")
+            .unwrap();
+        assert_eq!(block.version, crate::block::ZEROS_BLOCK_VERSION);
+        assert_eq!(block.bits, 0);
+        assert!(hash_meets_difficulty(&block.hash, DIFFICULTY));
+
+        let json = serde_json::to_string(&blockchain).unwrap();
+        assert!(!json.contains("\"bits\""));
+        let from_json: Blockchain = serde_json::from_str(&json).unwrap();
+        let from_bytes = Blockchain::from_bytes(&blockchain.to_bytes()).unwrap();
+        for loaded in [from_json, from_bytes] {
+            assert_eq!(loaded, blockchain);
+            assert!(loaded.is_valid());
+        }
+    }
+
+    #[test]
+    fn blocks_with_forged_wallet_transactions_are_rejected() {
+        let wallet = crate::wallet::Wallet::generate();
+        let mut blockchain = funded_chain(&wallet.address(), 1);
+        let forged = Transaction::new(&wallet.address(), "Mallory", 50.0);
+
+        let block = new_block(2, blockchain.tip().hash.clone(), vec![forged.clone()]);
+        assert_eq!(
+            blockchain.add_block(block).unwrap_err(),
+            BlockchainError::InvalidSignature {
+                index: 2,
+                tx_id: forged.calculate_hash()
+            }
+        );
+
+        let mut signed = forged.clone();
+        wallet.sign_transaction(&mut signed).unwrap();
+        let block = new_block(2, blockchain.tip().hash.clone(), vec![signed]);
+        blockchain.add_block(block).unwrap();
+        assert_eq!(blockchain.get_balance("Mallory"), 50.0);
+
+        // Stripping the signature from a confirmed transaction breaks validation
+        blockchain.chain[2].transactions[1].signature = None;
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::InvalidSignature {
+                index: 2,
+                tx_id: forged.calculate_hash()
+            })
+        );
+    }
+
+    #[test]
+    fn blocks_repeating_confirmed_transactions_are_rejected() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        let payment = Transaction::new("Alice", "Bob", 10.0);
+        mempool.add_transaction(payment.clone()).unwrap();
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+
+        let replay = new_block(3, String::new(), vec![payment.clone()]);
+        assert_eq!(
+            blockchain.add_block(replay).unwrap_err(),
+            BlockchainError::DuplicateTransaction {
+                index: 3,
+                tx_id: payment.calculate_hash()
+            }
+        );
+
+        // A chain edited to contain the replay fails validation at that block
+        let mut replay = new_block(3, blockchain.chain[2].hash.clone(), vec![payment.clone()]);
+        replay.timestamp = blockchain.tip().timestamp + 1;
+        replay
+            .mine_block_with_difficulty(blockchain.current_difficulty())
+            .unwrap();
+        blockchain.chain.push(replay);
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::DuplicateTransaction {
+                index: 3,
+                tx_id: payment.calculate_hash()
+            })
+        );
+    }
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&rand::random::<[u8; 32]>())
+    }
+
+    /// Alice, funded at genesis, pays Bob, who pays part of it on to Carol
+    fn run_payment_scenario(mut blockchain: Blockchain) {
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 30.0))
+            .unwrap();
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        mempool
+            .add_transaction(Transaction::new("Bob", "Carol", 10.0))
+            .unwrap();
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+
+        assert_eq!(blockchain.get_total_blocks(), 3);
+        assert_eq!(blockchain.get_balance("Alice"), 70.0);
+        assert_eq!(blockchain.get_balance("Bob"), 20.0);
+        assert_eq!(blockchain.get_balance("Carol"), 10.0);
+        assert_eq!(blockchain.validate(), Ok(()));
+    }
+
+    #[test]
+    fn scenario_runs_under_every_consensus_engine() {
+        let genesis = GenesisConfig::new(1_700_000_000, 2).with_allocation("Alice", 100.0);
+        let authority = signing_key();
+        let authorities = vec![authority.verifying_key()];
+
+        run_payment_scenario(Blockchain::with_genesis(genesis.clone()));
+        run_payment_scenario(
+            Blockchain::with_consensus(genesis.clone(), ProofOfWork::new(3)).unwrap(),
+        );
+        run_payment_scenario(
+            Blockchain::with_consensus(
+                genesis,
+                ProofOfAuthority::new(authorities).with_signer(authority),
+            )
+            .unwrap(),
+        );
+    }
+
+    #[test]
+    fn blocks_signed_by_non_authorities_are_rejected() {
+        let authority = signing_key();
+        let mut blockchain = Blockchain::with_consensus(
+            GenesisConfig::new(1_700_000_000, 2),
+            ProofOfAuthority::new(vec![authority.verifying_key()]).with_signer(authority),
+        )
+        .unwrap();
+
+        let outsider = signing_key();
+        let outsider_engine =
+            ProofOfAuthority::new(vec![outsider.verifying_key()]).with_signer(outsider);
+        let mut block = blockchain.prepare_block(&mut Mempool::new(), "Mallory");
+        outsider_engine.seal(&mut block).unwrap();
+
+        assert_eq!(
+            blockchain.add_block(block.clone()).unwrap_err(),
+            BlockchainError::MiningFailed { index: 1 }
+        );
+        blockchain.chain.push(block);
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::InvalidSeal { index: 1 })
+        );
+    }
+
+    #[test]
+    fn peer_blocks_are_never_sealed_for_them() {
+        let authority = signing_key();
+        let mut blockchain = Blockchain::with_consensus(
+            GenesisConfig::new(1_700_000_000, 2),
+            ProofOfAuthority::new(vec![authority.verifying_key()]).with_signer(authority),
+        )
+        .unwrap();
+
+        // add_block would sign this with the node's own key
+        let unsealed = blockchain.prepare_block(&mut Mempool::new(), "Mallory");
+        assert_eq!(
+            blockchain.accept_block(unsealed.clone()).unwrap_err(),
+            BlockchainError::Unsealed { index: 1 }
+        );
+        let mut claimed = unsealed;
+        claimed.mined = true;
+        assert_eq!(
+            blockchain.accept_block(claimed).unwrap_err(),
+            BlockchainError::MiningFailed { index: 1 }
+        );
+        assert_eq!(blockchain.get_total_blocks(), 1);
+
+        let mut blockchain = Blockchain::new();
+        let mut mined = blockchain.prepare_block(&mut Mempool::new(), "Miner");
+        mined.mine_block().unwrap();
+        assert_eq!(blockchain.accept_block(mined), Ok(ChainUpdate::Extended));
+    }
+
+    /// A mined block on the tip carrying exactly `transactions`, as a peer could send it
+    fn forged_block(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+        let tip = blockchain.tip();
+        let mut block = Block::new(tip.index + 1, tip.hash.clone(), transactions);
+        block.timestamp = tip.timestamp + 1;
+        block
+            .mine_block_with_difficulty(blockchain.current_difficulty())
+            .unwrap();
+        block
+    }
+
+    #[test]
+    fn peer_blocks_with_bad_coinbases_are_rejected() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let payment = Transaction::new("Alice", "Bob", 1.0);
+        let minted = |amount: f64| Transaction::coinbase("Mallory", amount, 2);
+        let cases = [
+            (vec![payment.clone()], CoinbaseError::Missing),
+            (
+                vec![minted(1e9), minted(1e9).with_fee(1.0)],
+                CoinbaseError::Multiple { count: 2 },
+            ),
+            (
+                vec![payment.clone(), minted(BLOCK_REWARD)],
+                CoinbaseError::NotFirst { position: 1 },
+            ),
+            (
+                vec![minted(1e9), payment],
+                CoinbaseError::ExcessiveReward {
+                    paid: 1e9,
+                    allowed: BLOCK_REWARD,
+                },
+            ),
+        ];
+
+        for (transactions, reason) in cases {
+            let block = forged_block(&blockchain, transactions);
+            assert_eq!(
+                blockchain.accept_block(block.clone()).unwrap_err(),
+                BlockchainError::InvalidCoinbase {
+                    index: 2,
+                    reason: reason.clone(),
+                }
+            );
+            blockchain.chain.push(block);
+            assert_eq!(
+                blockchain.validate(),
+                Err(ChainValidationError::InvalidCoinbase { index: 2, reason })
+            );
+            blockchain.chain.pop();
+        }
+        assert_eq!(blockchain.get_balance("Mallory"), 0.0);
+    }
+
+    #[test]
+    fn peer_blocks_overdrawing_an_account_are_rejected() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let theft = Transaction::new("Carol", "Mallory", 1e6);
+        let block = forged_block(
+            &blockchain,
+            vec![
+                Transaction::coinbase("Mallory", BLOCK_REWARD, 2),
+                theft.clone(),
+            ],
+        );
+        assert_eq!(
+            blockchain.accept_block(block.clone()).unwrap_err(),
+            BlockchainError::Overdraft {
+                index: 2,
+                tx_id: theft.calculate_hash(),
+                balance: 0.0,
+                required: 1e6,
+            }
+        );
+        blockchain.chain.push(block);
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::Overdraft {
+                index: 2,
+                tx_id: theft.calculate_hash(),
+                balance: 0.0,
+                required: 1e6,
+            })
+        );
+        blockchain.chain.pop();
+
+        // Payments earlier in the same block count towards the sender's balance
+        let relay = Transaction::new("Bob", "Carol", 30.0);
+        let block = forged_block(
+            &blockchain,
+            vec![
+                Transaction::coinbase("Miner", BLOCK_REWARD, 2),
+                Transaction::new("Alice", "Bob", 50.0),
+                relay.clone(),
+                Transaction::new("Bob", "Carol", 30.0).with_fee(0.5),
+            ],
+        );
+        assert!(matches!(
+            blockchain.accept_block(block).unwrap_err(),
+            BlockchainError::Overdraft { balance, required, .. }
+                if balance == 20.0 && required == 30.5
+        ));
+        let block = forged_block(
+            &blockchain,
+            vec![
+                Transaction::coinbase("Miner", BLOCK_REWARD, 2),
+                Transaction::new("Alice", "Bob", 50.0),
+                relay,
+            ],
+        );
+        blockchain.accept_block(block).unwrap();
+        assert_eq!(blockchain.get_balance("Carol"), 30.0);
+        assert!(blockchain.is_valid());
+    }
+
+    /// A block on the current tip carrying `timestamp`, mined at the required difficulty
+    fn block_at_time(blockchain: &Blockchain, timestamp: u64) -> Block {
+        let tip = blockchain.tip();
+        let mut block = new_block(tip.index + 1, tip.hash.clone(), Vec::new());
+        block.timestamp = timestamp;
+        block
+            .mine_block_with_difficulty(blockchain.current_difficulty())
+            .unwrap();
+        block
+    }
+
+    /// A mined block on the current tip whose one transaction expires `expiry_offset`
+    /// seconds after the block's timestamp
+    fn block_with_expiring_transaction(blockchain: &Blockchain, expiry_offset: i64) -> Block {
+        let tip = blockchain.tip();
+        let timestamp = tip.timestamp + 5;
+        let transaction = Transaction::new("Alice", "Bob", 1.0)
+            .with_expiry(timestamp.saturating_add_signed(expiry_offset));
+        let mut block = new_block(tip.index + 1, tip.hash.clone(), vec![transaction]);
+        block.timestamp = timestamp;
+        block
+            .mine_block_with_difficulty(blockchain.current_difficulty())
+            .unwrap();
+        block
+    }
+
+    #[test]
+    fn transactions_may_be_mined_up_to_their_expiry() {
+        let mut blockchain = chain_of(2);
+        let on_time = block_with_expiring_transaction(&blockchain, 0);
+        assert_eq!(blockchain.add_block(on_time), Ok(ChainUpdate::Extended));
+
+        let late = block_with_expiring_transaction(&blockchain, -1);
+        let tx_id = late.transactions[1].calculate_hash();
+        assert_eq!(
+            blockchain.add_block(late.clone()),
+            Err(BlockchainError::ExpiredTransaction {
+                index: 3,
+                tx_id: tx_id.clone(),
+                expires_at: late.timestamp - 1,
+            })
+        );
+
+        // A chain file holding the late block fails validation the same way
+        blockchain.chain.push(late.clone());
+        assert_eq!(
+            blockchain.validate(),
+            Err(ChainValidationError::ExpiredTransaction {
+                index: 3,
+                tx_id,
+                expires_at: late.timestamp - 1,
+            })
+        );
+    }
+
+    #[test]
+    fn prepared_blocks_leave_out_expired_transactions() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let mut mempool = Mempool::new();
+        let now = SystemClock.now_secs();
+        let lasting = Transaction::new("Alice", "Bob", 1.0).with_expiry(now + 3600);
+        mempool.add_transaction(lasting.clone()).unwrap();
+        // Expires before the block can be stamped, because the block must follow its parents
+        blockchain.chain.last_mut().unwrap().timestamp = now + 100;
+        blockchain.chain[0].timestamp = now + 100;
+        let doomed = Transaction::new("Alice", "Carol", 1.0).with_expiry(now + 50);
+        mempool.add_transaction(doomed).unwrap();
+
+        let block = blockchain.prepare_block(&mut mempool, "Miner");
+        assert_eq!(block.transactions[1..], [lasting]);
+        assert!(block.timestamp > now + 100);
+    }
+
+    #[test]
+    fn median_time_past_uses_the_last_eleven_blocks() {
+        let mut blocks: Vec<Block> = (0..15).map(block).collect();
+        for (position, block) in blocks.iter_mut().enumerate() {
+            block.timestamp = 1000 - position as u64;
+        }
+        assert_eq!(median_time_past(&[]), 0);
+        assert_eq!(median_time_past(&blocks[..1]), 1000);
+        assert_eq!(median_time_past(&blocks[..2]), 1000);
+        // Blocks 4..15 have timestamps 996 down to 986
+        assert_eq!(median_time_past(&blocks), 991);
+    }
+
+    #[test]
+    fn timestamps_must_pass_the_median_time_past() {
+        let mut blockchain = chain_of(4);
+        let median = median_time_past(&blockchain.chain);
+
+        let too_old = block_at_time(&blockchain, median - 1);
+        assert_eq!(
+            blockchain.add_block(too_old).unwrap_err(),
+            BlockchainError::TimestampTooOld {
+                index: 4,
+                timestamp: median - 1,
+                median_time_past: median
+            }
+        );
+
+        let at_median = block_at_time(&blockchain, median);
+        assert!(matches!(
+            blockchain.add_block(at_median),
+            Err(BlockchainError::TimestampTooOld { .. })
+        ));
+
+        let after_median = block_at_time(&blockchain, median + 1);
+        blockchain.add_block(after_median).unwrap();
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn timestamps_may_not_run_too_far_ahead() {
+        let mut blockchain = Blockchain::new();
+        blockchain.max_future_drift_secs = 60;
+
+        let far_ahead = block_at_time(&blockchain, blockchain.max_allowed_timestamp() + 60);
+        assert!(matches!(
+            blockchain.add_block(far_ahead),
+            Err(BlockchainError::TimestampTooFarAhead { index: 1, .. })
+        ));
+
+        let at_limit = block_at_time(&blockchain, blockchain.max_allowed_timestamp());
+        blockchain.add_block(at_limit).unwrap();
+        assert!(blockchain.is_valid());
+
+        // Shrinking the allowance makes the stored block invalid after the fact
+        blockchain.max_future_drift_secs = 0;
+        assert!(matches!(
+            blockchain.validate(),
+            Err(ChainValidationError::TimestampTooFarAhead { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn validate_rejects_blocks_older_than_the_median() {
+        let mut blockchain = chain_of(3);
+        let stale = block_at_time(&blockchain, blockchain.chain[0].timestamp);
+        blockchain.chain.push(stale);
+        assert!(matches!(
+            blockchain.validate(),
+            Err(ChainValidationError::TimestampTooOld { index: 3, .. })
+        ));
+    }
+
+    /// Mines a block on top of `parent` rewarding `miner` and carrying `transactions`,
+    /// then submits it
+    fn add_child(
+        blockchain: &mut Blockchain,
+        parent: &Block,
+        miner: &str,
+        transactions: Vec<Transaction>,
+    ) -> (Block, ChainUpdate) {
+        let mut block_transactions =
+            vec![Transaction::coinbase(miner, BLOCK_REWARD, parent.index + 1)];
+        block_transactions.extend(transactions);
+        let mut child = Block::new(parent.index + 1, parent.hash.clone(), block_transactions);
+        child.timestamp = parent.timestamp + 1;
+        child
+            .mine_block_with_difficulty(blockchain.difficulty_config.initial_difficulty)
+            .unwrap();
+        let update = blockchain.add_block(child.clone()).unwrap();
+        (child, update)
+    }
+
+    #[test]
+    fn longer_branch_triggers_reorg_and_reports_orphaned_transactions() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let fork_point = blockchain.tip().clone();
+
+        let paid_bob = Transaction::new("Alice", "Bob", 10.0);
+        let paid_carol = Transaction::new("Alice", "Carol", 20.0);
+        let (a1, update) = add_child(&mut blockchain, &fork_point, "A", vec![paid_bob.clone()]);
+        assert_eq!(update, ChainUpdate::Extended);
+        let (a2, _) = add_child(&mut blockchain, &a1, "A", vec![paid_carol.clone()]);
+
+        // A competing branch of equal length is kept aside
+        let (b1, update) = add_child(&mut blockchain, &fork_point, "B", vec![paid_bob.clone()]);
+        assert_eq!(update, ChainUpdate::SideChain);
+        let (b2, update) = add_child(&mut blockchain, &b1, "B", Vec::new());
+        assert_eq!(update, ChainUpdate::SideChain);
+        assert_eq!(blockchain.tip().hash, a2.hash);
+        assert_eq!(blockchain.side_block_count(), 2);
+
+        // ...until it becomes longer
+        let events = blockchain.subscribe();
+        let (b3, update) = add_child(&mut blockchain, &b2, "B", Vec::new());
+        let reorg = match update {
+            ChainUpdate::Reorganized(reorg) => reorg,
+            other => panic!("expected a reorg, got {:?}", other),
+        };
+        assert_eq!(reorg.old_tip, a2.hash);
+        assert_eq!(reorg.new_tip, b3.hash);
+        assert_eq!(reorg.depth, 2);
+        assert_eq!(reorg.orphaned_transactions, vec![paid_carol]);
+        let added = |block: &Block| ChainEvent::BlockAdded {
+            height: block.index,
+            hash: block.hash.clone(),
+        };
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                ChainEvent::Reorg {
+                    old_tip: a2.hash.clone(),
+                    new_tip: b3.hash.clone(),
+                    depth: 2,
+                },
+                added(&b1),
+                added(&b2),
+                added(&b3),
+            ]
+        );
+
+        assert_eq!(blockchain.tip().hash, b3.hash);
+        let best: Vec<&str> = blockchain
+            .best_chain()
+            .iter()
+            .map(|block| block.hash.as_str())
+            .collect();
+        assert_eq!(best[2..], [&b1.hash, &b2.hash, &b3.hash]);
+        assert_eq!(blockchain.side_block_count(), 2);
+        assert_eq!(blockchain.get_balance("Carol"), 0.0);
+        assert_eq!(blockchain.get_balance("Bob"), 10.0);
+        assert_eq!(blockchain.balances, balances_in(&blockchain.chain));
+        assert!(blockchain.is_valid());
+    }
+
+    #[test]
+    fn abandoned_branch_can_win_back() {
+        let mut blockchain = Blockchain::new();
+        let genesis = blockchain.tip().clone();
+        let (a1, _) = add_child(&mut blockchain, &genesis, "A", Vec::new());
+        let (b1, _) = add_child(&mut blockchain, &genesis, "B", Vec::new());
+        let (b2, _) = add_child(&mut blockchain, &b1, "B", Vec::new());
+        assert_eq!(blockchain.tip().hash, b2.hash);
+
+        let (a2, _) = add_child(&mut blockchain, &a1, "A", Vec::new());
+        assert_eq!(blockchain.tip().hash, b2.hash);
+        let (a3, update) = add_child(&mut blockchain, &a2, "A", Vec::new());
+        assert!(matches!(
+            update,
+            ChainUpdate::Reorganized(Reorg { depth: 2, .. })
+        ));
+        assert_eq!(blockchain.tip().hash, a3.hash);
+    }
+
+    #[test]
+    fn orphans_completing_a_heavier_branch_reorganize() {
+        let mut blockchain = Blockchain::new();
+        let genesis = blockchain.tip().clone();
+        let mut other = blockchain.clone();
+        let (b1, _) = add_child(&mut other, &genesis, "B", Vec::new());
+        let (b2, _) = add_child(&mut other, &b1, "B", Vec::new());
+        let (a1, _) = add_child(&mut blockchain, &genesis, "A", Vec::new());
+
+        assert_eq!(blockchain.add_block(b2.clone()), Ok(ChainUpdate::Orphaned));
+        let update = blockchain.add_block(b1).unwrap();
+        assert_eq!(
+            update,
+            ChainUpdate::Reorganized(Reorg {
+                old_tip: a1.hash,
+                new_tip: b2.hash.clone(),
+                depth: 1,
+                orphaned_transactions: Vec::new(),
+            })
+        );
+        assert_eq!(blockchain.tip().hash, b2.hash);
+    }
+
+    #[test]
+    fn side_blocks_are_validated() {
+        let mut blockchain = chain_of(3);
+        let genesis = blockchain.chain[0].clone();
+        let mut forged = new_block(1, genesis.hash.clone(), Vec::new());
+        forged.timestamp = genesis.timestamp + 1;
+        forged.mined = true;
+        forged.hash = "f".repeat(64);
+        assert_eq!(
+            blockchain.add_block(forged).unwrap_err(),
+            BlockchainError::MiningFailed { index: 1 }
+        );
+
+        let (known, _) = add_child(&mut blockchain, &genesis, "B", Vec::new());
+        assert_eq!(
+            blockchain.add_block(known.clone()).unwrap_err(),
+            BlockchainError::DuplicateBlock { hash: known.hash }
+        );
+    }
+
+    #[test]
+    fn side_blocks_are_revalidated_on_load() {
+        let mut blockchain = chain_of(5);
+        let genesis = blockchain.chain[0].clone();
+        let (kept, _) = add_child(&mut blockchain, &genesis, "B", Vec::new());
+        let (forged, _) = add_child(&mut blockchain, &kept, "B", Vec::new());
+        add_child(&mut blockchain, &forged, "B", Vec::new());
+        assert_eq!(blockchain.side_block_count(), 3);
+
+        // Claiming a far lower target would make the branch outweigh the best chain
+        let forged = blockchain.side_blocks.get_mut(&forged.hash).unwrap();
+        forged.bits = 0x1d00_ffff;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chain.json");
+        blockchain.save_to_file(&path).unwrap();
+
+        let loaded = Blockchain::load_from_file(&path).unwrap();
+        assert_eq!(loaded.side_block_count(), 1);
+        assert!(loaded.side_blocks.contains_key(&kept.hash));
+        assert_eq!(loaded.tip().hash, blockchain.tip().hash);
+    }
+
+    #[test]
+    fn inverted_difficulty_ranges_retarget_without_panicking() {
+        let mut blockchain = Blockchain::with_difficulty_config(DifficultyConfig {
+            initial_difficulty: 1,
+            adjustment_interval: 2,
+            min_difficulty: 2,
+            max_difficulty: 1,
+            ..DifficultyConfig::default()
+        });
+        let mut mempool = Mempool::new();
+        for _ in 0..4 {
+            blockchain
+                .mine_pending_transactions(&mut mempool, "Miner")
+                .unwrap();
+        }
+        assert_eq!(blockchain.current_difficulty(), 1);
+    }
+
+    #[test]
+    fn chains_with_unusable_difficulty_settings_fail_to_load() {
+        let mut blockchain = Blockchain::new();
+        blockchain.difficulty_config.target_block_time_secs = u64::MAX;
+        let dir = tempfile::tempdir().unwrap();
+        for (name, format) in [
+            ("chain.json", ChainFormat::Json),
+            ("chain.bin", ChainFormat::Binary),
+        ] {
+            let path = dir.path().join(name);
+            blockchain.save_to_file_as(&path, format).unwrap();
+            assert!(matches!(
+                Blockchain::load_from_file(&path),
+                Err(LoadError::Invalid(ChainValidationError::InvalidConfig(
+                    DifficultyConfigError::IntervalTooLong { .. }
+                )))
+            ));
+        }
+    }
+}
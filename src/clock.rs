@@ -0,0 +1,52 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time for timestamps, so runs can be replayed exactly
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Seconds since the UNIX epoch
+    fn now_secs(&self) -> u64;
+}
+
+/// The machine's real-time clock. A clock set before 1970 reads as 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+/// A clock that only moves when told to, for tests and reproducible simulations
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+impl MockClock {
+    /// Creates a clock stopped at `now_secs`
+    pub fn new(now_secs: u64) -> MockClock {
+        MockClock {
+            now: AtomicU64::new(now_secs),
+        }
+    }
+
+    /// Moves the clock forward by `secs`
+    pub fn advance(&self, secs: u64) {
+        self.now.fetch_add(secs, Ordering::Relaxed);
+    }
+
+    /// Moves the clock to `now_secs`
+    pub fn set(&self, now_secs: u64) {
+        self.now.store(now_secs, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_secs(&self) -> u64 {
+        self.now.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,283 @@
+use crate::address::Address;
+use crate::block::{Block, HeaderHasher};
+use crate::blockchain::{Blockchain, BlockchainError};
+use crate::difficulty::DifficultyConfig;
+use crate::transaction::Mempool;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Barrier, Mutex};
+use std::thread;
+
+/// Hashes a miner tries per tick unless configured otherwise
+pub const DEFAULT_HASH_BUDGET_PER_TICK: u64 = 1_000;
+
+/// Reasons `run_mining_competition` can't run or finish
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompetitionError {
+    /// No hash has this many leading zeros, so no round could ever be won
+    DifficultyTooHigh(usize),
+    /// Two miners share a name and would be credited each other's wins
+    DuplicateMiner(String),
+    /// The chain refused a winning block
+    Block(BlockchainError),
+}
+
+impl fmt::Display for CompetitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompetitionError::DifficultyTooHigh(difficulty) => write!(
+                f,
+                "difficulty {} can never be met; it must be below 64",
+                difficulty
+            ),
+            CompetitionError::DuplicateMiner(name) => {
+                write!(f, "miner {} is entered more than once", name)
+            }
+            CompetitionError::Block(e) => write!(f, "winning block was refused: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CompetitionError {}
+
+impl From<BlockchainError> for CompetitionError {
+    fn from(e: BlockchainError) -> Self {
+        CompetitionError::Block(e)
+    }
+}
+
+/// One contestant in `run_mining_competition`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerConfig {
+    pub name: String,              // Address credited when this miner wins a round
+    pub hash_budget_per_tick: u64, // Hashes tried per tick, standing in for hash power
+}
+
+impl MinerConfig {
+    pub fn new(name: &str) -> MinerConfig {
+        MinerConfig {
+            name: name.to_string(),
+            hash_budget_per_tick: DEFAULT_HASH_BUDGET_PER_TICK,
+        }
+    }
+
+    /// Sets how many hashes the miner tries per tick
+    pub fn with_hash_budget(mut self, hash_budget_per_tick: u64) -> MinerConfig {
+        self.hash_budget_per_tick = hash_budget_per_tick;
+        self
+    }
+}
+
+/// How one miner fared over a competition
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerStanding {
+    pub name: String,
+    pub wins: usize,
+    pub hashes_tried: u64, // Across every round, won or lost
+    pub balance: f64,      // Confirmed balance on the final chain
+}
+
+/// Outcome of `run_mining_competition`
+#[derive(Debug, Clone)]
+pub struct CompetitionResult {
+    pub blockchain: Blockchain,
+    pub standings: Vec<MinerStanding>, // In the order the miners were configured
+    pub winners: Vec<String>,          // Winner of each round, first round first
+}
+
+impl CompetitionResult {
+    /// Standings ordered by wins, then balance, most first
+    pub fn leaderboard(&self) -> Vec<&MinerStanding> {
+        let mut leaderboard: Vec<&MinerStanding> = self.standings.iter().collect();
+        leaderboard.sort_by(|a, b| b.wins.cmp(&a.wins).then(b.balance.total_cmp(&a.balance)));
+        leaderboard
+    }
+}
+
+/// First valid block found in a round
+struct Win {
+    miner: usize,
+    tick: u64,
+    nonce: u64,
+    hash: String,
+}
+
+/// Races `miners` for `rounds` blocks at a fixed `difficulty`.
+///
+/// Each round every miner gets its own thread and its own copy of the next block:
+/// the same pending transactions with the coinbase paid to that miner, searched
+/// from a different starting nonce. Miners advance in lockstep ticks, each trying
+/// its `hash_budget_per_tick` hashes per tick, so budgets act as relative hash
+/// power. The first valid block wins the round and is added to the chain; a shared
+/// stop flag cancels the rest.
+///
+/// Fails up front if `difficulty` is 64 or more, or if two miners share a name.
+pub fn run_mining_competition(
+    miners: &[MinerConfig],
+    rounds: usize,
+    difficulty: usize,
+) -> Result<CompetitionResult, CompetitionError> {
+    // A hash has 64 hex digits and only the all-zero hash meets difficulty 64
+    if difficulty >= 64 {
+        return Err(CompetitionError::DifficultyTooHigh(difficulty));
+    }
+    let mut names = HashSet::new();
+    if let Some(miner) = miners.iter().find(|miner| !names.insert(&miner.name)) {
+        return Err(CompetitionError::DuplicateMiner(miner.name.clone()));
+    }
+
+    let mut blockchain = Blockchain::with_difficulty_config(DifficultyConfig {
+        initial_difficulty: difficulty,
+        min_difficulty: difficulty,
+        max_difficulty: difficulty,
+        ..DifficultyConfig::default()
+    });
+    let mut mempool = Mempool::new();
+    let mut hashes_tried = vec![0; miners.len()];
+    let mut winners = Vec::new();
+
+    if !miners.is_empty() {
+        for _ in 0..rounds {
+            let template = blockchain.prepare_block(&mut mempool, &miners[0].name);
+            let candidates: Vec<Block> = miners
+                .iter()
+                .map(|miner| candidate_for(&template, &miner.name))
+                .collect();
+
+            let win = race(miners, &candidates, &mut hashes_tried);
+            let mut block = candidates[win.miner].clone();
+            block.nonce = win.nonce;
+            block.hash = win.hash;
+            block.mined = true;
+            blockchain.add_block(block)?;
+            winners.push(miners[win.miner].name.clone());
+        }
+    }
+
+    let standings = miners
+        .iter()
+        .zip(hashes_tried)
+        .map(|(miner, hashes_tried)| MinerStanding {
+            name: miner.name.clone(),
+            wins: winners.iter().filter(|name| **name == miner.name).count(),
+            hashes_tried,
+            balance: blockchain.get_balance(&miner.name),
+        })
+        .collect();
+    Ok(CompetitionResult {
+        blockchain,
+        standings,
+        winners,
+    })
+}
+
+/// `template` with its coinbase paid to `miner` instead
+fn candidate_for(template: &Block, miner: &str) -> Block {
+    let mut block = template.clone();
+    block.transactions[0].recipient = Address::unchecked(miner);
+    block.merkle_root = block.calculate_merkle_root();
+    block.hash = block.calculate_hash();
+    block
+}
+
+/// Mines every candidate on its own thread until one is valid, adding each miner's
+/// hashes to `hashes_tried`
+fn race(miners: &[MinerConfig], candidates: &[Block], hashes_tried: &mut [u64]) -> Win {
+    let stop = AtomicBool::new(false);
+    let win: Mutex<Option<Win>> = Mutex::new(None);
+    let barrier = Barrier::new(candidates.len());
+    // Spread the starting nonces so no two miners walk the same range
+    let spacing = u64::MAX / candidates.len() as u64;
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = candidates
+            .iter()
+            .zip(miners)
+            .enumerate()
+            .map(|(index, (block, miner))| {
+                let (stop, win, barrier) = (&stop, &win, &barrier);
+                let budget = miner.hash_budget_per_tick.max(1);
+                scope.spawn(move || {
+                    let target = block.target().expect("the chain sets a valid target");
+                    let hasher = HeaderHasher::new(block);
+                    let mut nonce = index as u64 * spacing;
+                    let mut tried = 0;
+                    for tick in 0.. {
+                        for _ in 0..budget {
+                            if stop.load(Ordering::Relaxed) {
+                                break;
+                            }
+                            let digest = hasher.digest(nonce);
+                            tried += 1;
+                            if target.is_met_by(&digest) {
+                                let mut win = win.lock().expect("competition lock poisoned");
+                                win.get_or_insert(Win {
+                                    miner: index,
+                                    tick,
+                                    nonce,
+                                    hash: hex::encode(digest),
+                                });
+                                stop.store(true, Ordering::Relaxed);
+                                break;
+                            }
+                            nonce = nonce.wrapping_add(1);
+                        }
+
+                        // Nobody starts the next tick until every miner has spent
+                        // this one, and all of them leave after the winning tick
+                        barrier.wait();
+                        let won = win.lock().expect("competition lock poisoned");
+                        if won.as_ref().is_some_and(|won| won.tick <= tick) {
+                            break;
+                        }
+                    }
+                    tried
+                })
+            })
+            .collect();
+        for (total, worker) in hashes_tried.iter_mut().zip(workers) {
+            *total += worker.join().expect("miner thread panicked");
+        }
+    });
+
+    win.into_inner()
+        .expect("competition lock poisoned")
+        .expect("the race only ends once a miner has won")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_miner_without_rivals_wins_every_round() {
+        let result = run_mining_competition(&[MinerConfig::new("solo")], 3, 1).unwrap();
+        assert_eq!(result.winners, vec!["solo"; 3]);
+        assert_eq!(result.standings[0].wins, 3);
+        assert!(result.blockchain.is_valid());
+    }
+
+    #[test]
+    fn unwinnable_difficulties_and_duplicate_miners_are_refused() {
+        let miners = [MinerConfig::new("Alice"), MinerConfig::new("Bob")];
+        assert_eq!(
+            run_mining_competition(&miners, 1, 64).unwrap_err(),
+            CompetitionError::DifficultyTooHigh(64)
+        );
+        assert_eq!(
+            run_mining_competition(&miners, 1, usize::MAX).unwrap_err(),
+            CompetitionError::DifficultyTooHigh(usize::MAX)
+        );
+
+        let twice = [
+            MinerConfig::new("Alice"),
+            MinerConfig::new("Bob"),
+            MinerConfig::new("Alice").with_hash_budget(10),
+        ];
+        assert_eq!(
+            run_mining_competition(&twice, 1, 1).unwrap_err(),
+            CompetitionError::DuplicateMiner("Alice".to_string())
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::block::{Block, MiningError};
+use crate::target::difficulty_to_bits;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Rules for sealing a block so other nodes accept it, and for checking that seal
+pub trait Consensus: fmt::Debug + Send + Sync {
+    /// Finalizes `block`: sets its hash and whatever proof the rules require
+    fn seal(&self, block: &mut Block) -> Result<(), SealError>;
+
+    /// Returns true if `block` carries a valid seal under these rules
+    fn verify(&self, block: &Block) -> bool;
+}
+
+/// Reasons a consensus engine can fail to seal a block
+#[derive(Debug, Clone, PartialEq)]
+pub enum SealError {
+    /// The engine has no signing key to seal blocks with
+    NoSigner,
+    /// The engine's signing key is not one of the authorities
+    NotAnAuthority { signer: String },
+    /// Proof of work gave up without finding a valid hash
+    Mining(MiningError),
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SealError::NoSigner => write!(f, "no signing key configured"),
+            SealError::NotAnAuthority { signer } => {
+                write!(f, "signer {} is not an authority", signer)
+            }
+            SealError::Mining(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+impl From<MiningError> for SealError {
+    fn from(e: MiningError) -> Self {
+        SealError::Mining(e)
+    }
+}
+
+/// Proof of work at a fixed difficulty. Blocks that use a target are held to
+/// `bits`; older blocks count the leading zeros of their hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProofOfWork {
+    pub difficulty: usize,
+    pub bits: u32, // Compact target for blocks that use one
+}
+
+impl ProofOfWork {
+    /// Proof of work at `difficulty` leading zeros, or the equivalent target
+    pub fn new(difficulty: usize) -> ProofOfWork {
+        ProofOfWork {
+            difficulty,
+            bits: difficulty_to_bits(difficulty),
+        }
+    }
+}
+
+impl Consensus for ProofOfWork {
+    fn seal(&self, block: &mut Block) -> Result<(), SealError> {
+        block.difficulty = self.difficulty;
+        if block.uses_target() {
+            block.bits = self.bits;
+        }
+        block.mine_block()?;
+        Ok(())
+    }
+
+    fn verify(&self, block: &Block) -> bool {
+        block.difficulty == self.difficulty
+            && (!block.uses_target() || block.bits == self.bits)
+            && block.hash_meets_target()
+            && block.hash == block.calculate_hash()
+    }
+}
+
+/// An authority's signature over a block hash, stored on the block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthoritySeal {
+    pub signer: String,    // Hex-encoded public key of the authority
+    pub signature: String, // Hex-encoded signature of the block hash
+}
+
+/// Blocks are sealed by a signature from one of a fixed set of authorities instead of work
+#[derive(Debug, Clone)]
+pub struct ProofOfAuthority {
+    authorities: Vec<VerifyingKey>,
+    signer: Option<SigningKey>, // Key this node seals with, if it is a sealer
+}
+
+impl ProofOfAuthority {
+    /// Creates an engine that verifies blocks against `authorities` but cannot seal
+    pub fn new(authorities: Vec<VerifyingKey>) -> ProofOfAuthority {
+        ProofOfAuthority {
+            authorities,
+            signer: None,
+        }
+    }
+
+    /// Seals blocks by signing with `signer`
+    pub fn with_signer(mut self, signer: SigningKey) -> ProofOfAuthority {
+        self.signer = Some(signer);
+        self
+    }
+
+    fn is_authority(&self, key: &VerifyingKey) -> bool {
+        self.authorities.contains(key)
+    }
+}
+
+impl Consensus for ProofOfAuthority {
+    fn seal(&self, block: &mut Block) -> Result<(), SealError> {
+        let signer = self.signer.as_ref().ok_or(SealError::NoSigner)?;
+        let public_key = signer.verifying_key();
+        if !self.is_authority(&public_key) {
+            return Err(SealError::NotAnAuthority {
+                signer: hex::encode(public_key.as_bytes()),
+            });
+        }
+
+        // Authority blocks carry no work, so they count as difficulty 0 when comparing branches
+        block.difficulty = 0;
+        block.hash = block.calculate_hash();
+        block.authority_seal = Some(AuthoritySeal {
+            signer: hex::encode(public_key.as_bytes()),
+            signature: hex::encode(signer.sign(block.hash.as_bytes()).to_bytes()),
+        });
+        block.mined = true;
+        Ok(())
+    }
+
+    fn verify(&self, block: &Block) -> bool {
+        let seal = match &block.authority_seal {
+            Some(seal) => seal,
+            None => return false,
+        };
+        let signer = match decode_key(&seal.signer) {
+            Some(key) => key,
+            None => return false,
+        };
+        let signature = match hex::decode(&seal.signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        {
+            Some(signature) => signature,
+            None => return false,
+        };
+
+        block.hash == block.calculate_hash()
+            && self.is_authority(&signer)
+            && signer.verify(block.hash.as_bytes(), &signature).is_ok()
+    }
+}
+
+fn decode_key(encoded: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(encoded).ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+
+    fn key() -> SigningKey {
+        SigningKey::from_bytes(&rand::random::<[u8; 32]>())
+    }
+
+    fn unsealed() -> Block {
+        Block::new(
+            1,
+            String::from("previous"),
+            vec![Transaction::new("Alice", "Bob", 1.0)],
+        )
+    }
+
+    #[test]
+    fn proof_of_work_checks_difficulty() {
+        let mut block = unsealed();
+        ProofOfWork::new(2).seal(&mut block).unwrap();
+        assert!(ProofOfWork::new(2).verify(&block));
+        assert!(!ProofOfWork::new(3).verify(&block));
+    }
+
+    #[test]
+    fn authority_seals_verify_only_for_listed_signers() {
+        let authority = key();
+        let engine = ProofOfAuthority::new(vec![authority.verifying_key()]).with_signer(authority);
+        let mut block = unsealed();
+        engine.seal(&mut block).unwrap();
+        assert!(engine.verify(&block));
+
+        let mut tampered = block.clone();
+        tampered.transactions[0].amount = 100.0;
+        tampered.merkle_root = tampered.calculate_merkle_root();
+        tampered.hash = tampered.calculate_hash();
+        assert!(!engine.verify(&tampered));
+
+        let outsider = key();
+        let other_network = ProofOfAuthority::new(vec![outsider.verifying_key()]);
+        assert!(!other_network.verify(&block));
+    }
+
+    #[test]
+    fn sealing_requires_an_authorized_signer() {
+        let authority = key();
+        let verifier = ProofOfAuthority::new(vec![authority.verifying_key()]);
+        assert_eq!(verifier.seal(&mut unsealed()), Err(SealError::NoSigner));
+
+        let outsider = verifier.clone().with_signer(key());
+        assert!(matches!(
+            outsider.seal(&mut unsealed()),
+            Err(SealError::NotAnAuthority { .. })
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::block::{Block, DIFFICULTY};
+use crate::target::{difficulty_to_target, Target};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Settings for retargeting the mining difficulty from observed block times
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyConfig {
+    pub initial_difficulty: usize,   // Difficulty of the genesis block
+    pub target_block_time_secs: u64, // Desired time between blocks
+    pub adjustment_interval: u32,    // Retarget every this many blocks
+    pub min_difficulty: usize,
+    pub max_difficulty: usize,
+}
+
+/// Reasons `DifficultyConfig::validate` refuses a config
+#[derive(Debug, Clone, PartialEq)]
+pub enum DifficultyConfigError {
+    /// The time one adjustment interval should span doesn't fit in a u64
+    IntervalTooLong {
+        target_block_time_secs: u64,
+        adjustment_interval: u32,
+    },
+    /// The minimum difficulty is above the maximum
+    InvalidRange { min: usize, max: usize },
+}
+
+impl fmt::Display for DifficultyConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DifficultyConfigError::IntervalTooLong {
+                target_block_time_secs,
+                adjustment_interval,
+            } => write!(
+                f,
+                "{} blocks of {}s each is too long an adjustment interval",
+                adjustment_interval, target_block_time_secs
+            ),
+            DifficultyConfigError::InvalidRange { min, max } => {
+                write!(f, "minimum difficulty {} is above the maximum {}", min, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DifficultyConfigError {}
+
+/// Expected number of hashes needed to mine a block at `difficulty` (16 per hex zero)
+pub fn block_work(difficulty: usize) -> f64 {
+    16f64.powi(difficulty as i32)
+}
+
+/// Total expected work behind a sequence of blocks, used to pick the best branch
+pub fn chain_work(blocks: &[Block]) -> f64 {
+    blocks
+        .iter()
+        .map(|block| block.target().map_or(0.0, |target| target.work()))
+        .sum()
+}
+
+/// Scales `target` by how long the last interval took against how long it should
+/// have: `target * actual_secs / expected_secs`. The ratio is clamped so one
+/// adjustment never changes the target by more than 4x either way.
+pub fn retarget(target: &Target, actual_secs: u64, expected_secs: u64) -> Target {
+    let expected_secs = expected_secs.max(1);
+    let actual_secs = actual_secs.clamp(expected_secs.div_ceil(4), expected_secs.saturating_mul(4));
+    target.scale(actual_secs, expected_secs)
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        DifficultyConfig {
+            initial_difficulty: DIFFICULTY,
+            target_block_time_secs: 10,
+            adjustment_interval: 10,
+            min_difficulty: 1,
+            max_difficulty: 5,
+        }
+    }
+}
+
+impl DifficultyConfig {
+    /// Checks that retargeting can work with these settings: the adjustment interval
+    /// must span a representable number of seconds, with room for the 4x clamp in
+    /// `retarget`, and the difficulty range must not be empty
+    pub fn validate(&self) -> Result<(), DifficultyConfigError> {
+        if self
+            .expected_interval_secs()
+            .and_then(|secs| secs.checked_mul(4))
+            .is_none()
+        {
+            return Err(DifficultyConfigError::IntervalTooLong {
+                target_block_time_secs: self.target_block_time_secs,
+                adjustment_interval: self.adjustment_interval,
+            });
+        }
+        if self.min_difficulty > self.max_difficulty {
+            return Err(DifficultyConfigError::InvalidRange {
+                min: self.min_difficulty,
+                max: self.max_difficulty,
+            });
+        }
+        Ok(())
+    }
+
+    /// Seconds the blocks of one adjustment interval should span, or `None` if that
+    /// overflows
+    fn expected_interval_secs(&self) -> Option<u64> {
+        let interval = self.adjustment_interval.max(2) as u64;
+        self.target_block_time_secs.checked_mul(interval - 1)
+    }
+
+    /// Returns the difficulty required for the block that follows `blocks`.
+    ///
+    /// Every `adjustment_interval` blocks the time spanned by the last interval is
+    /// compared with the target: under half the target raises the difficulty by one,
+    /// over twice the target lowers it by one, clamped to the configured range.
+    /// Between adjustments the previous block's difficulty carries over.
+    ///
+    /// Blocks that use a target take the whole leading zeros of `next_bits` instead.
+    pub fn next_difficulty(&self, blocks: &[Block]) -> usize {
+        let last = match blocks.last() {
+            Some(block) => block,
+            None => return self.initial_difficulty,
+        };
+        if last.uses_target() {
+            return Target::from_compact(self.next_bits(blocks))
+                .map_or(last.difficulty, |target| target.leading_zero_digits());
+        }
+
+        let interval = self.adjustment_interval.max(2) as usize;
+        let height = blocks.len();
+        if height < interval || !height.is_multiple_of(interval) {
+            return last.difficulty;
+        }
+
+        let first = &blocks[height - interval];
+        let actual = last.timestamp.saturating_sub(first.timestamp);
+        let expected = self.expected_interval_secs().unwrap_or(u64::MAX);
+
+        let difficulty = if actual < expected / 2 {
+            last.difficulty + 1
+        } else if actual > expected.saturating_mul(2) {
+            last.difficulty.saturating_sub(1)
+        } else {
+            last.difficulty
+        };
+        // Not `clamp`, which panics on an inverted range; `max_difficulty` wins then
+        difficulty.max(self.min_difficulty).min(self.max_difficulty)
+    }
+
+    /// Returns the compact target required for the block that follows `blocks`.
+    ///
+    /// Every `adjustment_interval` blocks the last target is scaled by the time the
+    /// interval actually took over the target time (see `retarget`), then kept
+    /// between the targets of `max_difficulty` and `min_difficulty`. Between
+    /// adjustments the previous block's target carries over.
+    pub fn next_bits(&self, blocks: &[Block]) -> u32 {
+        let last = match blocks.last() {
+            Some(block) => block,
+            None => return difficulty_to_target(self.initial_difficulty).to_compact(),
+        };
+        let Some(target) = last.target() else {
+            return last.bits;
+        };
+
+        let interval = self.adjustment_interval.max(2) as usize;
+        let height = blocks.len();
+        if height < interval || !height.is_multiple_of(interval) {
+            return target.to_compact();
+        }
+
+        let first = &blocks[height - interval];
+        let actual = last.timestamp.saturating_sub(first.timestamp);
+        let expected = self.expected_interval_secs().unwrap_or(u64::MAX);
+        // As in `next_difficulty`, the easiest target gives way on an inverted range
+        retarget(&target, actual, expected)
+            .min(difficulty_to_target(self.min_difficulty))
+            .max(difficulty_to_target(self.max_difficulty))
+            .to_compact()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::ZEROS_BLOCK_VERSION;
+    use crate::target::{difficulty_to_bits, target_to_difficulty_f64};
+
+    /// Leading-zeros blocks at `difficulty` spaced `spacing` seconds apart
+    fn spaced_blocks(count: u32, spacing: u64, difficulty: usize) -> Vec<Block> {
+        (0..count)
+            .map(|index| {
+                let mut block = Block::new(index, String::new(), Vec::new());
+                block.version = ZEROS_BLOCK_VERSION;
+                block.timestamp = 1_000 + index as u64 * spacing;
+                block.difficulty = difficulty;
+                block
+            })
+            .collect()
+    }
+
+    /// Blocks with compact target `bits` spaced `spacing` seconds apart
+    fn spaced_target_blocks(count: u32, spacing: u64, bits: u32) -> Vec<Block> {
+        (0..count)
+            .map(|index| {
+                let mut block = Block::new(index, String::new(), Vec::new());
+                block.timestamp = 1_000 + index as u64 * spacing;
+                block.bits = bits;
+                block
+            })
+            .collect()
+    }
+
+    fn config() -> DifficultyConfig {
+        DifficultyConfig {
+            adjustment_interval: 5,
+            ..DifficultyConfig::default()
+        }
+    }
+
+    #[test]
+    fn harder_blocks_count_for_more_work() {
+        assert_eq!(block_work(2), 256.0);
+        assert!(chain_work(&spaced_blocks(1, 0, 3)) > chain_work(&spaced_blocks(15, 0, 2)));
+        // A target block counts the same work as the leading zeros it stands for
+        let target_work = chain_work(&spaced_target_blocks(1, 0, difficulty_to_bits(3)));
+        assert!((target_work / block_work(3) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn retargeting_scales_by_the_observed_time() {
+        let target = difficulty_to_target(2);
+        assert_eq!(retarget(&target, 15, 30), target.scale(1, 2));
+        assert_eq!(retarget(&target, 60, 30), target.scale(2, 1));
+        // No more than 4x either way
+        assert_eq!(retarget(&target, 1, 40), target.scale(1, 4));
+        assert_eq!(retarget(&target, 0, 40), target.scale(1, 4));
+        assert_eq!(retarget(&target, 1_000, 40), target.scale(4, 1));
+    }
+
+    #[test]
+    fn target_chains_retarget_by_the_time_ratio() {
+        let config = config();
+        let bits = difficulty_to_bits(2);
+        assert_eq!(bits, 0x2000_ffff);
+        // Between adjustments the target carries over
+        assert_eq!(config.next_bits(&spaced_target_blocks(4, 5, bits)), bits);
+        // Twenty seconds for four forty-second gaps halves 0x00ffff00..00
+        let halved = config.next_bits(&spaced_target_blocks(5, 5, bits));
+        assert_eq!(halved, 0x1f7f_ff80);
+        let difficulty = target_to_difficulty_f64(&Target::from_compact(halved).unwrap());
+        assert!((difficulty - 2.25).abs() < 1e-4, "{}", difficulty);
+        assert_eq!(config.next_difficulty(&spaced_target_blocks(5, 5, bits)), 2);
+        // On-target blocks keep it, and the configured bounds still apply
+        assert_eq!(config.next_bits(&spaced_target_blocks(5, 10, bits)), bits);
+        assert_eq!(
+            config.next_bits(&spaced_target_blocks(5, 100, difficulty_to_bits(1))),
+            difficulty_to_bits(1)
+        );
+    }
+
+    #[test]
+    fn oversized_intervals_are_refused_and_never_overflow() {
+        let oversized = DifficultyConfig {
+            target_block_time_secs: u64::MAX / 2,
+            ..config()
+        };
+        assert_eq!(
+            oversized.validate(),
+            Err(DifficultyConfigError::IntervalTooLong {
+                target_block_time_secs: u64::MAX / 2,
+                adjustment_interval: 5
+            })
+        );
+        // Retargeting still saturates rather than overflowing: the blocks came far
+        // faster than expected, so both models get harder
+        let bits = difficulty_to_bits(2);
+        let next = Target::from_compact(oversized.next_bits(&spaced_target_blocks(5, 5, bits)));
+        assert!(next.unwrap() < difficulty_to_target(2));
+        assert_eq!(oversized.next_difficulty(&spaced_blocks(5, 5, 3)), 4);
+        assert_eq!(config().validate(), Ok(()));
+        let inverted = DifficultyConfig {
+            min_difficulty: 4,
+            max_difficulty: 2,
+            ..config()
+        };
+        assert_eq!(
+            inverted.validate(),
+            Err(DifficultyConfigError::InvalidRange { min: 4, max: 2 })
+        );
+        // Retargeting on an inverted range settles on the maximum instead of panicking
+        assert_eq!(inverted.next_difficulty(&spaced_blocks(5, 0, 3)), 2);
+        assert_eq!(inverted.next_difficulty(&spaced_blocks(5, 100, 3)), 2);
+        assert_eq!(
+            inverted.next_bits(&spaced_target_blocks(5, 100, difficulty_to_bits(3))),
+            difficulty_to_bits(2)
+        );
+    }
+
+    #[test]
+    fn empty_chains_start_at_the_initial_target() {
+        assert_eq!(config().next_bits(&[]), difficulty_to_bits(DIFFICULTY));
+    }
+
+    #[test]
+    fn empty_chain_uses_initial_difficulty() {
+        assert_eq!(config().next_difficulty(&[]), DIFFICULTY);
+    }
+
+    #[test]
+    fn difficulty_only_changes_on_interval_boundaries() {
+        assert_eq!(config().next_difficulty(&spaced_blocks(4, 0, 3)), 3);
+        assert_eq!(config().next_difficulty(&spaced_blocks(7, 0, 3)), 3);
+        assert_eq!(config().next_difficulty(&spaced_blocks(10, 0, 3)), 4);
+    }
+
+    #[test]
+    fn on_target_block_times_keep_difficulty() {
+        assert_eq!(config().next_difficulty(&spaced_blocks(5, 10, 3)), 3);
+    }
+
+    #[test]
+    fn adjustments_are_clamped() {
+        let config = config();
+        assert_eq!(config.next_difficulty(&spaced_blocks(5, 0, 5)), 5);
+        assert_eq!(config.next_difficulty(&spaced_blocks(5, 100, 1)), 1);
+    }
+}
@@ -0,0 +1,613 @@
+use crate::address::{Address, AddressError};
+use crate::block::{Block, MiningStats, ZEROS_BLOCK_VERSION};
+use crate::consensus::AuthoritySeal;
+use crate::difficulty::DifficultyConfig;
+use crate::limits::BlockLimits;
+use crate::reward::RewardSchedule;
+use crate::transaction::{Transaction, TransactionSignature};
+use crate::utxo::{TxInput, TxOutput};
+use std::fmt;
+use std::time::Duration;
+
+/// Reasons bytes can't be decoded
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a value
+    UnexpectedEnd,
+    /// A string isn't valid UTF-8
+    InvalidUtf8,
+    /// A flag or option tag is neither 0 nor 1
+    InvalidTag(u8),
+    /// A length or count doesn't fit this platform
+    ValueOutOfRange(u64),
+    /// Bytes are left over after the value
+    TrailingBytes(usize),
+    /// The file doesn't start with the expected magic bytes
+    BadMagic,
+    /// The file was written in a format version this build doesn't read
+    UnsupportedVersion(u8),
+    /// A string in an address field is a malformed wallet address
+    InvalidAddress(AddressError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            DecodeError::InvalidTag(tag) => write!(f, "invalid tag byte {}", tag),
+            DecodeError::ValueOutOfRange(value) => write!(f, "value {} is out of range", value),
+            DecodeError::TrailingBytes(count) => {
+                write!(f, "{} unexpected bytes after the value", count)
+            }
+            DecodeError::BadMagic => write!(f, "missing magic header"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            DecodeError::InvalidAddress(e) => write!(f, "invalid address: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Appends values in the canonical encoding: big-endian fixed-width integers,
+/// floats as their IEEE 754 bits and strings prefixed with their byte length
+#[derive(Debug, Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder::default()
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.u64(value.to_bits());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn raw(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads values written by `Encoder`
+#[derive(Debug)]
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Decoder<'a> {
+        Decoder { bytes }
+    }
+
+    /// Takes the next `count` bytes
+    pub fn take(&mut self, count: usize) -> Result<&'a [u8], DecodeError> {
+        if count > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().expect("took 4 bytes")))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DecodeError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().expect("took 8 bytes")))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, DecodeError> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+
+    pub fn string(&mut self) -> Result<String, DecodeError> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    /// Fails unless every byte has been read
+    pub fn finish(&self) -> Result<(), DecodeError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeError::TrailingBytes(self.bytes.len()))
+        }
+    }
+}
+
+/// Types with a canonical binary encoding
+pub trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+/// Types that can be read back from their canonical binary encoding
+pub trait Decode: Sized {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError>;
+}
+
+/// Encodes `value` on its own
+pub fn to_bytes<T: Encode>(value: &T) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    value.encode(&mut encoder);
+    encoder.into_bytes()
+}
+
+/// Decodes a value that must take up all of `bytes`
+pub fn from_bytes<T: Decode>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let mut decoder = Decoder::new(bytes);
+    let value = T::decode(&mut decoder)?;
+    decoder.finish()?;
+    Ok(value)
+}
+
+impl Encode for u32 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(*self);
+    }
+}
+
+impl Decode for u32 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.u32()
+    }
+}
+
+impl Encode for u64 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u64(*self);
+    }
+}
+
+impl Decode for u64 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.u64()
+    }
+}
+
+// Always 8 bytes, whatever the platform's pointer width
+impl Encode for usize {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u64(*self as u64);
+    }
+}
+
+impl Decode for usize {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        let value = decoder.u64()?;
+        usize::try_from(value).map_err(|_| DecodeError::ValueOutOfRange(value))
+    }
+}
+
+impl Encode for f64 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.f64(*self);
+    }
+}
+
+impl Decode for f64 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.f64()
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.bool(*self);
+    }
+}
+
+impl Decode for bool {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.bool()
+    }
+}
+
+impl Encode for Duration {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u64(self.as_secs());
+        encoder.u32(self.subsec_nanos());
+    }
+}
+
+impl Decode for Duration {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        let secs = decoder.u64()?;
+        let nanos = decoder.u32()?;
+        if nanos >= 1_000_000_000 {
+            return Err(DecodeError::ValueOutOfRange(nanos as u64));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.str(self);
+    }
+}
+
+impl Decode for String {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.string()
+    }
+}
+
+impl Encode for Address {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.str(self);
+    }
+}
+
+impl Decode for Address {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Address::parse_or_name(&decoder.string()?).map_err(DecodeError::InvalidAddress)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            None => encoder.bool(false),
+            Some(value) => {
+                encoder.bool(true);
+                value.encode(encoder);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        if decoder.bool()? {
+            Ok(Some(T::decode(decoder)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.len() as u32);
+        for item in self {
+            item.encode(encoder);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        let count = decoder.u32()?;
+        // Don't trust the count for the allocation; a short input fails soon enough
+        let mut items = Vec::with_capacity(count.min(1024) as usize);
+        for _ in 0..count {
+            items.push(T::decode(decoder)?);
+        }
+        Ok(items)
+    }
+}
+
+impl Encode for TransactionSignature {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.public_key.encode(encoder);
+        self.signature.encode(encoder);
+    }
+}
+
+impl Decode for TransactionSignature {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(TransactionSignature {
+            public_key: Decode::decode(decoder)?,
+            signature: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for TxInput {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.prev_tx_id.encode(encoder);
+        self.output_index.encode(encoder);
+        self.signature.encode(encoder);
+    }
+}
+
+impl Decode for TxInput {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(TxInput {
+            prev_tx_id: Decode::decode(decoder)?,
+            output_index: Decode::decode(decoder)?,
+            signature: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for TxOutput {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.amount.encode(encoder);
+        self.recipient.encode(encoder);
+    }
+}
+
+impl Decode for TxOutput {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(TxOutput {
+            amount: Decode::decode(decoder)?,
+            recipient: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.sender.encode(encoder);
+        self.recipient.encode(encoder);
+        self.amount.encode(encoder);
+        self.fee.encode(encoder);
+        self.nonce.encode(encoder);
+        self.timestamp.encode(encoder);
+        self.signature.encode(encoder);
+        self.inputs.encode(encoder);
+        self.outputs.encode(encoder);
+        self.expires_at.encode(encoder);
+    }
+}
+
+impl Decode for Transaction {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(Transaction {
+            sender: Decode::decode(decoder)?,
+            recipient: Decode::decode(decoder)?,
+            amount: Decode::decode(decoder)?,
+            fee: Decode::decode(decoder)?,
+            nonce: Decode::decode(decoder)?,
+            timestamp: Decode::decode(decoder)?,
+            signature: Decode::decode(decoder)?,
+            inputs: Decode::decode(decoder)?,
+            outputs: Decode::decode(decoder)?,
+            expires_at: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for AuthoritySeal {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.signer.encode(encoder);
+        self.signature.encode(encoder);
+    }
+}
+
+impl Decode for AuthoritySeal {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(AuthoritySeal {
+            signer: Decode::decode(decoder)?,
+            signature: Decode::decode(decoder)?,
+        })
+    }
+}
+
+// The header fields come first, in the order `Block::header_bytes` hashes them
+impl Encode for Block {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.version.encode(encoder);
+        self.index.encode(encoder);
+        self.previous_hash.encode(encoder);
+        self.timestamp.encode(encoder);
+        self.merkle_root.encode(encoder);
+        self.difficulty.encode(encoder);
+        if self.uses_target() {
+            self.bits.encode(encoder);
+        }
+        self.nonce.encode(encoder);
+        self.transactions.encode(encoder);
+        self.hash.encode(encoder);
+        self.mined.encode(encoder);
+        self.authority_seal.encode(encoder);
+        self.mining_stats.encode(encoder);
+    }
+}
+
+impl Decode for Block {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        let version = Decode::decode(decoder)?;
+        let index = Decode::decode(decoder)?;
+        let previous_hash = Decode::decode(decoder)?;
+        let timestamp = Decode::decode(decoder)?;
+        let merkle_root = Decode::decode(decoder)?;
+        let difficulty = Decode::decode(decoder)?;
+        // Blocks from before compact targets don't store `bits`
+        let bits = if version > ZEROS_BLOCK_VERSION {
+            Decode::decode(decoder)?
+        } else {
+            0
+        };
+        Ok(Block {
+            version,
+            index,
+            previous_hash,
+            timestamp,
+            merkle_root,
+            difficulty,
+            bits,
+            nonce: Decode::decode(decoder)?,
+            transactions: Decode::decode(decoder)?,
+            hash: Decode::decode(decoder)?,
+            mined: Decode::decode(decoder)?,
+            authority_seal: Decode::decode(decoder)?,
+            mining_stats: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for MiningStats {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.hashes_tried.encode(encoder);
+        self.restarts.encode(encoder);
+        self.elapsed.encode(encoder);
+    }
+}
+
+impl Decode for MiningStats {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(MiningStats {
+            hashes_tried: Decode::decode(decoder)?,
+            restarts: Decode::decode(decoder)?,
+            elapsed: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for DifficultyConfig {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.initial_difficulty.encode(encoder);
+        self.target_block_time_secs.encode(encoder);
+        self.adjustment_interval.encode(encoder);
+        self.min_difficulty.encode(encoder);
+        self.max_difficulty.encode(encoder);
+    }
+}
+
+impl Decode for DifficultyConfig {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(DifficultyConfig {
+            initial_difficulty: Decode::decode(decoder)?,
+            target_block_time_secs: Decode::decode(decoder)?,
+            adjustment_interval: Decode::decode(decoder)?,
+            min_difficulty: Decode::decode(decoder)?,
+            max_difficulty: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for RewardSchedule {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.initial_reward.encode(encoder);
+        self.halving_interval.encode(encoder);
+    }
+}
+
+impl Decode for RewardSchedule {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(RewardSchedule {
+            initial_reward: Decode::decode(decoder)?,
+            halving_interval: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for BlockLimits {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.max_transactions_per_block.encode(encoder);
+        self.max_block_bytes.encode(encoder);
+    }
+}
+
+impl Decode for BlockLimits {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(BlockLimits {
+            max_transactions_per_block: Decode::decode(decoder)?,
+            max_block_bytes: Decode::decode(decoder)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_use_fixed_widths_and_length_prefixes() {
+        let mut encoder = Encoder::new();
+        encoder.u32(1);
+        encoder.u64(2);
+        encoder.str("ab");
+        encoder.bool(true);
+        assert_eq!(
+            encoder.into_bytes(),
+            [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 2, b'a', b'b', 1]
+        );
+    }
+
+    #[test]
+    fn truncated_and_padded_input_is_rejected() {
+        let bytes = to_bytes(&String::from("hello"));
+        assert_eq!(
+            from_bytes::<String>(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert_eq!(
+            from_bytes::<String>(&padded),
+            Err(DecodeError::TrailingBytes(1))
+        );
+        assert_eq!(
+            from_bytes::<Option<u32>>(&[2]),
+            Err(DecodeError::InvalidTag(2))
+        );
+    }
+
+    #[test]
+    fn transactions_round_trip() {
+        let wallet = crate::wallet::Wallet::generate();
+        let mut transaction = Transaction::new(&wallet.address(), "Bob", 2.5).with_fee(0.1);
+        transaction.inputs.push(TxInput {
+            prev_tx_id: "ab".repeat(32),
+            output_index: 3,
+            signature: None,
+        });
+        transaction.outputs.push(TxOutput {
+            amount: 2.5,
+            recipient: "Bob".to_string(),
+        });
+        wallet.sign_transaction(&mut transaction).unwrap();
+
+        let bytes = to_bytes(&transaction);
+        assert_eq!(from_bytes::<Transaction>(&bytes), Ok(transaction));
+    }
+}
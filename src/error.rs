@@ -0,0 +1,427 @@
+use crate::address::AddressError;
+use crate::block::MiningError;
+use crate::blockchain::{BlockchainError, ChainValidationError, LoadError};
+use crate::competition::CompetitionError;
+use crate::consensus::SealError;
+use crate::encoding::DecodeError;
+use crate::network::{NetworkError, SubmitError};
+use crate::scenario::ScenarioError;
+use crate::snapshot::SnapshotError;
+use crate::time::TimeError;
+use crate::transaction::{MempoolError, SignatureError};
+use crate::wallet::WalletError;
+use std::fmt;
+use std::io;
+
+/// Result of the crate's top-level operations
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Any error the crate reports, wrapping the error of the module it came from.
+/// Library functions return their module's own error; this is for callers such as
+/// the CLI that combine several of them with `?`.
+#[derive(Debug)]
+pub enum Error {
+    /// Bad input from the user, e.g. a malformed argument
+    Usage(String),
+    /// Mining gave up or was cancelled
+    Mining(MiningError),
+    /// A consensus engine couldn't seal a block
+    Seal(SealError),
+    /// A block was refused by the chain
+    Block(BlockchainError),
+    /// A chain failed validation
+    Validation(ChainValidationError),
+    /// A chain, store or mempool file couldn't be loaded
+    Load(LoadError),
+    /// Binary data couldn't be decoded
+    Decode(DecodeError),
+    /// A snapshot couldn't be exported or was refused on import
+    Snapshot(SnapshotError),
+    /// JSON couldn't be read or written
+    Json(serde_json::Error),
+    /// A transaction's signature was refused
+    Signature(SignatureError),
+    /// A wallet couldn't be created, opened or used
+    Wallet(WalletError),
+    /// A string isn't a valid address
+    Address(AddressError),
+    /// The mempool refused a transaction, e.g. for an insufficient balance
+    Mempool(MempoolError),
+    /// A node refused a submitted transaction
+    Submit(SubmitError),
+    /// A scenario file is malformed or didn't play out as written
+    Scenario(ScenarioError),
+    /// A mining competition was misconfigured or its chain refused a block
+    Competition(CompetitionError),
+    /// A time couldn't be parsed or formatted
+    Time(TimeError),
+    /// Talking to a peer failed
+    Network(NetworkError),
+    /// Reading or writing a file or stream failed
+    Io(io::Error),
+}
+
+/// What kind of problem an `Error` is; the CLI exits with a code per kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Usage,
+    Mining,
+    Validation,
+    Persistence,
+    Signature,
+    Balance,
+    Network,
+    Io,
+}
+
+impl ErrorKind {
+    /// Process exit code for errors of this kind. 2 is left to argument parsing.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 1,
+            ErrorKind::Mining => 3,
+            ErrorKind::Validation => 4,
+            ErrorKind::Persistence => 5,
+            ErrorKind::Signature => 6,
+            ErrorKind::Balance => 7,
+            ErrorKind::Network => 8,
+            ErrorKind::Io => 9,
+        }
+    }
+}
+
+impl Error {
+    /// The kind of problem, looking inside wrapped errors where that is more specific
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Usage(_) | Error::Address(_) | Error::Scenario(_) | Error::Time(_) => {
+                ErrorKind::Usage
+            }
+            Error::Mining(_) | Error::Seal(_) => ErrorKind::Mining,
+            Error::Block(BlockchainError::InvalidSignature { .. })
+            | Error::Validation(ChainValidationError::InvalidSignature { .. })
+            | Error::Mempool(MempoolError::InvalidSignature(_))
+            | Error::Signature(_)
+            | Error::Wallet(_) => ErrorKind::Signature,
+            Error::Block(BlockchainError::SealFailed { .. }) => ErrorKind::Mining,
+            Error::Competition(CompetitionError::Block(_)) => ErrorKind::Validation,
+            Error::Competition(_) => ErrorKind::Usage,
+            Error::Block(BlockchainError::Storage { .. }) => ErrorKind::Persistence,
+            Error::Block(_)
+            | Error::Validation(_)
+            | Error::Load(LoadError::Invalid(_) | LoadError::Rejected(_))
+            | Error::Snapshot(SnapshotError::InvalidBlock { .. }) => ErrorKind::Validation,
+            Error::Load(_) | Error::Decode(_) | Error::Snapshot(_) | Error::Json(_) => {
+                ErrorKind::Persistence
+            }
+            Error::Mempool(_) | Error::Submit(_) => ErrorKind::Balance,
+            Error::Network(_) => ErrorKind::Network,
+            Error::Io(_) => ErrorKind::Io,
+        }
+    }
+
+    /// Shorthand for `kind().exit_code()`
+    pub fn exit_code(&self) -> i32 {
+        self.kind().exit_code()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(message) => write!(f, "{}", message),
+            Error::Mining(e) => write!(f, "{}", e),
+            Error::Seal(e) => write!(f, "{}", e),
+            Error::Block(e) => write!(f, "{}", e),
+            Error::Validation(e) => write!(f, "{}", e),
+            Error::Load(e) => write!(f, "{}", e),
+            Error::Decode(e) => write!(f, "{}", e),
+            Error::Snapshot(e) => write!(f, "{}", e),
+            Error::Json(e) => write!(f, "{}", e),
+            Error::Signature(e) => write!(f, "{}", e),
+            Error::Wallet(e) => write!(f, "{}", e),
+            Error::Address(e) => write!(f, "{}", e),
+            Error::Mempool(e) => write!(f, "{}", e),
+            Error::Submit(e) => write!(f, "{}", e),
+            Error::Scenario(e) => write!(f, "{}", e),
+            Error::Competition(e) => write!(f, "{}", e),
+            Error::Time(e) => write!(f, "{}", e),
+            Error::Network(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Usage(_) => None,
+            Error::Mining(e) => Some(e),
+            Error::Seal(e) => Some(e),
+            Error::Block(e) => Some(e),
+            Error::Validation(e) => Some(e),
+            Error::Load(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Snapshot(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Signature(e) => Some(e),
+            Error::Wallet(e) => Some(e),
+            Error::Address(e) => Some(e),
+            Error::Mempool(e) => Some(e),
+            Error::Submit(e) => Some(e),
+            Error::Scenario(e) => Some(e),
+            Error::Competition(e) => Some(e),
+            Error::Time(e) => Some(e),
+            Error::Network(e) => Some(e),
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Usage(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Usage(message.to_string())
+    }
+}
+
+impl From<MiningError> for Error {
+    fn from(e: MiningError) -> Self {
+        Error::Mining(e)
+    }
+}
+
+impl From<SealError> for Error {
+    fn from(e: SealError) -> Self {
+        Error::Seal(e)
+    }
+}
+
+impl From<BlockchainError> for Error {
+    fn from(e: BlockchainError) -> Self {
+        Error::Block(e)
+    }
+}
+
+impl From<ChainValidationError> for Error {
+    fn from(e: ChainValidationError) -> Self {
+        Error::Validation(e)
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(e: LoadError) -> Self {
+        Error::Load(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<SnapshotError> for Error {
+    fn from(e: SnapshotError) -> Self {
+        Error::Snapshot(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<SignatureError> for Error {
+    fn from(e: SignatureError) -> Self {
+        Error::Signature(e)
+    }
+}
+
+impl From<WalletError> for Error {
+    fn from(e: WalletError) -> Self {
+        Error::Wallet(e)
+    }
+}
+
+impl From<AddressError> for Error {
+    fn from(e: AddressError) -> Self {
+        Error::Address(e)
+    }
+}
+
+impl From<MempoolError> for Error {
+    fn from(e: MempoolError) -> Self {
+        Error::Mempool(e)
+    }
+}
+
+impl From<SubmitError> for Error {
+    fn from(e: SubmitError) -> Self {
+        Error::Submit(e)
+    }
+}
+
+impl From<ScenarioError> for Error {
+    fn from(e: ScenarioError) -> Self {
+        Error::Scenario(e)
+    }
+}
+
+impl From<CompetitionError> for Error {
+    fn from(e: CompetitionError) -> Self {
+        Error::Competition(e)
+    }
+}
+
+impl From<TimeError> for Error {
+    fn from(e: TimeError) -> Self {
+        Error::Time(e)
+    }
+}
+
+impl From<NetworkError> for Error {
+    fn from(e: NetworkError) -> Self {
+        Error::Network(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blockchain::Blockchain;
+    use crate::transaction::{Mempool, Transaction};
+    use crate::wallet::Wallet;
+    use std::fs;
+
+    #[test]
+    fn blocks_at_the_wrong_index_are_validation_errors() {
+        let mut blockchain = Blockchain::new();
+        let tip = blockchain.tip();
+        let mut block = Block::new(5, tip.hash.clone(), Vec::new());
+        block.timestamp = tip.timestamp + 1;
+
+        let error = blockchain.add_block(block).unwrap_err();
+        assert_eq!(
+            error,
+            BlockchainError::InvalidIndex {
+                expected: 1,
+                found: 5
+            }
+        );
+        assert_eq!(Error::from(error).kind(), ErrorKind::Validation);
+    }
+
+    #[test]
+    fn corrupt_chain_files_are_persistence_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chain.json");
+
+        fs::write(&path, "{\"chain\": [").unwrap();
+        let error = Blockchain::load_from_file(&path).unwrap_err();
+        assert!(matches!(error, LoadError::Parse(_)), "{:?}", error);
+        assert_eq!(Error::from(error).kind(), ErrorKind::Persistence);
+
+        let bytes = Blockchain::new().to_bytes();
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        let error = Blockchain::load_from_file(&path).unwrap_err();
+        assert!(
+            matches!(error, LoadError::Decode(DecodeError::UnexpectedEnd)),
+            "{:?}",
+            error
+        );
+
+        // A file that parses but holds no blocks used to panic on first use
+        let mut json = serde_json::to_value(Blockchain::new()).unwrap();
+        json["chain"] = serde_json::json!([]);
+        fs::write(&path, json.to_string()).unwrap();
+        let error = Blockchain::load_from_file(&path).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                LoadError::Invalid(ChainValidationError::MissingGenesis)
+            ),
+            "{:?}",
+            error
+        );
+        assert_eq!(Error::from(error).kind(), ErrorKind::Validation);
+    }
+
+    #[test]
+    fn badly_signed_transactions_are_signature_errors() {
+        let wallet = Wallet::generate();
+        let mut transaction = Transaction::new(&wallet.address(), "Bob", 5.0);
+        wallet.sign_transaction(&mut transaction).unwrap();
+        transaction.amount = 500.0;
+
+        let error = Mempool::new().add_transaction(transaction).unwrap_err();
+        assert_eq!(
+            error,
+            MempoolError::InvalidSignature(SignatureError::Invalid)
+        );
+        let error = Error::from(error);
+        assert_eq!(error.kind(), ErrorKind::Signature);
+        assert_eq!(error.exit_code(), 6);
+        assert_eq!(error.to_string(), "transaction signature is invalid");
+    }
+
+    #[test]
+    fn wrapped_errors_are_exposed_as_the_source() {
+        use std::error::Error as _;
+
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "no chain"));
+        let source = error.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        let refused = BlockchainError::InvalidIndex {
+            expected: 1,
+            found: 5,
+        };
+        let error = Error::from(refused.clone());
+        assert_eq!(
+            error.source().unwrap().downcast_ref::<BlockchainError>(),
+            Some(&refused)
+        );
+
+        let json = serde_json::from_str::<Blockchain>("{").unwrap_err();
+        let error = Error::from(json);
+        assert!(error.source().unwrap().is::<serde_json::Error>());
+
+        assert!(Error::from("bad argument").source().is_none());
+    }
+
+    #[test]
+    fn exit_codes_are_distinct_per_kind() {
+        let kinds = [
+            ErrorKind::Usage,
+            ErrorKind::Mining,
+            ErrorKind::Validation,
+            ErrorKind::Persistence,
+            ErrorKind::Signature,
+            ErrorKind::Balance,
+            ErrorKind::Network,
+            ErrorKind::Io,
+        ];
+        let mut codes: Vec<i32> = kinds.iter().map(|kind| kind.exit_code()).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), kinds.len());
+        assert!(!codes.contains(&0) && !codes.contains(&2));
+    }
+}
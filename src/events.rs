@@ -0,0 +1,120 @@
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Events each subscriber can have waiting before further ones are dropped for it
+pub const EVENT_BUFFER: usize = 1024;
+
+/// Something that happened to a chain or its mempool
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent {
+    /// A block joined the best chain, after any `Reorg` that brought it there
+    BlockAdded { height: u32, hash: String },
+    /// The best chain switched to a competing branch
+    Reorg {
+        old_tip: String,
+        new_tip: String,
+        depth: usize, // Blocks abandoned from the old best chain
+    },
+    /// The mempool admitted the transaction with this id
+    TransactionAccepted(String),
+    /// The transaction was refused, with the error's message
+    TransactionRejected { tx_id: String, reason: String },
+}
+
+/// Fans events out to any number of subscribers. Clones publish to the same
+/// subscribers.
+///
+/// Each subscriber gets a bounded channel so a slow one can't hold up mining or
+/// grow without limit: once `EVENT_BUFFER` events are waiting for it, newer ones
+/// are dropped for that subscriber and counted in `dropped`. Subscribers whose
+/// receiver is gone are forgotten.
+#[derive(Debug, Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+#[derive(Debug, Default)]
+struct Subscribers {
+    senders: Vec<SyncSender<ChainEvent>>,
+    dropped: u64,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, Subscribers> {
+        self.subscribers.lock().expect("event bus lock poisoned")
+    }
+
+    /// Returns a receiver for every event published from now on
+    pub fn subscribe(&self) -> Receiver<ChainEvent> {
+        let (sender, receiver) = mpsc::sync_channel(EVENT_BUFFER);
+        self.subscribers().senders.push(sender);
+        receiver
+    }
+
+    /// Sends `event` to every subscriber without waiting on any of them
+    pub fn publish(&self, event: ChainEvent) {
+        let mut subscribers = self.subscribers();
+        let mut dropped = 0;
+        subscribers
+            .senders
+            .retain(|sender| match sender.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    dropped += 1;
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+        subscribers.dropped += dropped;
+    }
+
+    /// Events dropped so far because a subscriber's buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.subscribers().dropped
+    }
+
+    /// Number of live subscribers, as of the last published event
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers().senders.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepted(n: usize) -> ChainEvent {
+        ChainEvent::TransactionAccepted(n.to_string())
+    }
+
+    #[test]
+    fn full_subscribers_lose_events_without_blocking_others() {
+        let bus = EventBus::new();
+        let slow = bus.subscribe();
+        let fast = bus.subscribe();
+        for n in 0..EVENT_BUFFER + 5 {
+            bus.publish(accepted(n));
+            assert_eq!(fast.try_recv(), Ok(accepted(n)));
+        }
+
+        assert_eq!(bus.dropped(), 5);
+        assert_eq!(slow.try_iter().count(), EVENT_BUFFER);
+        assert_eq!(slow.try_recv().ok(), None);
+    }
+
+    #[test]
+    fn closed_subscribers_are_forgotten() {
+        let bus = EventBus::new();
+        let kept = bus.subscribe();
+        drop(bus.subscribe());
+        bus.clone().publish(accepted(1));
+
+        assert_eq!(bus.subscriber_count(), 1);
+        assert_eq!(kept.recv(), Ok(accepted(1)));
+        assert_eq!(bus.dropped(), 0);
+    }
+}
@@ -0,0 +1,389 @@
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::target::{target_to_difficulty_f64, Target};
+use crate::time::format_timestamp;
+use crate::transaction::Transaction;
+use std::fmt::Write;
+
+/// Hex digits shown of a hash when it's abbreviated
+const SHORT_HASH_LEN: usize = 16;
+
+/// How much of a block `format_block` shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// Abbreviated hashes and one line per transaction
+    #[default]
+    Brief,
+    /// Full hashes, plus fees and nonces in the transaction table
+    Detailed,
+}
+
+/// Multi-line view of a block: its header fields and a table of its transactions
+pub fn format_block(block: &Block, verbosity: Verbosity) -> String {
+    let hash = |hash: &str| match verbosity {
+        Verbosity::Brief => short(hash).to_string(),
+        Verbosity::Detailed => hash.to_string(),
+    };
+    let previous_hash = if block.previous_hash.is_empty() {
+        "-".to_string()
+    } else {
+        hash(&block.previous_hash)
+    };
+
+    let mut out = String::new();
+    writeln!(out, "Block {}", block.index).unwrap();
+    let mut fields = vec![
+        ("Hash", block.hash.clone()),
+        ("Previous Hash", previous_hash),
+        ("Merkle Root", hash(&block.merkle_root)),
+        ("Timestamp", format_timestamp(block.timestamp)),
+        ("Difficulty", difficulty(block)),
+    ];
+    if block.uses_target() {
+        fields.push(("Bits", format!("{:#010x}", block.bits)));
+    }
+    fields.extend([
+        ("Nonce", block.nonce.to_string()),
+        ("Version", block.version.to_string()),
+        ("Transactions", block.transactions.len().to_string()),
+    ]);
+    write_fields(&mut out, &fields);
+    if block.transactions.is_empty() {
+        return out;
+    }
+
+    out.push('\n');
+    let rows: Vec<Vec<String>> = block
+        .transactions
+        .iter()
+        .map(|tx| {
+            let mut row = vec![
+                hash(&tx.calculate_hash()),
+                tx.sender.to_string(),
+                tx.recipient.to_string(),
+                tx.amount.to_string(),
+            ];
+            if verbosity == Verbosity::Detailed {
+                row.push(tx.fee.to_string());
+                row.push(tx.nonce.to_string());
+            }
+            row
+        })
+        .collect();
+    let headers: &[&str] = match verbosity {
+        Verbosity::Brief => &["Id", "Sender", "Recipient", "Amount"],
+        Verbosity::Detailed => &["Id", "Sender", "Recipient", "Amount", "Fee", "Nonce"],
+    };
+    write_table(&mut out, headers, &rows);
+    out
+}
+
+/// Multi-line view of a confirmed transaction and the block that holds it
+pub fn format_transaction(block: &Block, transaction: &Transaction) -> String {
+    let mut out = String::new();
+    writeln!(out, "Transaction {}", transaction.calculate_hash()).unwrap();
+    let mut fields = vec![
+        ("Block", format!("{} ({})", block.index, short(&block.hash))),
+        ("Timestamp", format_timestamp(transaction.timestamp)),
+        ("Sender", transaction.sender.to_string()),
+        ("Recipient", transaction.recipient.to_string()),
+        ("Amount", transaction.amount.to_string()),
+        ("Fee", transaction.fee.to_string()),
+        ("Nonce", transaction.nonce.to_string()),
+    ];
+    if let Some(expires_at) = transaction.expires_at {
+        fields.push(("Expires", format_timestamp(expires_at)));
+    }
+    if transaction.signature.is_some() {
+        fields.push(("Signed", "yes".to_string()));
+    }
+    for input in &transaction.inputs {
+        fields.push(("Input", input.outpoint().to_string()));
+    }
+    for output in &transaction.outputs {
+        fields.push((
+            "Output",
+            format!("{} to {}", output.amount, output.recipient),
+        ));
+    }
+    write_fields(&mut out, &fields);
+    out
+}
+
+/// Every transaction on the best chain that touches `address`, oldest first, with
+/// the balance after each
+pub fn format_address_history(blockchain: &Blockchain, address: &str) -> String {
+    let mut rows = Vec::new();
+    let mut balance = 0.0;
+    for block in blockchain {
+        for tx in &block.transactions {
+            let received = if tx.recipient == address {
+                tx.amount
+            } else {
+                0.0
+            };
+            let sent = if tx.sender == address && !tx.is_coinbase() {
+                tx.total_cost()
+            } else {
+                0.0
+            };
+            if tx.recipient != address && tx.sender != address {
+                continue;
+            }
+
+            let change = received - sent;
+            balance += change;
+            let counterparty = if tx.sender == address {
+                &tx.recipient
+            } else {
+                &tx.sender
+            };
+            rows.push(vec![
+                block.index.to_string(),
+                short(&tx.calculate_hash()).to_string(),
+                counterparty.to_string(),
+                signed(change),
+                balance.to_string(),
+            ]);
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "Address {}", address).unwrap();
+    if rows.is_empty() {
+        writeln!(out, "No transactions").unwrap();
+        return out;
+    }
+    out.push('\n');
+    write_table(
+        &mut out,
+        &["Block", "Transaction", "Counterparty", "Change", "Balance"],
+        &rows,
+    );
+    writeln!(out, "\nFinal Balance: {}", balance).unwrap();
+    out
+}
+
+/// Overview of the best chain and a line per block
+pub fn format_chain_summary(blockchain: &Blockchain) -> String {
+    let tip = blockchain.tip();
+    let transactions: usize = blockchain
+        .iter()
+        .map(|block| block.transactions.len())
+        .sum();
+
+    let mut out = String::new();
+    writeln!(out, "Chain of {} blocks", blockchain.get_total_blocks()).unwrap();
+    let fields = [
+        ("Tip", format!("{} (block {})", tip.hash, tip.index)),
+        ("Next Difficulty", next_difficulty(blockchain, tip)),
+        ("Transactions", transactions.to_string()),
+        ("Total Supply", blockchain.total_supply().to_string()),
+        ("Side Blocks", blockchain.side_block_count().to_string()),
+    ];
+    write_fields(&mut out, &fields);
+
+    out.push('\n');
+    let rows: Vec<Vec<String>> = blockchain
+        .iter()
+        .map(|block| {
+            vec![
+                block.index.to_string(),
+                short(&block.hash).to_string(),
+                format_timestamp(block.timestamp),
+                difficulty(block),
+                block.transactions.len().to_string(),
+            ]
+        })
+        .collect();
+    write_table(
+        &mut out,
+        &["Block", "Hash", "Timestamp", "Difficulty", "Transactions"],
+        &rows,
+    );
+    out
+}
+
+/// The block's difficulty; for target blocks the fractional difficulty of `bits`
+fn difficulty(block: &Block) -> String {
+    if block.uses_target() {
+        bits_difficulty(block.bits)
+    } else {
+        block.difficulty.to_string()
+    }
+}
+
+/// Difficulty required of the block after `tip`
+fn next_difficulty(blockchain: &Blockchain, tip: &Block) -> String {
+    if tip.uses_target() {
+        bits_difficulty(blockchain.current_bits())
+    } else {
+        blockchain.current_difficulty().to_string()
+    }
+}
+
+fn bits_difficulty(bits: u32) -> String {
+    Target::from_compact(bits).map_or_else(
+        || "invalid".to_string(),
+        |target| format!("{:.2}", target_to_difficulty_f64(&target)),
+    )
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..SHORT_HASH_LEN.min(hash.len())]
+}
+
+/// `+5` or `-5`, so incoming and outgoing amounts line up
+fn signed(amount: f64) -> String {
+    if amount < 0.0 {
+        amount.to_string()
+    } else {
+        format!("+{}", amount)
+    }
+}
+
+/// Indented `Name: value` lines with the values aligned
+fn write_fields(out: &mut String, fields: &[(&str, String)]) {
+    let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0) + 1;
+    for (name, value) in fields {
+        writeln!(out, "  {:<width$} {}", format!("{}:", name), value).unwrap();
+    }
+}
+
+/// Columns padded to their widest cell, separated by two spaces
+fn write_table(out: &mut String, headers: &[&str], rows: &[Vec<String>]) {
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            rows.iter()
+                .map(|row| row[column].len())
+                .chain([header.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let headers: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+    for row in [&headers].into_iter().chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell))
+            .collect();
+        writeln!(out, "  {}", line.join("  ").trim_end()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::transaction::Mempool;
+    use std::sync::Arc;
+
+    /// A chain on a stopped clock, so every hash is the same on every run
+    fn chain() -> Blockchain {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let mut blockchain = Blockchain::with_clock(clock.clone());
+        let mut mempool = Mempool::new();
+        clock.advance(10);
+        blockchain
+            .mine_pending_transactions(&mut mempool, "alice")
+            .unwrap();
+        let payment = Transaction::new_with_clock("alice", "bob", 30.0, clock.as_ref());
+        mempool.add_transaction(payment.with_fee(0.5)).unwrap();
+        clock.advance(10);
+        blockchain
+            .mine_pending_transactions(&mut mempool, "carol")
+            .unwrap();
+        blockchain
+    }
+
+    #[test]
+    fn block_view_is_stable() {
+        let blockchain = chain();
+        let block = blockchain.get_block_by_index(2).unwrap();
+        assert_eq!(
+            format_block(block, Verbosity::Brief),
+            "Block 2
+  Hash:          00b766ca01deaa911af63d87591c895ed5858240d13d96d93854529e537b4bda
+  Previous Hash: 003b077c67f5b602
+  Merkle Root:   dcb04f99b3b33120
+  Timestamp:     2023-11-14T22:13:40Z
+  Difficulty:    2.00
+  Bits:          0x2000ffff
+  Nonce:         227
+  Version:       3
+  Transactions:  2
+
+  Id                Sender    Recipient  Amount
+  ff90d30bb57e5c0f  COINBASE  carol      137.5
+  209f670588eb91b1  alice     bob        30
+"
+        );
+    }
+
+    #[test]
+    fn detailed_block_view_has_full_hashes() {
+        let blockchain = chain();
+        let block = blockchain.get_block_by_index(2).unwrap();
+        let view = format_block(block, Verbosity::Detailed);
+        assert!(view.contains(&block.previous_hash));
+        assert!(view.contains(&block.transactions[1].calculate_hash()));
+        assert!(view.lines().any(|line| line.ends_with("Fee  Nonce")));
+    }
+
+    #[test]
+    fn address_history_keeps_a_running_balance() {
+        let blockchain = chain();
+        assert_eq!(
+            format_address_history(&blockchain, "alice"),
+            "Address alice
+
+  Block  Transaction       Counterparty  Change  Balance
+  1      83606e7ad3ad7e1e  COINBASE      +137    137
+  2      209f670588eb91b1  bob           -30.5   106.5
+
+Final Balance: 106.5
+"
+        );
+        assert_eq!(
+            format_address_history(&blockchain, "dave"),
+            "Address dave\nNo transactions\n"
+        );
+    }
+
+    #[test]
+    fn transaction_and_chain_views_are_stable() {
+        let blockchain = chain();
+        let block = blockchain.get_block_by_index(2).unwrap();
+        assert_eq!(
+            format_transaction(block, &block.transactions[1]),
+            "Transaction 209f670588eb91b1ee800a939cf8cb59491b647e71ded1d9eb0e016629350527
+  Block:     2 (00b766ca01deaa91)
+  Timestamp: 2023-11-14T22:13:30Z
+  Sender:    alice
+  Recipient: bob
+  Amount:    30
+  Fee:       0.5
+  Nonce:     0
+"
+        );
+        assert_eq!(
+            format_chain_summary(&blockchain),
+            "Chain of 3 blocks
+  Tip:             00b766ca01deaa911af63d87591c895ed5858240d13d96d93854529e537b4bda (block 2)
+  Next Difficulty: 2.00
+  Transactions:    3
+  Total Supply:    274
+  Side Blocks:     0
+
+  Block  Hash              Timestamp             Difficulty  Transactions
+  0      00c0ce7bb9bebb2d  2023-11-14T22:13:20Z  2.00        0
+  1      003b077c67f5b602  2023-11-14T22:13:30Z  2.00        1
+  2      00b766ca01deaa91  2023-11-14T22:13:40Z  2.00        2
+"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use crate::block::{Block, DIFFICULTY};
+use crate::clock::{Clock, SystemClock};
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+
+/// Describes block 0 of a chain. Two nodes with the same config build the same
+/// genesis block, down to its hash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    pub timestamp: u64,    // UNIX timestamp of the genesis block and its allocations
+    pub difficulty: usize, // Leading zeros the genesis block is mined at
+    pub allocations: Vec<(String, f64)>, // Premined (address, amount) pairs, one per address
+}
+
+impl GenesisConfig {
+    /// Creates a config with no allocations
+    pub fn new(timestamp: u64, difficulty: usize) -> GenesisConfig {
+        GenesisConfig {
+            timestamp,
+            difficulty,
+            allocations: Vec::new(),
+        }
+    }
+
+    /// Adds a premined allocation of `amount` to `address`
+    pub fn with_allocation(mut self, address: &str, amount: f64) -> GenesisConfig {
+        self.allocations.push((address.to_string(), amount));
+        self
+    }
+
+    /// Builds and mines the genesis block. The result only depends on the config.
+    pub fn build_block(&self) -> Block {
+        let mut genesis_block = self.unsealed_block();
+        genesis_block
+            .mine_block_with_difficulty(self.difficulty)
+            .expect("the default limits cover the whole nonce space");
+        // Every node builds the same genesis block, so it can't carry one run's timings
+        genesis_block.mining_stats = None;
+        genesis_block
+    }
+
+    /// Builds the genesis block without sealing it. Each allocation becomes a
+    /// coinbase transaction stamped with the config's timestamp.
+    pub fn unsealed_block(&self) -> Block {
+        let transactions = self
+            .allocations
+            .iter()
+            .map(|(address, amount)| {
+                let mut allocation = Transaction::coinbase(address, *amount, 0);
+                allocation.timestamp = self.timestamp;
+                allocation
+            })
+            .collect();
+
+        let mut genesis_block = Block::new(0, String::new(), transactions);
+        genesis_block.timestamp = self.timestamp;
+        genesis_block.set_difficulty_target(self.difficulty);
+        genesis_block
+    }
+}
+
+impl Default for GenesisConfig {
+    /// An empty genesis block stamped with the current time
+    fn default() -> Self {
+        GenesisConfig::new(SystemClock.now_secs(), DIFFICULTY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn genesis_block_depends_only_on_config() {
+        let config = GenesisConfig::new(1_700_000_000, 2)
+            .with_allocation("Alice", 500.0)
+            .with_allocation("Bob", 250.0);
+
+        let first = config.build_block();
+        let second = config.clone().build_block();
+        assert_eq!(first, second);
+        assert_eq!(first.timestamp, 1_700_000_000);
+        assert_eq!(first.transactions.len(), 2);
+        assert!(first.transactions.iter().all(Transaction::is_coinbase));
+
+        let other = GenesisConfig::new(1_700_000_001, 2).build_block();
+        assert_ne!(other.hash, first.hash);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::network::Node;
+use crate::transaction::Transaction;
+use serde::Serialize;
+use serde_json::json;
+use std::io;
+use std::net::SocketAddr;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Starts a REST server for `node` on `addr` in the background and returns the bound address.
+///
+/// Routes: `GET /chain`, `GET /block/{index}`, `GET /balance/{address}`, `GET /mempool`,
+/// `POST /transaction` (a `Transaction` as JSON) and `POST /mine?miner={address}`.
+/// Errors come back as `{"error": "..."}` with a 4xx or 5xx status.
+pub fn serve(node: Node, addr: &str) -> io::Result<SocketAddr> {
+    let server = Server::http(addr).map_err(io::Error::other)?;
+    let local_addr = server
+        .server_addr()
+        .to_ip()
+        .ok_or_else(|| io::Error::other("server is not bound to an IP address"))?;
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(&node, request);
+        }
+    });
+    Ok(local_addr)
+}
+
+/// A status code and JSON body to send back
+struct Reply {
+    status: u16,
+    body: serde_json::Value,
+}
+
+impl Reply {
+    fn ok(status: u16, body: impl Serialize) -> Reply {
+        Reply {
+            status,
+            body: serde_json::to_value(body).expect("response types serialize to JSON"),
+        }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Reply {
+        Reply {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+}
+
+fn handle(node: &Node, mut request: Request) {
+    let reply = route(node, &mut request);
+    let header =
+        Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+    let response = Response::from_string(reply.body.to_string())
+        .with_status_code(reply.status)
+        .with_header(header);
+    // The client may have hung up; there is no one left to tell
+    let _ = request.respond(response);
+}
+
+fn route(node: &Node, request: &mut Request) -> Reply {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["chain"]) => Reply::ok(200, node.best_chain()),
+        (Method::Get, ["block", index]) => match index.parse::<u32>() {
+            Ok(index) => match node.block(index) {
+                Some(block) => Reply::ok(200, block),
+                None => Reply::error(404, format!("no block with index {}", index)),
+            },
+            Err(_) => Reply::error(400, format!("invalid block index {}", index)),
+        },
+        (Method::Get, ["balance", address]) => Reply::ok(
+            200,
+            json!({ "address": address, "balance": node.balance(address) }),
+        ),
+        (Method::Get, ["mempool"]) => Reply::ok(200, node.pending_transactions()),
+        (Method::Post, ["transaction"]) => submit_transaction(node, request),
+        (Method::Post, ["mine"]) => {
+            let miner = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("miner="))
+                .filter(|miner| !miner.is_empty());
+            match miner {
+                Some(miner) => match node.mine(miner) {
+                    Ok(block) => Reply::ok(200, block),
+                    Err(e) => Reply::error(500, e),
+                },
+                None => Reply::error(400, "missing miner query parameter"),
+            }
+        }
+        (
+            _,
+            ["chain"] | ["block", _] | ["balance", _] | ["mempool"] | ["transaction"] | ["mine"],
+        ) => Reply::error(405, "method not allowed"),
+        _ => Reply::error(404, format!("no route for {}", path)),
+    }
+}
+
+fn submit_transaction(node: &Node, request: &mut Request) -> Reply {
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        return Reply::error(400, e);
+    }
+    let transaction: Transaction = match serde_json::from_str(&body) {
+        Ok(transaction) => transaction,
+        Err(e) => return Reply::error(400, format!("invalid transaction: {}", e)),
+    };
+
+    let id = transaction.calculate_hash();
+    match node.submit_transaction(transaction) {
+        Ok(()) => Reply::ok(202, json!({ "id": id })),
+        Err(e) => Reply::error(400, e),
+    }
+}
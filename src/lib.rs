@@ -0,0 +1,38 @@
+//! Blockchain simulation library: blocks, proof-of-work mining and the chain itself.
+
+pub mod address;
+pub mod block;
+pub mod blockchain;
+pub mod clock;
+pub mod competition;
+pub mod consensus;
+pub mod difficulty;
+pub mod encoding;
+pub mod error;
+pub mod events;
+pub mod explorer;
+pub mod genesis;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod light;
+pub mod limits;
+pub mod merkle;
+pub mod network;
+pub mod orphan;
+pub mod reward;
+pub mod scenario;
+pub mod simulation;
+pub mod snapshot;
+pub mod stats;
+pub mod store;
+pub mod target;
+pub mod time;
+pub mod transaction;
+pub mod utxo;
+pub mod wallet;
+
+pub use address::Address;
+pub use block::Block;
+pub use blockchain::Blockchain;
+pub use error::Error;
+pub use transaction::{Mempool, Transaction};
@@ -0,0 +1,317 @@
+//! Light (SPV) client: keeps only block headers and checks that a transaction is in
+//! the chain with a merkle proof served by a full node.
+
+use crate::block::BlockHeader;
+use crate::merkle::{verify_proof, MerkleProof};
+use crate::transaction::Transaction;
+use std::fmt;
+
+/// Reasons `HeaderChain` refuses a header
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderError {
+    /// The header isn't at the next height
+    InvalidIndex { expected: u32, found: u32 },
+    /// The header uses a different block version from the genesis header
+    InvalidVersion {
+        index: u32,
+        expected: u32,
+        found: u32,
+    },
+    /// The header's previous hash isn't the hash of the current tip
+    BrokenLink { index: u32 },
+    /// The stored hash isn't the hash of the header's fields
+    HashMismatch { index: u32 },
+    /// The hash doesn't meet the header's target
+    InsufficientWork { index: u32 },
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeaderError::InvalidIndex { expected, found } => {
+                write!(f, "expected header {} but got {}", expected, found)
+            }
+            HeaderError::InvalidVersion {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "header {} has version {}, expected {}",
+                index, found, expected
+            ),
+            HeaderError::BrokenLink { index } => {
+                write!(f, "header {} does not link to the previous header", index)
+            }
+            HeaderError::HashMismatch { index } => {
+                write!(f, "header {}: hash does not match its fields", index)
+            }
+            HeaderError::InsufficientWork { index } => {
+                write!(f, "header {}: hash does not meet its target", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// Reasons `HeaderChain::verify_transaction` refuses a transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpvError {
+    /// No header at this index has been received
+    UnknownBlock(u32),
+    /// The proof doesn't lead from the transaction to the header's merkle root
+    InvalidProof { tx_id: String, block_index: u32 },
+}
+
+impl fmt::Display for SpvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpvError::UnknownBlock(index) => write!(f, "no header for block {}", index),
+            SpvError::InvalidProof { tx_id, block_index } => write!(
+                f,
+                "proof does not show transaction {} in block {}",
+                tx_id, block_index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpvError {}
+
+/// Headers of a proof-of-work chain, genesis first, checked as they arrive.
+///
+/// Each header must link to the one before it and carry a hash that is both its
+/// own and within its target. The target itself is taken from the header rather
+/// than recomputed, since retargeting needs timestamps the client trusts no more
+/// than the rest of the header.
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    headers: Vec<BlockHeader>,
+}
+
+impl HeaderChain {
+    /// Starts a chain from the genesis header, which must be at index 0 and carry
+    /// valid proof of work
+    pub fn new(genesis: BlockHeader) -> Result<HeaderChain, HeaderError> {
+        if genesis.index != 0 {
+            return Err(HeaderError::InvalidIndex {
+                expected: 0,
+                found: genesis.index,
+            });
+        }
+        check_proof_of_work(&genesis)?;
+        Ok(HeaderChain {
+            headers: vec![genesis],
+        })
+    }
+
+    /// Appends `header` to the tip if it links to it and its proof of work holds
+    pub fn add_header(&mut self, header: BlockHeader) -> Result<(), HeaderError> {
+        let tip = self.tip();
+        if header.index != tip.index + 1 {
+            return Err(HeaderError::InvalidIndex {
+                expected: tip.index + 1,
+                found: header.index,
+            });
+        }
+        if header.version != tip.version {
+            return Err(HeaderError::InvalidVersion {
+                index: header.index,
+                expected: tip.version,
+                found: header.version,
+            });
+        }
+        if header.previous_hash != tip.hash {
+            return Err(HeaderError::BrokenLink {
+                index: header.index,
+            });
+        }
+        check_proof_of_work(&header)?;
+        self.headers.push(header);
+        Ok(())
+    }
+
+    /// Checks that `tx` is in block `block_index` by following `proof` from its
+    /// hash up to the merkle root of the stored header
+    pub fn verify_transaction(
+        &self,
+        tx: &Transaction,
+        proof: &MerkleProof,
+        block_index: u32,
+    ) -> Result<(), SpvError> {
+        let header = self
+            .get_header(block_index)
+            .ok_or(SpvError::UnknownBlock(block_index))?;
+        let tx_id = tx.calculate_hash();
+        if !verify_proof(&tx_id, proof, &header.merkle_root) {
+            return Err(SpvError::InvalidProof { tx_id, block_index });
+        }
+        Ok(())
+    }
+
+    /// Returns the header at `index`
+    pub fn get_header(&self, index: u32) -> Option<&BlockHeader> {
+        self.headers.get(index as usize)
+    }
+
+    /// Returns the latest header
+    pub fn tip(&self) -> &BlockHeader {
+        self.headers
+            .last()
+            .expect("header chain always contains a genesis header")
+    }
+
+    /// Number of headers, genesis included
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Always false: the chain starts with its genesis header
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+}
+
+/// Checks that the header's hash is its own and meets its target
+fn check_proof_of_work(header: &BlockHeader) -> Result<(), HeaderError> {
+    if header.calculate_hash() != header.hash {
+        return Err(HeaderError::HashMismatch {
+            index: header.index,
+        });
+    }
+    if !header.hash_meets_target() {
+        return Err(HeaderError::InsufficientWork {
+            index: header.index,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blockchain::Blockchain;
+    use crate::reward::BLOCK_REWARD;
+
+    /// A 10-block chain whose blocks each hold a coinbase to Alice and three of her
+    /// payments
+    fn full_node() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for index in 1..10u32 {
+            let mut transactions = vec![Transaction::coinbase("Alice", BLOCK_REWARD, index)];
+            transactions
+                .extend((0..3).map(|i| Transaction::new("Alice", "Bob", (index + 10 * i) as f64)));
+            blockchain
+                .add_block(Block::new(index, String::new(), transactions))
+                .unwrap();
+        }
+        blockchain
+    }
+
+    fn light_client(blockchain: &Blockchain) -> HeaderChain {
+        let mut headers = HeaderChain::new(blockchain.get_header(0).unwrap()).unwrap();
+        for index in 1..blockchain.get_total_blocks() as u32 {
+            headers
+                .add_header(blockchain.get_header(index).unwrap())
+                .unwrap();
+        }
+        headers
+    }
+
+    #[test]
+    fn proofs_from_a_full_node_verify_against_headers() {
+        let blockchain = full_node();
+        let headers = light_client(&blockchain);
+        assert_eq!(headers.len(), 10);
+        assert_eq!(headers.tip(), &blockchain.tip().header());
+
+        let tx = blockchain.get_block_by_index(5).unwrap().transactions[1].clone();
+        let (block_index, proof) = blockchain.get_merkle_proof(&tx.calculate_hash()).unwrap();
+        assert_eq!(block_index, 5);
+        assert_eq!(headers.verify_transaction(&tx, &proof, block_index), Ok(()));
+    }
+
+    #[test]
+    fn tampered_proofs_and_transactions_fail() {
+        let blockchain = full_node();
+        let headers = light_client(&blockchain);
+        let tx = blockchain.get_block_by_index(5).unwrap().transactions[1].clone();
+        let (block_index, proof) = blockchain.get_merkle_proof(&tx.calculate_hash()).unwrap();
+
+        let mut wrong_sibling = proof.clone();
+        wrong_sibling[0].0 = "0".repeat(64);
+        let mut wrong_side = proof.clone();
+        wrong_side[0].1 = !wrong_side[0].1;
+        for tampered in [wrong_sibling, wrong_side, Vec::new()] {
+            assert!(matches!(
+                headers.verify_transaction(&tx, &tampered, block_index),
+                Err(SpvError::InvalidProof { block_index: 5, .. })
+            ));
+        }
+
+        let mut forged = tx.clone();
+        forged.amount *= 100.0;
+        assert_eq!(
+            headers.verify_transaction(&forged, &proof, block_index),
+            Err(SpvError::InvalidProof {
+                tx_id: forged.calculate_hash(),
+                block_index: 5
+            })
+        );
+
+        assert!(headers.verify_transaction(&tx, &proof, 4).is_err());
+        assert_eq!(
+            headers.verify_transaction(&tx, &proof, 10),
+            Err(SpvError::UnknownBlock(10))
+        );
+    }
+
+    #[test]
+    fn headers_that_break_the_chain_are_refused() {
+        let blockchain = full_node();
+        let mut headers = HeaderChain::new(blockchain.get_header(0).unwrap()).unwrap();
+
+        assert_eq!(
+            headers.add_header(blockchain.get_header(2).unwrap()),
+            Err(HeaderError::InvalidIndex {
+                expected: 1,
+                found: 2
+            })
+        );
+
+        let mut relinked = blockchain.get_header(1).unwrap();
+        relinked.previous_hash = "0".repeat(64);
+        assert_eq!(
+            headers.add_header(relinked),
+            Err(HeaderError::BrokenLink { index: 1 })
+        );
+
+        // Swapping in another merkle root changes the hash the header claims
+        let mut rewritten = blockchain.get_header(1).unwrap();
+        rewritten.merkle_root = blockchain.get_header(2).unwrap().merkle_root;
+        assert_eq!(
+            headers.add_header(rewritten.clone()),
+            Err(HeaderError::HashMismatch { index: 1 })
+        );
+
+        // Rehashing it without mining leaves the hash above the target
+        loop {
+            rewritten.nonce += 1;
+            rewritten.hash = rewritten.calculate_hash();
+            if !rewritten.hash_meets_target() {
+                break;
+            }
+        }
+        assert_eq!(
+            headers.add_header(rewritten),
+            Err(HeaderError::InsufficientWork { index: 1 })
+        );
+
+        headers
+            .add_header(blockchain.get_header(1).unwrap())
+            .unwrap();
+        assert_eq!(headers.len(), 2);
+    }
+}
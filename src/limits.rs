@@ -0,0 +1,128 @@
+use crate::block::Block;
+use crate::encoding;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Default number of transactions a block may carry besides its coinbase
+pub const MAX_TRANSACTIONS_PER_BLOCK: usize = 1_000;
+
+/// Default bytes a block's transactions may take up, coinbase excluded
+pub const MAX_BLOCK_BYTES: usize = 1_000_000;
+
+/// How much a block may hold. The coinbase is left out of both limits, so it always
+/// fits; sizes are the transactions' canonical binary encoding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockLimits {
+    pub max_transactions_per_block: usize, // Transactions besides the coinbase
+    pub max_block_bytes: usize,            // Encoded bytes of those transactions
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        BlockLimits {
+            max_transactions_per_block: MAX_TRANSACTIONS_PER_BLOCK,
+            max_block_bytes: MAX_BLOCK_BYTES,
+        }
+    }
+}
+
+/// Which limit a block goes over
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitError {
+    TooManyTransactions { count: usize, max: usize },
+    TooManyBytes { bytes: usize, max: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitError::TooManyTransactions { count, max } => write!(
+                f,
+                "{} transactions besides the coinbase, at most {} allowed",
+                count, max
+            ),
+            LimitError::TooManyBytes { bytes, max } => {
+                write!(
+                    f,
+                    "{} bytes of transactions, at most {} allowed",
+                    bytes, max
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl BlockLimits {
+    /// Checks the block's non-coinbase transactions against both limits
+    pub fn check(&self, block: &Block) -> Result<(), LimitError> {
+        let transactions: Vec<&Transaction> = block
+            .transactions
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .collect();
+        if transactions.len() > self.max_transactions_per_block {
+            return Err(LimitError::TooManyTransactions {
+                count: transactions.len(),
+                max: self.max_transactions_per_block,
+            });
+        }
+        let bytes = transactions.iter().map(|tx| encoded_size(tx)).sum();
+        if bytes > self.max_block_bytes {
+            return Err(LimitError::TooManyBytes {
+                bytes,
+                max: self.max_block_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Splits `transactions`, highest priority first, into the longest prefix that
+    /// fits in one block and the rest. The first transaction that doesn't fit ends
+    /// the block, since later ones may spend what it pays.
+    pub fn fit(&self, mut transactions: Vec<Transaction>) -> (Vec<Transaction>, Vec<Transaction>) {
+        let mut bytes = 0;
+        let fitting = transactions
+            .iter()
+            .take(self.max_transactions_per_block)
+            .take_while(|tx| {
+                bytes += encoded_size(tx);
+                bytes <= self.max_block_bytes
+            })
+            .count();
+        let deferred = transactions.split_off(fitting);
+        (transactions, deferred)
+    }
+}
+
+fn encoded_size(transaction: &Transaction) -> usize {
+    encoding::to_bytes(transaction).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_takes_the_prefix_within_both_limits() {
+        let small = Transaction::new("Alice", "Bob", 1.0);
+        let large = Transaction::new(&"A".repeat(200), "Bob", 1.0);
+        let limits = BlockLimits {
+            max_transactions_per_block: 2,
+            max_block_bytes: 2 * encoded_size(&small),
+        };
+
+        let smalls: Vec<Transaction> = (0..3)
+            .map(|nonce| small.clone().with_nonce(nonce))
+            .collect();
+        let (included, deferred) = limits.fit(smalls.clone());
+        assert_eq!(included, smalls[..2]);
+        assert_eq!(deferred, smalls[2..]);
+
+        let (included, deferred) = limits.fit(vec![small.clone(), large.clone(), small.clone()]);
+        assert_eq!(included, vec![small.clone()]);
+        assert_eq!(deferred, vec![large, small]);
+    }
+}
@@ -1,195 +1,660 @@
-// Import necessary dependencies
-use sha2::{Digest, Sha256};  // For cryptographic hashing
-use std::fmt;                // For custom display formatting
-use std::thread;             // For thread sleeping during mining
-use std::time::Duration;     // For time-based operations
-use std::time::{SystemTime, UNIX_EPOCH};  // For timestamp generation
-
-// Define mining difficulty - number of leading zeros required in hash
-const DIFFICULTY: usize = 2;
-
-/// Represents a single block in the blockchain
-struct Block {
-    index: u32, // Index of the block in the chain
-    previous_hash: String, // Hash of the previous block
-    timestamp: u64,     // When the block was created (UNIX timestamp)
-    data: String,       // Transaction data stored in the block
-    nonce: u64,         // Number used once for mining
-    hash: String, 
-    mined: bool,      // This block's hash
-}
-
-impl Block {
-    /// Creates a new block with the given parameters
-    fn new(index: u32, previous_hash: String, data: String) -> Block {
-        // Get current timestamp in seconds since UNIX epoch
-        let timestamp: u64 = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time Went Backwards")
-            .as_secs();
-            
-        Block {
-            index,
-            previous_hash,
-            timestamp,
-            data,
-            nonce: 0,  // Initialize nonce to 0
-            hash: String::new(),  
-            mined: false// Hash will be calculated during mining
-        }
-    }
-
-    /// Calculates the SHA-256 hash of the block
-    fn calculate_hash(&mut self) -> String {
-        // Combine block data into a single string
-        let data = format!(
-            "{}{}{}{}{}",
-            self.index, self.previous_hash, self.timestamp, self.data, self.nonce
-        );
-        
-        // Create SHA-256 hasher
-        let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
-        
-        // Finalize hash and convert to hexadecimal string
-        let result = hasher.finalize();
-        format!("{:x}", result)
-    }
-
-    /// Mines the block by finding a valid hash that meets the difficulty requirement
-    fn mine_block(&mut self) {
-        let mut iterations: u64 = 0;
-        loop {
-            // Calculate hash with current nonce
-            self.hash = self.calculate_hash();
-            iterations += 1;
-            
-            // Check if hash meets difficulty requirement (starts with N zeros)
-            if !self.hash.is_empty() && &self.hash[..DIFFICULTY] == "00".repeat(DIFFICULTY) {
-                println!("Mining Block {}", self.index);
-                self.mined = true;
-                break;
+use blockchain_simulation_rust::address::{Address, AddressError};
+use blockchain_simulation_rust::block::MiningObserver;
+use blockchain_simulation_rust::blockchain::ChainFormat;
+use blockchain_simulation_rust::competition::{run_mining_competition, MinerConfig};
+use blockchain_simulation_rust::error::Error;
+use blockchain_simulation_rust::explorer::{self, Verbosity};
+use blockchain_simulation_rust::network::{self, Node};
+use blockchain_simulation_rust::scenario;
+use blockchain_simulation_rust::simulation::{self, TRADER_NAMES};
+use blockchain_simulation_rust::time::parse_timestamp;
+use blockchain_simulation_rust::wallet::Wallet;
+use blockchain_simulation_rust::{Blockchain, Mempool, Transaction};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::env;
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Command-line interface to the blockchain simulation.
+/// Without a subcommand the interactive demo simulation runs.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Chain file read and written by the commands
+    #[arg(long, global = true, default_value = "blockchain.json")]
+    chain: PathBuf,
+
+    /// File holding transactions waiting to be mined
+    #[arg(long, global = true, default_value = "mempool.json")]
+    mempool: PathBuf,
+
+    /// Accept plain account names such as alice, as the simulation uses, where a
+    /// wallet address is otherwise required
+    #[arg(long, global = true)]
+    legacy_names: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a new chain file containing only the genesis block
+    Init {
+        /// Overwrite an existing chain file
+        #[arg(long)]
+        force: bool,
+        /// Save the chain in the compact binary format; later commands keep it
+        #[arg(long)]
+        binary: bool,
+    },
+    /// Create an encrypted wallet file and print its address
+    Wallet {
+        /// Wallet file to create
+        #[arg(long)]
+        out: PathBuf,
+        /// Environment variable holding the passphrase
+        #[arg(long, default_value = "WALLET_PASSPHRASE")]
+        passphrase_env: String,
+    },
+    /// Queue a transaction in the mempool
+    Send {
+        /// Sender name; defaults to the wallet's address with --wallet
+        #[arg(long, required_unless_present = "wallet")]
+        from: Option<String>,
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+        /// Last block time the transaction may be mined at, e.g. 2030-01-01T00:00:00Z
+        #[arg(long, value_parser = parse_timestamp)]
+        expires: Option<u64>,
+        /// Sign the transaction with this wallet file
+        #[arg(long)]
+        wallet: Option<PathBuf>,
+        /// Environment variable holding the wallet passphrase
+        #[arg(long, default_value = "WALLET_PASSPHRASE")]
+        passphrase_env: String,
+    },
+    /// Mine the pending transactions into a new block
+    Mine {
+        /// Address credited with the block reward and fees
+        #[arg(long)]
+        miner: String,
+    },
+    /// Print the confirmed balance of an address
+    Balance { address: String },
+    /// Check the chain file's integrity
+    Validate,
+    /// Print a summary of the chain, or one block, transaction or address in detail
+    Show {
+        #[command(subcommand)]
+        target: Option<ShowTarget>,
+    },
+    /// Write the chain as a snapshot directory with an integrity manifest
+    Export {
+        /// Directory to write the snapshot into
+        out: PathBuf,
+    },
+    /// Verify a snapshot directory and save its chain as the chain file
+    Import {
+        /// Snapshot directory written by `export`
+        dir: PathBuf,
+        /// Refuse the snapshot unless its genesis block has this hash
+        #[arg(long)]
+        genesis: Option<String>,
+        /// Overwrite an existing chain file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run the demo simulation passing coins between traders
+    Simulate {
+        /// Miner name; asked for interactively when omitted
+        #[arg(long)]
+        miner: Option<String>,
+        /// Print progress as text, or a JSON report on stdout with progress on stderr
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Move coins as account transfers or as UTXO spends with change
+        #[arg(long, value_enum, default_value_t = Ledger::Account)]
+        ledger: Ledger,
+        /// Run the accounts and transactions described in this TOML file instead
+        #[arg(long, conflicts_with_all = ["miner", "ledger", "seed"])]
+        scenario: Option<PathBuf>,
+        /// Replay a reproducible run: simulated time and block intervals drawn from this seed
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Race several miners for a number of blocks and print a leaderboard
+    Compete {
+        /// Miner as NAME or NAME:HASHES_PER_TICK; repeat for each contestant
+        #[arg(long = "miner", required = true, value_parser = parse_miner)]
+        miners: Vec<MinerConfig>,
+        /// Blocks to race for
+        #[arg(long, default_value_t = 10)]
+        rounds: usize,
+        /// Leading zeros every block is mined at
+        #[arg(long, default_value_t = 2)]
+        difficulty: usize,
+    },
+    /// Run a network node that syncs blocks with peers over TCP
+    Node {
+        /// Address to accept peer connections on
+        #[arg(long, default_value = "0.0.0.0:7000")]
+        listen: String,
+        /// Peer to connect to and sync from; may be repeated
+        #[arg(long)]
+        peer: Vec<String>,
+        /// Mine a block every interval, rewarding this address
+        #[arg(long)]
+        miner: Option<String>,
+        /// Seconds between mined blocks and chain file saves
+        #[arg(long, default_value_t = 10)]
+        interval: u64,
+        /// Also serve the REST API on this address (needs the `http` feature)
+        #[arg(long)]
+        http: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ShowTarget {
+    /// A block by index or hash
+    Block {
+        id: String,
+        /// Print full hashes, fees and nonces
+        #[arg(long)]
+        verbose: bool,
+    },
+    /// A confirmed transaction by id
+    Tx { id: String },
+    /// Every transaction touching an address, with its running balance
+    Address { address: String },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Ledger {
+    Account,
+    Utxo,
+}
+
+impl From<Ledger> for simulation::Ledger {
+    fn from(ledger: Ledger) -> Self {
+        match ledger {
+            Ledger::Account => simulation::Ledger::Account,
+            Ledger::Utxo => simulation::Ledger::Utxo,
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Error> {
+    match cli.command {
+        None => simulate(None, OutputFormat::Text, Ledger::Account, None),
+        Some(Command::Simulate {
+            scenario: Some(path),
+            format,
+            ..
+        }) => run_scenario(&path, format),
+        Some(Command::Simulate {
+            miner,
+            format,
+            ledger,
+            scenario: None,
+            seed,
+        }) => simulate(miner, format, ledger, seed),
+        Some(Command::Init { force, binary }) => {
+            if cli.chain.exists() && !force {
+                return Err(format!(
+                    "{} already exists (use --force to overwrite)",
+                    cli.chain.display()
+                )
+                .into());
+            }
+            let blockchain = Blockchain::new();
+            let format = if binary {
+                ChainFormat::Binary
+            } else {
+                ChainFormat::Json
+            };
+            blockchain.save_to_file_as(&cli.chain, format)?;
+            Mempool::new().save_to_file(&cli.mempool)?;
+            println!("Created {} with genesis block", cli.chain.display());
+            println!("Genesis Hash: {}", blockchain.tip().hash);
+            Ok(())
+        }
+        Some(Command::Wallet {
+            out,
+            passphrase_env,
+        }) => {
+            if out.exists() {
+                return Err(format!("{} already exists", out.display()).into());
+            }
+            let wallet = Wallet::generate();
+            wallet.save_encrypted(&out, &passphrase(&passphrase_env)?)?;
+            println!("{}", wallet.address());
+            Ok(())
+        }
+        Some(Command::Send {
+            from,
+            to,
+            amount,
+            fee,
+            expires,
+            wallet,
+            passphrase_env,
+        }) => {
+            let blockchain = Blockchain::load_from_file(&cli.chain)?;
+            let mut mempool = load_mempool(&cli.mempool)?;
+
+            let wallet = match wallet {
+                Some(path) => Some(Wallet::load_encrypted(
+                    &path,
+                    &passphrase(&passphrase_env)?,
+                )?),
+                None => None,
+            };
+            let from = match (&wallet, from) {
+                (Some(wallet), None) => wallet.address(),
+                (_, Some(from)) => account(&from, cli.legacy_names)?,
+                (None, None) => unreachable!("clap requires --from without --wallet"),
+            };
+            let to = account(&to, cli.legacy_names)?;
+
+            let mut transaction = Transaction::try_new(&from, &to, amount)?
+                .with_fee(fee)
+                .with_nonce(next_nonce(&blockchain, &mempool, &from));
+            if let Some(expires_at) = expires {
+                transaction = transaction.with_expiry(expires_at);
+            }
+            if let Some(wallet) = &wallet {
+                wallet.sign_transaction(&mut transaction)?;
+            }
+            let tx_id = transaction.calculate_hash();
+            mempool.add_funded_transaction(transaction, &blockchain)?;
+            mempool.save_to_file(&cli.mempool)?;
+            println!("Queued transaction {}", tx_id);
+            Ok(())
+        }
+        Some(Command::Mine { miner }) => {
+            let miner = account(&miner, cli.legacy_names)?;
+            let mut blockchain = Blockchain::load_from_file(&cli.chain)?;
+            let mut mempool = load_mempool(&cli.mempool)?;
+
+            let mut block = blockchain.prepare_block(&mut mempool, &miner);
+            block.mine_block_observed(&mut HashRateReporter::new())?;
+            blockchain.add_block(block)?;
+            let block = blockchain.latest_block();
+            println!("Mined block {}", block.index);
+            println!("Hash: {}", block.hash);
+            println!("Transactions: {}", block.transactions.len());
+
+            save_chain(&blockchain, &cli.chain)?;
+            mempool.save_to_file(&cli.mempool)?;
+            Ok(())
+        }
+        Some(Command::Balance { address }) => {
+            let blockchain = Blockchain::load_from_file(&cli.chain)?;
+            println!(
+                "{}",
+                blockchain.get_balance(&account(&address, cli.legacy_names)?)
+            );
+            Ok(())
+        }
+        Some(Command::Validate) => {
+            let blockchain = Blockchain::load_from_file(&cli.chain)?;
+            println!("Chain is valid ({} blocks)", blockchain.get_total_blocks());
+            Ok(())
+        }
+        Some(Command::Export { out }) => {
+            let blockchain = Blockchain::load_from_file(&cli.chain)?;
+            let manifest = blockchain.export_snapshot(&out)?;
+            println!(
+                "Exported {} blocks to {}",
+                manifest.height + 1,
+                out.display()
+            );
+            println!("Digest: {}", manifest.payload_sha256);
+            Ok(())
+        }
+        Some(Command::Import {
+            dir,
+            genesis,
+            force,
+        }) => {
+            if cli.chain.exists() && !force {
+                return Err(format!(
+                    "{} already exists (use --force to overwrite)",
+                    cli.chain.display()
+                )
+                .into());
             }
-            
-            // Safety mechanism to prevent infinite loops
-            if iterations > 100 {
-                println!("Mining in progress... ");
-                thread::sleep(Duration::from_millis(3000));
-                println!("Calculated Hash {}", self.hash);
-                break;
+            let blockchain = match genesis {
+                Some(genesis) => Blockchain::import_snapshot_with_genesis(&dir, &genesis)?,
+                None => Blockchain::import_snapshot(&dir)?,
+            };
+            save_chain(&blockchain, &cli.chain)?;
+            println!(
+                "Imported {} blocks into {}",
+                blockchain.get_total_blocks(),
+                cli.chain.display()
+            );
+            println!("Tip Hash: {}", blockchain.tip().hash);
+            Ok(())
+        }
+        Some(Command::Compete {
+            miners,
+            rounds,
+            difficulty,
+        }) => {
+            let result = run_mining_competition(&miners, rounds, difficulty)?;
+            println!(
+                "{:<4} {:<12} {:>5} {:>12} {:>10}",
+                "Rank", "Miner", "Wins", "Hashes", "Balance"
+            );
+            for (rank, standing) in result.leaderboard().into_iter().enumerate() {
+                println!(
+                    "{:<4} {:<12} {:>5} {:>12} {:>10}",
+                    rank + 1,
+                    standing.name,
+                    standing.wins,
+                    standing.hashes_tried,
+                    standing.balance
+                );
             }
-            
-            // Try next nonce value
-            self.nonce += 1;
+            Ok(())
+        }
+        Some(Command::Node {
+            listen,
+            peer,
+            miner,
+            interval,
+            http,
+        }) => run_node(
+            &cli.chain,
+            &listen,
+            &peer,
+            miner.as_deref(),
+            interval,
+            http.as_deref(),
+        ),
+        Some(Command::Show { target }) => {
+            let blockchain = Blockchain::load_from_file(&cli.chain)?;
+            let view = match target {
+                None => explorer::format_chain_summary(&blockchain),
+                Some(ShowTarget::Block { id, verbose }) => {
+                    // Numbers are indexes; anything else is a hash
+                    let block = match id.parse::<u32>() {
+                        Ok(index) => blockchain.get_block_by_index(index),
+                        Err(_) => blockchain.get_block_by_hash(&id),
+                    }
+                    .ok_or_else(|| format!("no block {}", id))?;
+                    let verbosity = if verbose {
+                        Verbosity::Detailed
+                    } else {
+                        Verbosity::Brief
+                    };
+                    explorer::format_block(block, verbosity)
+                }
+                Some(ShowTarget::Tx { id }) => {
+                    let (block, transaction) = blockchain
+                        .find_transaction(&id)
+                        .ok_or_else(|| format!("no confirmed transaction {}", id))?;
+                    explorer::format_transaction(block, transaction)
+                }
+                Some(ShowTarget::Address { address }) => explorer::format_address_history(
+                    &blockchain,
+                    &account(&address, cli.legacy_names)?,
+                ),
+            };
+            print!("{}", view);
+            Ok(())
         }
     }
 }
 
-// Implement custom display formatting for Block
-impl fmt::Display for Block {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Convert UNIX timestamp to readable date-time
-        let date_time = chrono::NaiveDateTime::from_timestamp(self.timestamp as i64, 0);
-        write!(f, "Block {}: {} at {}", self.index, self.data, date_time)
+/// Reads an account argument: a wallet address, or with `--legacy-names` also a plain name
+fn account(s: &str, legacy_names: bool) -> Result<Address, Error> {
+    if legacy_names {
+        return Ok(Address::parse_or_name(s)?);
+    }
+    match Address::parse(s) {
+        Ok(address) => Ok(address),
+        Err(e @ AddressError::MissingPrefix(_)) => {
+            Err(format!("{}; pass --legacy-names to use plain names", e).into())
+        }
+        Err(e) => Err(e.into()),
     }
 }
 
-/// Represents the blockchain containing a vector of blocks
-struct Blockchain {
-    chain: Vec<Block>,
+/// Parses `NAME` or `NAME:HASHES_PER_TICK` for `compete --miner`
+fn parse_miner(s: &str) -> Result<MinerConfig, String> {
+    match s.split_once(':') {
+        None => Ok(MinerConfig::new(s)),
+        Some((name, budget)) => {
+            let budget = budget
+                .parse()
+                .map_err(|e| format!("invalid hashes per tick {:?}: {}", budget, e))?;
+            Ok(MinerConfig::new(name).with_hash_budget(budget))
+        }
+    }
 }
 
-impl Blockchain {
-    /// Creates a new blockchain with a genesis block
-    fn new() -> Blockchain {
-        let genesis_block = Block::new(0, String::new(), String::from("Genesis Block"));
-        Blockchain {
-            chain: vec![genesis_block],  // Initialize with genesis block
+/// Runs the demo simulation, asking for the miner's name if not given
+fn simulate(
+    miner: Option<String>,
+    format: OutputFormat,
+    ledger: Ledger,
+    seed: Option<u64>,
+) -> Result<(), Error> {
+    // Progress goes to stderr when stdout is reserved for the JSON report
+    let mut out: Box<dyn Write> = match format {
+        OutputFormat::Text => Box::new(io::stdout()),
+        OutputFormat::Json => Box::new(io::stderr()),
+    };
+
+    // Initialize the simulation
+    writeln!(out, "Starting the Blockchain Simulation")?;
+
+    let miner_name = match miner {
+        Some(name) => name,
+        None => {
+            writeln!(out, "Enter miner's name:")?;
+
+            // Get miner's name from user input
+            let mut miner_name = String::new();
+            std::io::stdin().read_line(&mut miner_name)?;
+            miner_name.trim().to_string()
         }
+    };
+
+    let options = simulation::SimulationOptions {
+        ledger: ledger.into(),
+        seed,
+    };
+    let (_, report) =
+        simulation::simulate_with_options(&miner_name, &TRADER_NAMES, &options, &mut out)?;
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
     }
-    
-    /// Adds a new block to the blockchain
-    fn add_block(&mut self, mut new_block: Block) {
-        // Get hash of the last block in the chain
-        let previous_hash = self.chain.last().unwrap().hash.clone();
-        new_block.previous_hash = previous_hash;
-        
-        // Mine the new block
-        new_block.mine_block();
-        
-        // Add the block to the chain
-        self.chain.push(new_block);
+    Ok(())
+}
+
+/// Runs a scenario file and prints what happened to each transaction
+fn run_scenario(path: &Path, format: OutputFormat) -> Result<(), Error> {
+    let scenario = scenario::Scenario::load(path)?;
+    let result = scenario.run()?;
+    if format == OutputFormat::Json {
+        let report = serde_json::json!({
+            "outcomes": result.outcomes,
+            "balances": result.balances,
+            "total_blocks": result.blockchain.get_total_blocks(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
     }
-    
-    /// Returns the total number of blocks in the blockchain
-    fn get_total_blocks(&self) -> usize {
-        self.chain.len()
+
+    for (transaction, outcome) in scenario.transactions.iter().zip(&result.outcomes) {
+        println!(
+            "{} -> {}: {} {}",
+            transaction.from.get_ref(),
+            transaction.to.get_ref(),
+            transaction.amount,
+            outcome
+        );
     }
+    println!();
+    for (name, balance) in &result.balances {
+        println!("{}: {}", name, balance);
+    }
+    println!();
+    println!("{}", result.blockchain.stats());
+    Ok(())
 }
 
-fn main() {
-    // Initialize the simulation
-    println!("Starting the Blockchain Simulation");
-    println!("Enter miner's name:");
-
-    // Get miner's name from user input
-    let mut miner_name = String::new();
-    std::io::stdin()
-        .read_line(&mut miner_name)
-        .expect("Failed to read input");
-    miner_name = miner_name.trim().to_string();
-
-    // Define list of traders for simulation
-    let trader_names = vec!["Bob", "Alice", "Charlie", "David", "Eve"];
-    
-    // Initialize blockchain with genesis block
-    let mut blockchain = Blockchain::new();
-
-    println!("Let's start mining and simulating transactions");
-
-    // Start with miner as the initial sender
-    let mut sender = miner_name.clone();
-
-    // Simulate transactions between traders
-    for i in 0..trader_names.len() {
-        println!("Mining Block {}", i + 1);
-        
-        // Determine recipient (next trader or back to miner)
-        let recipient = if i < trader_names.len() - 1 {
-            trader_names[i + 1].to_string()
+/// Serves the chain file's chain to peers until killed, saving it every `interval` seconds.
+/// Without a chain file the node starts from the shared network genesis block.
+fn run_node(
+    chain_path: &Path,
+    listen: &str,
+    peers: &[String],
+    miner: Option<&str>,
+    interval: u64,
+    http: Option<&str>,
+) -> Result<(), Error> {
+    let blockchain = if chain_path.exists() {
+        Blockchain::load_from_file(chain_path)?
+    } else {
+        Blockchain::with_genesis(network::network_genesis())
+    };
+    let node = Node::new(blockchain, Mempool::new());
+    let addr = node.listen(listen)?;
+    println!("Listening on {}", addr);
+    if let Some(http) = http {
+        serve_http(&node, http)?;
+    }
+
+    for peer in peers {
+        match node.connect(peer) {
+            Ok(added) => println!("Synced {} blocks from {}", added, peer),
+            Err(e) => eprintln!("Could not sync from {}: {}", peer, e),
+        }
+    }
+
+    loop {
+        thread::sleep(Duration::from_secs(interval));
+        if let Some(miner) = miner {
+            let block = node.mine(miner)?;
+            println!("Mined block {} ({})", block.index, block.hash);
         } else {
-            miner_name.clone()
-        };
-
-        // Create transaction string
-        let transaction = format!("{} sent to {}", sender, recipient);
-
-        // Create and add new block with transaction
-        let new_block = Block::new((i + 1) as u32, String::new(), transaction.clone());
-        blockchain.add_block(new_block);
-
-        println!("Transaction: {}", transaction);
-        
-        // Update sender for next transaction
-        sender = recipient;
-        println!();  // Add blank line for better readability
-    }
-
-    // Display simulation results
-    let total_blocks = blockchain.get_total_blocks();
-    println!("Total Blocks: {}", total_blocks);
-
-    // Calculate and display total blockchain traded
-    let reward_per_block = 137;  // Fixed reward per block
-    let total_traded = total_blocks * reward_per_block;
-    println!("Total Reward Traded: {}", total_traded);
-
-    // Display end time of simulation
-    let end_timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_secs();
-    let end_date_time = chrono::NaiveDateTime::from_timestamp(end_timestamp as i64, 0);
-    println!("End Time: {}", end_date_time);
-    println!("Mining Completed Successfully");
+            node.sync_all();
+        }
+        let blockchain = node.blockchain();
+        save_chain(&blockchain, chain_path)?;
+        println!(
+            "Tip: block {} ({})",
+            blockchain.latest_block().index,
+            blockchain.latest_block().hash
+        );
+    }
+}
+
+#[cfg(feature = "http")]
+fn serve_http(node: &Node, addr: &str) -> Result<(), Error> {
+    let addr = blockchain_simulation_rust::http::serve(node.clone(), addr)?;
+    println!("Serving REST API on http://{}", addr);
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+fn serve_http(_node: &Node, _addr: &str) -> Result<(), Error> {
+    Err("--http needs a build with the http feature (cargo run --features http)".into())
+}
+
+/// Prints the hash rate to stderr about once a second while mining
+struct HashRateReporter {
+    last_report: Instant,
+    last_hashes: u64,
+}
+
+impl HashRateReporter {
+    fn new() -> HashRateReporter {
+        HashRateReporter {
+            last_report: Instant::now(),
+            last_hashes: 0,
+        }
+    }
+}
+
+impl MiningObserver for HashRateReporter {
+    fn on_progress(
+        &mut self,
+        hashes_tried: u64,
+        elapsed: Duration,
+        best_hash: &str,
+    ) -> ControlFlow<()> {
+        let since_report = self.last_report.elapsed();
+        if since_report >= Duration::from_secs(1) {
+            let rate = (hashes_tried - self.last_hashes) as f64 / since_report.as_secs_f64();
+            eprintln!(
+                "Mining: {} hashes in {:.0}s ({:.0} H/s), best {}",
+                hashes_tried,
+                elapsed.as_secs_f64(),
+                rate,
+                &best_hash[..16.min(best_hash.len())]
+            );
+            self.last_report = Instant::now();
+            self.last_hashes = hashes_tried;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Reads a wallet passphrase from the environment variable `var`
+fn passphrase(var: &str) -> Result<String, Error> {
+    env::var(var).map_err(|_| format!("set the wallet passphrase in ${}", var).into())
+}
+
+/// Saves the chain in the format its file already uses, JSON for a new file
+fn save_chain(blockchain: &Blockchain, path: &Path) -> io::Result<()> {
+    blockchain.save_to_file_as(path, ChainFormat::of_file(path))
+}
+
+/// Loads the mempool file, treating a missing file as an empty mempool
+fn load_mempool(path: &Path) -> Result<Mempool, Error> {
+    if path.exists() {
+        Ok(Mempool::load_from_file(path)?)
+    } else {
+        Ok(Mempool::new())
+    }
+}
+
+/// Next sequence number for `sender`: one past its confirmed and pending transactions
+fn next_nonce(blockchain: &Blockchain, mempool: &Mempool, sender: &str) -> u64 {
+    let sent = blockchain
+        .iter()
+        .flat_map(|block| &block.transactions)
+        .chain(mempool.pending())
+        .filter(|tx| tx.sender == sender)
+        .count();
+    sent as u64
 }
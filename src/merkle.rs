@@ -0,0 +1,154 @@
+use sha2::{Digest, Sha256};
+
+/// Merkle root of a block without transactions: 64 zero characters
+pub const EMPTY_MERKLE_ROOT: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Path from a leaf to the root: each step is a sibling hash and whether that
+/// sibling sits on the left
+pub type MerkleProof = Vec<(String, bool)>;
+
+/// Hashes two child nodes into their parent
+fn hash_pair(left: &str, right: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Builds the next level up of the tree, duplicating the last node of odd-sized levels
+fn next_level(level: &[String]) -> Vec<String> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+/// Computes the Merkle root of a list of transaction hashes.
+///
+/// A single hash is its own root; an empty list has the root `EMPTY_MERKLE_ROOT`.
+pub fn merkle_root(hashes: &[String]) -> String {
+    if hashes.is_empty() {
+        return EMPTY_MERKLE_ROOT.to_string();
+    }
+
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.remove(0)
+}
+
+/// Builds the inclusion proof for the hash at `index`.
+///
+/// Returns an empty proof if `index` is out of range.
+pub fn merkle_proof(hashes: &[String], index: usize) -> MerkleProof {
+    let mut proof = Vec::new();
+    if index >= hashes.len() {
+        return proof;
+    }
+
+    let mut level = hashes.to_vec();
+    let mut position = index;
+    while level.len() > 1 {
+        let sibling = if position.is_multiple_of(2) {
+            // Odd-sized levels pair the last node with itself
+            (
+                level.get(position + 1).unwrap_or(&level[position]).clone(),
+                false,
+            )
+        } else {
+            (level[position - 1].clone(), true)
+        };
+        proof.push(sibling);
+        level = next_level(&level);
+        position /= 2;
+    }
+    proof
+}
+
+/// Checks that `leaf` hashes up to `root` along `proof`
+pub fn verify_proof(leaf: &str, proof: &[(String, bool)], root: &str) -> bool {
+    let computed = proof
+        .iter()
+        .fold(leaf.to_string(), |node, (sibling, sibling_is_left)| {
+            if *sibling_is_left {
+                hash_pair(sibling, &node)
+            } else {
+                hash_pair(&node, sibling)
+            }
+        });
+    computed == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| {
+                let mut hasher = Sha256::new();
+                hasher.update(format!("transaction {}", i).as_bytes());
+                format!("{:x}", hasher.finalize())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_list_has_defined_root() {
+        assert_eq!(merkle_root(&[]), EMPTY_MERKLE_ROOT);
+        assert!(merkle_proof(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn single_hash_is_its_own_root() {
+        let hashes = leaves(1);
+        assert_eq!(merkle_root(&hashes), hashes[0]);
+        assert!(merkle_proof(&hashes, 0).is_empty());
+        assert!(verify_proof(&hashes[0], &[], &hashes[0]));
+    }
+
+    #[test]
+    fn two_hashes_are_paired() {
+        let hashes = leaves(2);
+        assert_eq!(merkle_root(&hashes), hash_pair(&hashes[0], &hashes[1]));
+    }
+
+    #[test]
+    fn odd_count_duplicates_last_hash() {
+        let hashes = leaves(3);
+        let expected = hash_pair(
+            &hash_pair(&hashes[0], &hashes[1]),
+            &hash_pair(&hashes[2], &hashes[2]),
+        );
+        assert_eq!(merkle_root(&hashes), expected);
+    }
+
+    #[test]
+    fn every_leaf_has_a_valid_proof() {
+        for count in [1, 2, 3, 8] {
+            let hashes = leaves(count);
+            let root = merkle_root(&hashes);
+            for (index, leaf) in hashes.iter().enumerate() {
+                let proof = merkle_proof(&hashes, index);
+                assert!(verify_proof(leaf, &proof, &root), "{} of {}", index, count);
+            }
+        }
+    }
+
+    #[test]
+    fn eight_leaves_need_three_proof_steps() {
+        let hashes = leaves(8);
+        assert_eq!(merkle_proof(&hashes, 5).len(), 3);
+    }
+
+    #[test]
+    fn tampered_leaf_fails_verification() {
+        let hashes = leaves(8);
+        let root = merkle_root(&hashes);
+        let proof = merkle_proof(&hashes, 3);
+        assert!(!verify_proof(&hashes[4], &proof, &root));
+        assert!(!verify_proof(&"f".repeat(64), &proof, &root));
+    }
+}
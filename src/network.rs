@@ -0,0 +1,571 @@
+use crate::block::{Block, DIFFICULTY};
+use crate::blockchain::{Blockchain, BlockchainError, ChainUpdate};
+use crate::events::ChainEvent;
+use crate::genesis::GenesisConfig;
+use crate::transaction::{Mempool, MempoolError, Transaction};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Genesis timestamp shared by nodes that start without a chain file, so they agree on block 0
+pub const NETWORK_GENESIS_TIMESTAMP: u64 = 1_700_000_000;
+
+/// Largest message a node will read; guards against bogus length prefixes
+pub const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Most blocks sent in reply to one `GetBlocks`
+pub const MAX_BLOCKS_PER_MESSAGE: usize = 500;
+
+/// How long a node waits on a peer before giving up on a read or write
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Genesis block configuration for nodes started without a chain file
+pub fn network_genesis() -> GenesisConfig {
+    GenesisConfig::new(NETWORK_GENESIS_TIMESTAMP, DIFFICULTY)
+}
+
+/// Messages exchanged between nodes, each sent as a 4-byte big-endian length and a JSON body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    /// Asks for the index of the peer's best tip; answered with `ChainHeight`
+    GetChainHeight,
+    ChainHeight(u32),
+    /// Asks for best-chain blocks starting at index `from`; answered with `Blocks`
+    GetBlocks {
+        from: u32,
+    },
+    Blocks(Vec<Block>),
+    /// Announces a newly mined block
+    NewBlock(Block),
+    /// Announces a transaction for the mempool
+    NewTransaction(Transaction),
+    /// Tells the peer where the sender listens, so it can push blocks back
+    Hello {
+        listen_addr: SocketAddr,
+    },
+}
+
+/// Reasons talking to a peer can fail
+#[derive(Debug)]
+pub enum NetworkError {
+    /// The connection failed or timed out
+    Io(io::Error),
+    /// A message couldn't be encoded or decoded
+    Parse(serde_json::Error),
+    /// The peer answered with something other than the expected reply
+    UnexpectedMessage(Box<Message>),
+    /// A block received from the peer was refused by the chain
+    Rejected(BlockchainError),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::Io(e) => write!(f, "connection error: {}", e),
+            NetworkError::Parse(e) => write!(f, "malformed message: {}", e),
+            NetworkError::UnexpectedMessage(message) => {
+                write!(f, "unexpected reply from peer: {:?}", message)
+            }
+            NetworkError::Rejected(e) => write!(f, "peer sent an invalid block: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<io::Error> for NetworkError {
+    fn from(e: io::Error) -> Self {
+        NetworkError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for NetworkError {
+    fn from(e: serde_json::Error) -> Self {
+        NetworkError::Parse(e)
+    }
+}
+
+impl From<BlockchainError> for NetworkError {
+    fn from(e: BlockchainError) -> Self {
+        NetworkError::Rejected(e)
+    }
+}
+
+/// Reasons a node refuses a locally submitted transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubmitError {
+    /// Coinbase transactions are only created by miners
+    Coinbase,
+    /// The sender's balance, less what it already has pending, doesn't cover the transaction
+    InsufficientBalance {
+        address: String,
+        available: f64,
+        required: f64,
+    },
+    /// The mempool refused the transaction
+    Mempool(MempoolError),
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubmitError::Coinbase => write!(f, "coinbase transactions cannot be submitted"),
+            SubmitError::InsufficientBalance {
+                address,
+                available,
+                required,
+            } => write!(
+                f,
+                "insufficient balance: {} has {} available but needs {}",
+                address, available, required
+            ),
+            SubmitError::Mempool(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {}
+
+impl From<MempoolError> for SubmitError {
+    fn from(e: MempoolError) -> Self {
+        SubmitError::Mempool(e)
+    }
+}
+
+/// Writes one length-prefixed message
+pub fn write_message(stream: &mut impl Write, message: &Message) -> Result<(), NetworkError> {
+    let body = serde_json::to_vec(message)?;
+    let length = u32::try_from(body.len())
+        .ok()
+        .filter(|&length| length as usize <= MAX_MESSAGE_BYTES)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "message too large"))?;
+    stream.write_all(&length.to_be_bytes())?;
+    stream.write_all(&body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads one length-prefixed message
+pub fn read_message(stream: &mut impl Read) -> Result<Message, NetworkError> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large").into());
+    }
+
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body)?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+struct NodeState {
+    blockchain: Blockchain,
+    mempool: Mempool,
+    peers: Vec<String>,
+    listen_addr: Option<SocketAddr>,
+}
+
+impl NodeState {
+    /// Checks the node makes before offering a submitted transaction to its mempool
+    fn check_submission(&self, transaction: &Transaction) -> Result<(), SubmitError> {
+        if transaction.is_coinbase() {
+            return Err(SubmitError::Coinbase);
+        }
+        let available = self
+            .blockchain
+            .get_available_balance(&transaction.sender, &self.mempool);
+        if available < transaction.total_cost() {
+            return Err(SubmitError::InsufficientBalance {
+                address: transaction.sender.to_string(),
+                available,
+                required: transaction.total_cost(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Applies what a block did to the chain: on a reorg, puts the transactions only
+    /// the abandoned branch confirmed back in the mempool, skipping any the new best
+    /// chain already holds or whose sender can no longer pay for them
+    fn absorb(&mut self, update: ChainUpdate) {
+        let ChainUpdate::Reorganized(reorg) = update else {
+            return;
+        };
+        for transaction in reorg.orphaned_transactions {
+            if self
+                .blockchain
+                .contains_transaction(&transaction.calculate_hash())
+                || self.check_submission(&transaction).is_err()
+            {
+                continue;
+            }
+            let _ = self.mempool.add_transaction(transaction);
+        }
+    }
+}
+
+/// A blockchain and mempool shared with peers over TCP.
+/// Clones are handles to the same node.
+#[derive(Clone)]
+pub struct Node {
+    state: Arc<Mutex<NodeState>>,
+}
+
+impl Node {
+    /// Creates a node around an existing chain and mempool. The mempool publishes on
+    /// the chain's event bus from then on.
+    pub fn new(blockchain: Blockchain, mut mempool: Mempool) -> Node {
+        mempool.set_events(blockchain.events().clone());
+        Node {
+            state: Arc::new(Mutex::new(NodeState {
+                blockchain,
+                mempool,
+                peers: Vec::new(),
+                listen_addr: None,
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, NodeState> {
+        self.state.lock().expect("node state lock poisoned")
+    }
+
+    /// Returns a receiver for the node's chain and mempool events from now on,
+    /// including transactions it refuses in `submit_transaction`
+    pub fn subscribe(&self) -> Receiver<ChainEvent> {
+        self.state().blockchain.subscribe()
+    }
+
+    /// Copy of the node's current chain
+    pub fn blockchain(&self) -> Blockchain {
+        self.state().blockchain.clone()
+    }
+
+    /// Copy of the blocks on the node's best chain, genesis first, without the side
+    /// blocks and indexes `blockchain` also copies
+    pub fn best_chain(&self) -> Vec<Block> {
+        self.state().blockchain.best_chain().to_vec()
+    }
+
+    /// Copy of the block at `index` on the node's best chain
+    pub fn block(&self, index: u32) -> Option<Block> {
+        self.state().blockchain.get_block_by_index(index).cloned()
+    }
+
+    /// Hash of the node's best tip
+    pub fn tip_hash(&self) -> String {
+        self.state().blockchain.tip().hash.clone()
+    }
+
+    /// Confirmed balance of `address` on the node's best chain
+    pub fn balance(&self, address: &str) -> f64 {
+        self.state().blockchain.get_balance(address)
+    }
+
+    /// Transactions waiting in the node's mempool
+    pub fn pending_transactions(&self) -> Vec<Transaction> {
+        self.state().mempool.pending().to_vec()
+    }
+
+    /// Addresses of the peers this node pushes blocks and transactions to
+    pub fn peers(&self) -> Vec<String> {
+        self.state().peers.clone()
+    }
+
+    /// Starts serving peers on `addr` in the background and returns the bound address
+    pub fn listen(&self, addr: &str) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        self.state().listen_addr = Some(local_addr);
+
+        let node = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let node = node.clone();
+                thread::spawn(move || node.serve(stream));
+            }
+        });
+        Ok(local_addr)
+    }
+
+    /// Answers one peer connection until it closes
+    fn serve(&self, mut stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(PEER_TIMEOUT));
+        while let Ok(message) = read_message(&mut stream) {
+            let reply = match message {
+                Message::GetChainHeight => {
+                    Some(Message::ChainHeight(self.state().blockchain.tip().index))
+                }
+                Message::GetBlocks { from } => Some(Message::Blocks(
+                    self.state()
+                        .blockchain
+                        .iter()
+                        .skip(from as usize)
+                        .take(MAX_BLOCKS_PER_MESSAGE)
+                        .cloned()
+                        .collect(),
+                )),
+                Message::NewBlock(block) => {
+                    self.receive_block(block);
+                    None
+                }
+                Message::NewTransaction(transaction) => {
+                    self.receive_transaction(transaction);
+                    None
+                }
+                Message::Hello { listen_addr } => {
+                    self.add_peer(&listen_addr.to_string());
+                    None
+                }
+                Message::ChainHeight(_) | Message::Blocks(_) => None,
+            };
+
+            if let Some(reply) = reply {
+                if write_message(&mut stream, &reply).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn add_peer(&self, addr: &str) {
+        let mut state = self.state();
+        if !state.peers.iter().any(|peer| peer == addr) {
+            state.peers.push(addr.to_string());
+        }
+    }
+
+    /// Adds `addr` as a peer, introduces this node to it and catches up from it.
+    /// Returns the number of blocks added.
+    pub fn connect(&self, addr: &str) -> Result<usize, NetworkError> {
+        self.add_peer(addr);
+        let listen_addr = self.state().listen_addr;
+        if let Some(listen_addr) = listen_addr {
+            let mut stream = open(addr)?;
+            // Advertise the address the peer actually reached us from, not a wildcard
+            let listen_addr = SocketAddr::new(stream.local_addr()?.ip(), listen_addr.port());
+            write_message(&mut stream, &Message::Hello { listen_addr })?;
+        }
+        self.sync(addr)
+    }
+
+    /// Fetches the blocks the peer at `addr` has beyond our tip, validates them and
+    /// adds them to the chain. Returns the number of blocks added.
+    ///
+    /// If the peer's blocks don't attach to our tip the whole peer chain is fetched,
+    /// letting `Blockchain::accept_block` pick the branch with the most work.
+    pub fn sync(&self, addr: &str) -> Result<usize, NetworkError> {
+        let mut stream = open(addr)?;
+        let mut added = 0;
+        loop {
+            write_message(&mut stream, &Message::GetChainHeight)?;
+            let peer_height = match read_message(&mut stream)? {
+                Message::ChainHeight(height) => height,
+                other => return Err(NetworkError::UnexpectedMessage(Box::new(other))),
+            };
+            let our_height = self.state().blockchain.tip().index;
+            if peer_height <= our_height {
+                return Ok(added);
+            }
+
+            let from = our_height + 1;
+            let blocks = fetch_blocks(&mut stream, from)?;
+            let attaches = blocks
+                .first()
+                .is_some_and(|block| block.previous_hash == self.tip_hash());
+            let blocks = if attaches {
+                blocks
+            } else {
+                fetch_chain(&mut stream)?
+            };
+            if blocks.is_empty() {
+                return Ok(added);
+            }
+
+            let mut state = self.state();
+            let mut added_now = 0;
+            for block in blocks {
+                match state.blockchain.accept_block(block) {
+                    Ok(ChainUpdate::Orphaned) => {}
+                    Ok(update) => {
+                        state.absorb(update);
+                        added_now += 1;
+                    }
+                    Err(BlockchainError::DuplicateBlock { .. }) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            // A taller peer chain with less work teaches us nothing new on a second pass
+            if added_now == 0 {
+                return Ok(added);
+            }
+            added += added_now;
+        }
+    }
+
+    /// Syncs from every known peer, skipping peers that can't be reached
+    pub fn sync_all(&self) -> usize {
+        self.peers()
+            .iter()
+            .filter_map(|peer| self.sync(peer).ok())
+            .sum()
+    }
+
+    /// Handles a block announced by a peer: blocks that don't attach to a known
+    /// block wait in the orphan pool while a sync fetches their ancestors
+    fn receive_block(&self, block: Block) {
+        let hash = block.hash.clone();
+        let result = {
+            let mut state = self.state();
+            let result = state.blockchain.accept_block(block);
+            if let Ok(update) = &result {
+                state.absorb(update.clone());
+            }
+            result
+        };
+        match result {
+            Ok(ChainUpdate::Extended) | Ok(ChainUpdate::Reorganized(_)) => {
+                // Relay the block as stored, not as received
+                let stored = self.state().blockchain.get_block_by_hash(&hash).cloned();
+                if let Some(stored) = stored {
+                    self.broadcast(&Message::NewBlock(stored));
+                }
+            }
+            Ok(ChainUpdate::Orphaned) => {
+                self.sync_all();
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles a transaction relayed by a peer, holding it to the same checks as a
+    /// local submission before queueing and relaying it
+    fn receive_transaction(&self, transaction: Transaction) {
+        let added = {
+            let mut state = self.state();
+            state.check_submission(&transaction).is_ok()
+                && state.mempool.add_transaction(transaction.clone()).is_ok()
+        };
+        if added {
+            self.broadcast(&Message::NewTransaction(transaction));
+        }
+    }
+
+    /// Queues a transaction locally and announces it to peers. The sender must be able
+    /// to pay for it on top of its other pending transactions.
+    pub fn submit_transaction(&self, transaction: Transaction) -> Result<(), SubmitError> {
+        {
+            let mut state = self.state();
+            if let Err(e) = state.check_submission(&transaction) {
+                state
+                    .blockchain
+                    .events()
+                    .publish(ChainEvent::TransactionRejected {
+                        tx_id: transaction.calculate_hash(),
+                        reason: e.to_string(),
+                    });
+                return Err(e);
+            }
+            state.mempool.add_transaction(transaction.clone())?;
+        }
+        self.broadcast(&Message::NewTransaction(transaction));
+        Ok(())
+    }
+
+    /// Mines the pending transactions into a block and announces it to peers
+    pub fn mine(&self, miner_address: &str) -> Result<Block, BlockchainError> {
+        let block = {
+            let mut state = self.state();
+            let NodeState {
+                blockchain,
+                mempool,
+                ..
+            } = &mut *state;
+            blockchain
+                .mine_pending_transactions(mempool, miner_address)?
+                .clone()
+        };
+        self.broadcast(&Message::NewBlock(block.clone()));
+        Ok(block)
+    }
+
+    /// Sends `message` to every peer, ignoring peers that can't be reached
+    fn broadcast(&self, message: &Message) {
+        for peer in self.peers() {
+            if let Ok(mut stream) = open(&peer) {
+                let _ = write_message(&mut stream, message);
+            }
+        }
+    }
+}
+
+/// Connects to a peer with read and write timeouts set
+fn open(addr: &str) -> Result<TcpStream, NetworkError> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+    stream.set_write_timeout(Some(PEER_TIMEOUT))?;
+    Ok(stream)
+}
+
+fn fetch_blocks(stream: &mut TcpStream, from: u32) -> Result<Vec<Block>, NetworkError> {
+    write_message(stream, &Message::GetBlocks { from })?;
+    match read_message(stream)? {
+        Message::Blocks(blocks) => Ok(blocks),
+        other => Err(NetworkError::UnexpectedMessage(Box::new(other))),
+    }
+}
+
+/// Fetches the peer's whole best chain after genesis
+fn fetch_chain(stream: &mut TcpStream) -> Result<Vec<Block>, NetworkError> {
+    let mut chain = Vec::new();
+    loop {
+        let batch = fetch_blocks(stream, chain.len() as u32 + 1)?;
+        if batch.is_empty() {
+            return Ok(chain);
+        }
+        chain.extend(batch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_through_the_framing() {
+        let messages = vec![
+            Message::GetChainHeight,
+            Message::GetBlocks { from: 3 },
+            Message::NewTransaction(Transaction::new("Alice", "Bob", 1.0)),
+        ];
+        let mut buffer = Vec::new();
+        for message in &messages {
+            write_message(&mut buffer, message).unwrap();
+        }
+
+        let mut reader = buffer.as_slice();
+        for message in messages {
+            assert_eq!(read_message(&mut reader).unwrap(), message);
+        }
+        assert!(matches!(
+            read_message(&mut reader),
+            Err(NetworkError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_length_prefix_is_refused() {
+        let mut frame = (MAX_MESSAGE_BYTES as u32 + 1).to_be_bytes().to_vec();
+        frame.extend_from_slice(b"{}");
+        assert!(matches!(
+            read_message(&mut frame.as_slice()),
+            Err(NetworkError::Io(_))
+        ));
+    }
+}
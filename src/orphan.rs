@@ -0,0 +1,158 @@
+use crate::block::Block;
+use std::collections::HashMap;
+
+/// Default number of orphan blocks kept before the oldest is evicted
+pub const MAX_ORPHANS: usize = 100;
+
+/// Default seconds an orphan waits for its parent before it is dropped
+pub const MAX_ORPHAN_AGE_SECS: u64 = 60 * 60;
+
+/// A block waiting for its parent, and when it arrived
+#[derive(Debug, Clone)]
+pub struct Orphan {
+    pub block: Block,
+    pub received_at: u64, // UNIX timestamp of arrival
+    sequence: u64,        // Arrival order, to evict deterministically within a second
+}
+
+/// Blocks whose parent isn't known yet, by parent hash. They are stored unchecked
+/// and only validated once their parent connects.
+#[derive(Debug, Clone)]
+pub struct OrphanPool {
+    orphans: HashMap<String, Vec<Orphan>>,
+    len: usize,
+    next_sequence: u64,
+    pub max_orphans: usize,
+    pub max_age_secs: u64,
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        OrphanPool::new(MAX_ORPHANS, MAX_ORPHAN_AGE_SECS)
+    }
+}
+
+impl OrphanPool {
+    /// Creates an empty pool holding at most `max_orphans` blocks for `max_age_secs` each
+    pub fn new(max_orphans: usize, max_age_secs: u64) -> OrphanPool {
+        OrphanPool {
+            orphans: HashMap::new(),
+            len: 0,
+            next_sequence: 0,
+            max_orphans,
+            max_age_secs,
+        }
+    }
+
+    /// Stashes `block` until its parent arrives. Orphans older than the maximum age
+    /// are dropped first, then the oldest ones if the pool is still full.
+    pub fn insert(&mut self, block: Block, now: u64) {
+        self.expire(now);
+        while self.len >= self.max_orphans.max(1) {
+            self.evict_oldest();
+        }
+
+        let orphan = Orphan {
+            block,
+            received_at: now,
+            sequence: self.next_sequence,
+        };
+        self.next_sequence += 1;
+        self.orphans
+            .entry(orphan.block.previous_hash.clone())
+            .or_default()
+            .push(orphan);
+        self.len += 1;
+    }
+
+    /// Removes and returns the orphans whose parent is `parent_hash`, oldest first
+    pub fn take_children(&mut self, parent_hash: &str) -> Vec<Block> {
+        let children = self.orphans.remove(parent_hash).unwrap_or_default();
+        self.len -= children.len();
+        children.into_iter().map(|orphan| orphan.block).collect()
+    }
+
+    /// Drops orphans that arrived more than `max_age_secs` before `now`
+    pub fn expire(&mut self, now: u64) {
+        let max_age_secs = self.max_age_secs;
+        self.orphans.retain(|_, children| {
+            children.retain(|orphan| now.saturating_sub(orphan.received_at) <= max_age_secs);
+            !children.is_empty()
+        });
+        self.len = self.orphans.values().map(Vec::len).sum();
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .iter()
+            .min_by_key(|orphan| (orphan.received_at, orphan.sequence))
+            .map(|orphan| (orphan.block.previous_hash.clone(), orphan.sequence));
+        if let Some((parent_hash, sequence)) = oldest {
+            let children = self
+                .orphans
+                .get_mut(&parent_hash)
+                .expect("the oldest orphan is in the pool");
+            children.retain(|orphan| orphan.sequence != sequence);
+            if children.is_empty() {
+                self.orphans.remove(&parent_hash);
+            }
+            self.len -= 1;
+        }
+    }
+
+    /// Returns true if a block with this hash is waiting in the pool
+    pub fn contains(&self, hash: &str) -> bool {
+        self.iter().any(|orphan| orphan.block.hash == hash)
+    }
+
+    /// Iterates over the waiting orphans in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &Orphan> {
+        self.orphans.values().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orphan(parent: &str, hash: &str) -> Block {
+        let mut block = Block::new(1, parent.to_string(), Vec::new());
+        block.hash = hash.to_string();
+        block
+    }
+
+    #[test]
+    fn full_pool_evicts_the_oldest() {
+        let mut pool = OrphanPool::new(2, MAX_ORPHAN_AGE_SECS);
+        pool.insert(orphan("p1", "a"), 100);
+        pool.insert(orphan("p2", "b"), 100);
+        pool.insert(orphan("p1", "c"), 101);
+
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains("a"));
+        assert!(pool.contains("b") && pool.contains("c"));
+        assert_eq!(pool.take_children("p1")[0].hash, "c");
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn old_orphans_expire() {
+        let mut pool = OrphanPool::new(10, 60);
+        pool.insert(orphan("p1", "a"), 100);
+        pool.insert(orphan("p1", "b"), 150);
+        pool.insert(orphan("p2", "c"), 170);
+
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains("a"));
+        assert_eq!(pool.take_children("p1").len(), 1);
+        assert!(pool.take_children("p1").is_empty());
+    }
+}
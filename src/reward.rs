@@ -0,0 +1,162 @@
+use crate::block::Block;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Default coins paid to the miner of each block before any halving
+pub const BLOCK_REWARD: f64 = 137.0;
+
+/// After this many halvings the reward is zero, as if it were shifted out of an integer
+pub const MAX_HALVINGS: u32 = 64;
+
+/// How much a block's coinbase may mint at each height
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardSchedule {
+    pub initial_reward: f64,   // Reward for blocks before the first halving
+    pub halving_interval: u32, // Halve the reward every this many blocks; 0 never halves
+}
+
+impl Default for RewardSchedule {
+    fn default() -> Self {
+        RewardSchedule {
+            initial_reward: BLOCK_REWARD,
+            halving_interval: 210,
+        }
+    }
+}
+
+/// Reasons a block's coinbase is refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoinbaseError {
+    /// The block has no coinbase
+    Missing,
+    /// The coinbase isn't the block's first transaction
+    NotFirst { position: usize },
+    /// The block has more than one coinbase
+    Multiple { count: usize },
+    /// The coinbase pays out more than the block reward plus the block's fees
+    ExcessiveReward { paid: f64, allowed: f64 },
+}
+
+impl fmt::Display for CoinbaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoinbaseError::Missing => write!(f, "block has no coinbase"),
+            CoinbaseError::NotFirst { position } => write!(
+                f,
+                "coinbase is transaction {} instead of the first",
+                position
+            ),
+            CoinbaseError::Multiple { count } => {
+                write!(f, "block has {} coinbases instead of one", count)
+            }
+            CoinbaseError::ExcessiveReward { paid, allowed } => write!(
+                f,
+                "coinbase pays {} but at most {} is allowed",
+                paid, allowed
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CoinbaseError {}
+
+impl RewardSchedule {
+    /// Returns the reward for the block at `height`
+    pub fn reward_at(&self, height: u32) -> f64 {
+        if self.halving_interval == 0 {
+            return self.initial_reward;
+        }
+
+        let halvings = height / self.halving_interval;
+        if halvings >= MAX_HALVINGS {
+            return 0.0;
+        }
+        self.initial_reward / 2f64.powi(halvings as i32)
+    }
+
+    /// Most the coinbase of `block` may pay: the reward at its height plus the fees
+    /// of its other transactions
+    pub fn allowed_payout(&self, block: &Block) -> f64 {
+        let fees: f64 = block
+            .transactions
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .map(|tx| tx.fee)
+            .sum();
+        self.reward_at(block.index) + fees
+    }
+
+    /// Checks that `block` starts with its only coinbase, paying at most
+    /// `allowed_payout`. Genesis blocks are exempt: their coinbases are allocations.
+    pub fn check_coinbase(&self, block: &Block) -> Result<(), CoinbaseError> {
+        if block.index == 0 {
+            return Ok(());
+        }
+        let coinbases: Vec<(usize, &Transaction)> = block
+            .transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_coinbase())
+            .collect();
+        let (position, coinbase) = match coinbases.as_slice() {
+            [] => return Err(CoinbaseError::Missing),
+            [only] => *only,
+            _ => {
+                return Err(CoinbaseError::Multiple {
+                    count: coinbases.len(),
+                })
+            }
+        };
+        if position != 0 {
+            return Err(CoinbaseError::NotFirst { position });
+        }
+
+        let paid = coinbase.amount;
+        let allowed = self.allowed_payout(block);
+        if paid > allowed {
+            return Err(CoinbaseError::ExcessiveReward { paid, allowed });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reward_halves_at_each_boundary() {
+        let schedule = RewardSchedule {
+            initial_reward: 50.0,
+            halving_interval: 10,
+        };
+        assert_eq!(schedule.reward_at(1), 50.0);
+        assert_eq!(schedule.reward_at(9), 50.0);
+        assert_eq!(schedule.reward_at(10), 25.0);
+        assert_eq!(schedule.reward_at(19), 25.0);
+        assert_eq!(schedule.reward_at(20), 12.5);
+    }
+
+    #[test]
+    fn rewards_can_be_fractional_and_run_out() {
+        let schedule = RewardSchedule {
+            initial_reward: BLOCK_REWARD,
+            halving_interval: 1,
+        };
+        assert_eq!(schedule.reward_at(1), 68.5);
+        assert_eq!(schedule.reward_at(3), 17.125);
+        assert!(schedule.reward_at(MAX_HALVINGS - 1) > 0.0);
+        assert_eq!(schedule.reward_at(MAX_HALVINGS), 0.0);
+        assert_eq!(schedule.reward_at(u32::MAX), 0.0);
+    }
+
+    #[test]
+    fn zero_interval_never_halves() {
+        let schedule = RewardSchedule {
+            initial_reward: 10.0,
+            halving_interval: 0,
+        };
+        assert_eq!(schedule.reward_at(u32::MAX), 10.0);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::address::Address;
+use crate::blockchain::Blockchain;
+use crate::difficulty::DifficultyConfig;
+use crate::genesis::GenesisConfig;
+use crate::reward::{RewardSchedule, BLOCK_REWARD};
+use crate::transaction::{Mempool, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use toml::Spanned;
+
+/// A simulation described in TOML: the accounts that exist at genesis and the
+/// transactions sent between them, in order
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub difficulty: usize, // Fixed mining difficulty of every block
+    #[serde(default = "default_block_reward")]
+    pub block_reward: f64,
+    pub miner: Address, // Rewarded for every block
+    #[serde(default)]
+    pub accounts: Vec<ScenarioAccount>,
+    #[serde(default)]
+    pub transactions: Vec<ScenarioTransaction>,
+}
+
+fn default_block_reward() -> f64 {
+    BLOCK_REWARD
+}
+
+/// An account and the balance it is allocated in the genesis block
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAccount {
+    pub name: Spanned<Address>,
+    #[serde(default)]
+    pub balance: f64,
+}
+
+/// A transfer the scenario sends, and whether the chain should confirm it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioTransaction {
+    pub from: Spanned<Address>,
+    pub to: Spanned<Address>,
+    pub amount: f64,
+    #[serde(default)]
+    pub expect: Outcome,
+    #[serde(skip)]
+    pub line: usize, // Line the transaction starts on, for error messages
+}
+
+/// What happened to a scenario transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// Mined into a block
+    #[default]
+    Accepted,
+    /// Refused by the mempool or left out of the block, e.g. for overdrawing its sender
+    Rejected,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Accepted => write!(f, "accepted"),
+            Outcome::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+/// Reasons a scenario can't be loaded or doesn't play out as written
+#[derive(Debug)]
+pub enum ScenarioError {
+    /// The scenario file couldn't be read
+    Io(io::Error),
+    /// The file isn't valid TOML of the expected shape
+    Parse { line: usize, message: String },
+    /// An account is declared twice
+    DuplicateAccount { line: usize, name: String },
+    /// A transaction names an account that isn't declared
+    UndeclaredAccount { line: usize, name: String },
+    /// A transaction's `expect` annotation didn't hold
+    UnexpectedOutcome {
+        line: usize,
+        expected: Outcome,
+        found: Outcome,
+    },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "could not read scenario: {}", e),
+            ScenarioError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ScenarioError::DuplicateAccount { line, name } => {
+                write!(f, "line {}: account {} is declared twice", line, name)
+            }
+            ScenarioError::UndeclaredAccount { line, name } => {
+                write!(f, "line {}: account {} is not declared", line, name)
+            }
+            ScenarioError::UnexpectedOutcome {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: transaction was {} but expected to be {}",
+                line, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<io::Error> for ScenarioError {
+    fn from(e: io::Error) -> Self {
+        ScenarioError::Io(e)
+    }
+}
+
+/// The chain a scenario produced and where the coins ended up
+#[derive(Debug)]
+pub struct ScenarioResult {
+    pub blockchain: Blockchain,
+    pub outcomes: Vec<Outcome>, // One per scenario transaction, in order
+    pub balances: BTreeMap<String, f64>, // Final balance of every account and the miner
+}
+
+/// Loads the scenario at `path` and runs it
+pub fn run_scenario(path: &Path) -> Result<ScenarioResult, ScenarioError> {
+    Scenario::load(path)?.run()
+}
+
+impl Scenario {
+    /// Reads and checks the scenario at `path`
+    pub fn load(path: &Path) -> Result<Scenario, ScenarioError> {
+        Scenario::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a scenario and checks that every transaction names declared accounts
+    pub fn parse(source: &str) -> Result<Scenario, ScenarioError> {
+        let mut scenario: Scenario = toml::from_str(source).map_err(|e| ScenarioError::Parse {
+            line: e.span().map_or(1, |span| line_of(source, span.start)),
+            message: e.message().to_string(),
+        })?;
+
+        // The miner is always an account; everything else must be declared
+        let mut declared = vec![scenario.miner.as_str()];
+        for account in &scenario.accounts {
+            if declared.contains(&account.name.get_ref().as_str()) {
+                return Err(ScenarioError::DuplicateAccount {
+                    line: line_of(source, account.name.span().start),
+                    name: account.name.get_ref().to_string(),
+                });
+            }
+            declared.push(account.name.get_ref());
+        }
+        for transaction in &mut scenario.transactions {
+            transaction.line = line_of(source, transaction.from.span().start);
+            for name in [&transaction.from, &transaction.to] {
+                if !declared.contains(&name.get_ref().as_str()) {
+                    return Err(ScenarioError::UndeclaredAccount {
+                        line: line_of(source, name.span().start),
+                        name: name.get_ref().to_string(),
+                    });
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Runs the scenario on a fresh chain, mining one block per transaction, and
+    /// checks every `expect` annotation along the way
+    pub fn run(&self) -> Result<ScenarioResult, ScenarioError> {
+        let mut genesis = GenesisConfig {
+            difficulty: self.difficulty,
+            ..GenesisConfig::default()
+        };
+        for account in self.accounts.iter().filter(|account| account.balance > 0.0) {
+            genesis = genesis.with_allocation(account.name.get_ref(), account.balance);
+        }
+
+        let mut blockchain = Blockchain::with_genesis(genesis);
+        // Pin the difficulty so the scenario takes the same effort however fast it runs
+        blockchain.difficulty_config = DifficultyConfig {
+            initial_difficulty: self.difficulty,
+            min_difficulty: self.difficulty,
+            max_difficulty: self.difficulty,
+            ..DifficultyConfig::default()
+        };
+        blockchain.reward_schedule = RewardSchedule {
+            initial_reward: self.block_reward,
+            halving_interval: 0,
+        };
+
+        let mut mempool = Mempool::new();
+        let mut outcomes = Vec::with_capacity(self.transactions.len());
+        for (position, scenario_tx) in self.transactions.iter().enumerate() {
+            // Number the transactions so repeated transfers get distinct ids
+            let transaction = Transaction::new(
+                scenario_tx.from.get_ref(),
+                scenario_tx.to.get_ref(),
+                scenario_tx.amount,
+            )
+            .with_nonce(position as u64 + 1);
+            let tx_id = transaction.calculate_hash();
+
+            let outcome = match mempool.add_transaction(transaction) {
+                Ok(()) => {
+                    let mined = blockchain
+                        .mine_pending_transactions(&mut mempool, &self.miner)
+                        .is_ok();
+                    if mined && blockchain.contains_transaction(&tx_id) {
+                        Outcome::Accepted
+                    } else {
+                        Outcome::Rejected
+                    }
+                }
+                Err(_) => Outcome::Rejected,
+            };
+            if outcome != scenario_tx.expect {
+                return Err(ScenarioError::UnexpectedOutcome {
+                    line: scenario_tx.line,
+                    expected: scenario_tx.expect,
+                    found: outcome,
+                });
+            }
+            outcomes.push(outcome);
+        }
+
+        let balances = self
+            .accounts
+            .iter()
+            .map(|account| account.name.get_ref())
+            .chain([&self.miner])
+            .map(|name| (name.to_string(), blockchain.get_balance(name)))
+            .collect();
+        Ok(ScenarioResult {
+            blockchain,
+            outcomes,
+            balances,
+        })
+    }
+}
+
+/// 1-based line number of the byte at `offset` in `source`
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"difficulty = 1
+miner = "Miner"
+
+[[accounts]]
+name = "Alice"
+balance = 10.0
+
+[[transactions]]
+from = "Alice"
+to = "Miner"
+amount = 4.0
+"#;
+
+    #[test]
+    fn malformed_files_report_the_line() {
+        let source = SCENARIO.replace("amount = 4.0", "amount = \"four\"");
+        match Scenario::parse(&source) {
+            Err(ScenarioError::Parse { line, .. }) => assert_eq!(line, 11),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        let source = SCENARIO.replace("to = \"Miner\"", "to = \"bsrMiner\"");
+        match Scenario::parse(&source) {
+            Err(ScenarioError::Parse { line, message }) => {
+                assert_eq!(line, 10);
+                assert!(message.contains("bsrMiner"));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        let source = SCENARIO.replace("balance", "balanse");
+        match Scenario::parse(&source) {
+            Err(ScenarioError::Parse { line, message }) => {
+                assert_eq!(line, 6);
+                assert!(message.contains("balanse"));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn undeclared_accounts_report_the_line() {
+        let source = SCENARIO.replace("to = \"Miner\"", "to = \"Mallory\"");
+        let error = Scenario::parse(&source).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 10: account Mallory is not declared"
+        );
+
+        let source = format!("{}\n[[accounts]]\nname = \"Alice\"\n", SCENARIO);
+        assert!(matches!(
+            Scenario::parse(&source),
+            Err(ScenarioError::DuplicateAccount { line: 14, .. })
+        ));
+    }
+
+    #[test]
+    fn wrong_expectations_fail_the_run() {
+        let source = SCENARIO.replace("amount = 4.0", "amount = 40.0");
+        let error = Scenario::parse(&source).unwrap().run().unwrap_err();
+        assert!(matches!(
+            error,
+            ScenarioError::UnexpectedOutcome {
+                line: 9,
+                expected: Outcome::Accepted,
+                found: Outcome::Rejected,
+            }
+        ));
+
+        let result = Scenario::parse(SCENARIO).unwrap().run().unwrap();
+        assert_eq!(result.outcomes, vec![Outcome::Accepted]);
+        assert_eq!(result.balances["Alice"], 6.0);
+        assert_eq!(result.balances["Miner"], BLOCK_REWARD + 4.0);
+    }
+}
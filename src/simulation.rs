@@ -0,0 +1,254 @@
+use crate::blockchain::Blockchain;
+use crate::clock::{Clock, MockClock, SystemClock};
+use crate::time::format_timestamp;
+use crate::transaction::{Mempool, Transaction};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Default list of traders used by the demo simulation
+pub const TRADER_NAMES: [&str; 5] = ["Bob", "Alice", "Charlie", "David", "Eve"];
+
+/// Amount passed along with every simulated transaction
+pub const TRANSFER_AMOUNT: f64 = 10.0;
+
+/// How the simulation moves coins between traders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ledger {
+    /// Plain transfers debiting and crediting account balances
+    #[default]
+    Account,
+    /// Transactions spending unspent outputs and returning change, like Bitcoin
+    Utxo,
+}
+
+/// Simulated time at the start of a seeded run (2023-11-14 22:13:20 UTC)
+pub const SEEDED_EPOCH: u64 = 1_700_000_000;
+
+/// Settings of a simulation run beyond its participants
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimulationOptions {
+    pub ledger: Ledger,
+    /// Makes the run reproducible: time comes from a mock clock starting at
+    /// `SEEDED_EPOCH`, the seconds between blocks are drawn from an RNG seeded with
+    /// this, and blocks are mined on one thread so the same nonce always wins
+    pub seed: Option<u64>,
+}
+
+/// Simulated passage of time in a seeded run
+struct SimulatedTime {
+    clock: Arc<MockClock>,
+    rng: StdRng,
+}
+
+impl SimulatedTime {
+    /// Moves the clock forward by a random block interval around `target_secs`
+    fn next_block(&mut self, target_secs: u64) {
+        let target_secs = target_secs.max(1);
+        self.clock.advance(self.rng.gen_range(1..=2 * target_secs));
+    }
+}
+
+/// How one block of the simulation was mined
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockReport {
+    pub index: u32,
+    pub hash: String,
+    pub nonce: u64,
+    pub iterations: u64, // Hashes tried across all mining threads
+    pub mining_ms: u64,
+    pub difficulty: usize,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Machine-readable summary of a simulation run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub blocks: Vec<BlockReport>, // Blocks mined by the simulation, genesis excluded
+    pub total_blocks: usize,      // Length of the chain, genesis included
+    pub total_reward: f64,        // Coins minted by block rewards
+    pub elapsed_ms: u64,
+}
+
+/// Runs the demo simulation: the miner and the traders pass a transaction
+/// around in a circle, one block per transaction
+pub fn run_simulation(miner_name: &str, trader_names: &[&str]) -> io::Result<Blockchain> {
+    let (blockchain, _) = simulate(miner_name, trader_names, &mut io::stdout())?;
+    Ok(blockchain)
+}
+
+/// Runs the demo simulation, writing progress to `out`, and returns the chain
+/// together with a report of every block mined
+pub fn simulate(
+    miner_name: &str,
+    trader_names: &[&str],
+    out: &mut impl Write,
+) -> io::Result<(Blockchain, SimulationReport)> {
+    simulate_with_ledger(miner_name, trader_names, Ledger::Account, out)
+}
+
+/// Runs the demo simulation like `simulate`, building transactions for `ledger`
+pub fn simulate_with_ledger(
+    miner_name: &str,
+    trader_names: &[&str],
+    ledger: Ledger,
+    out: &mut impl Write,
+) -> io::Result<(Blockchain, SimulationReport)> {
+    let options = SimulationOptions { ledger, seed: None };
+    simulate_with_options(miner_name, trader_names, &options, out)
+}
+
+/// Runs the demo simulation like `simulate` with the given options. Seeded runs
+/// with the same participants produce the same blocks, down to hashes and nonces.
+pub fn simulate_with_options(
+    miner_name: &str,
+    trader_names: &[&str],
+    options: &SimulationOptions,
+    out: &mut impl Write,
+) -> io::Result<(Blockchain, SimulationReport)> {
+    let started = Instant::now();
+
+    let mut simulated = options.seed.map(|seed| SimulatedTime {
+        clock: Arc::new(MockClock::new(SEEDED_EPOCH)),
+        rng: StdRng::seed_from_u64(seed),
+    });
+    let clock: Arc<dyn Clock> = match &simulated {
+        Some(simulated) => simulated.clock.clone(),
+        None => Arc::new(SystemClock),
+    };
+
+    // Mine on every available core, unless the run must be reproducible
+    let threads = match simulated {
+        Some(_) => 1,
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    // Initialize blockchain with genesis block
+    let mut blockchain = Blockchain::with_clock(clock.clone());
+    let mut mempool = Mempool::new();
+    mempool.set_clock(clock.clone());
+    let mut blocks = Vec::new();
+    let target_secs = blockchain.difficulty_config.target_block_time_secs;
+
+    writeln!(out, "Let's start mining and simulating transactions")?;
+
+    // Mine a first block so the miner has a reward to pass around
+    if let Some(simulated) = &mut simulated {
+        simulated.next_block(target_secs);
+    }
+    if let Some(report) = mine_block(&mut blockchain, &mut mempool, miner_name, threads, out)? {
+        blocks.push(report);
+    }
+
+    // Start with miner as the initial sender
+    let mut sender = miner_name.to_string();
+
+    // Simulate transactions between traders
+    for i in 0..trader_names.len() {
+        writeln!(out, "Mining Block {}", i + 2)?;
+
+        // Determine recipient (next trader or back to miner)
+        let recipient = match trader_names.get(i + 1) {
+            Some(name) => name.to_string(),
+            None => miner_name.to_string(),
+        };
+
+        // Queue the transaction and mine it into a new block
+        if let Some(simulated) = &mut simulated {
+            simulated.next_block(target_secs);
+        }
+        let transaction = match options.ledger {
+            Ledger::Account => {
+                Transaction::new_with_clock(&sender, &recipient, TRANSFER_AMOUNT, clock.as_ref())
+            }
+            Ledger::Utxo => {
+                match blockchain
+                    .utxo_set()
+                    .build_spend(&sender, &recipient, TRANSFER_AMOUNT, 0.0)
+                {
+                    // Unsigned, so it can be restamped without invalidating anything
+                    Ok(transaction) => Transaction {
+                        timestamp: clock.now_secs(),
+                        ..transaction
+                    },
+                    Err(e) => {
+                        writeln!(out, "Transaction rejected: {}", e)?;
+                        continue;
+                    }
+                }
+            }
+        };
+        if let Err(e) = mempool.add_transaction(transaction.clone()) {
+            writeln!(out, "Transaction rejected: {}", e)?;
+            continue;
+        }
+        match mine_block(&mut blockchain, &mut mempool, miner_name, threads, out)? {
+            Some(report) => blocks.push(report),
+            None => continue,
+        }
+
+        writeln!(out, "Transaction: {}", transaction)?;
+
+        // Update sender for next transaction
+        sender = recipient;
+        writeln!(out)?; // Add blank line for better readability
+    }
+
+    // Display simulation results
+    let total_blocks = blockchain.get_total_blocks();
+    writeln!(out, "Total Blocks: {}", total_blocks)?;
+
+    // Display how much mining effort the chain took
+    writeln!(out, "{}", blockchain.stats())?;
+
+    // Display end time of simulation
+    writeln!(out, "End Time: {}", format_timestamp(clock.now_secs()))?;
+    writeln!(out, "Mining Completed Successfully")?;
+
+    let report = SimulationReport {
+        blocks,
+        total_blocks,
+        total_reward: blockchain.total_supply(),
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+    Ok((blockchain, report))
+}
+
+/// Mines the pending transactions on `threads` threads and adds the block to the chain.
+/// Returns `None` if mining fails or the chain refuses the block.
+fn mine_block(
+    blockchain: &mut Blockchain,
+    mempool: &mut Mempool,
+    miner_name: &str,
+    threads: usize,
+    out: &mut impl Write,
+) -> io::Result<Option<BlockReport>> {
+    let mut block = blockchain.prepare_block(mempool, miner_name);
+    let result = match block.mine_block_parallel(threads) {
+        Ok(result) => result,
+        Err(e) => {
+            writeln!(out, "Mining failed: {}", e)?;
+            return Ok(None);
+        }
+    };
+    if let Err(e) = blockchain.add_block(block) {
+        writeln!(out, "Block rejected: {}", e)?;
+        return Ok(None);
+    }
+
+    let block = blockchain.latest_block();
+    writeln!(out, "Calculated Hash {}", block.hash)?;
+    writeln!(out, "Hash Rate: {:.0} H/s", result.hash_rate())?;
+    Ok(Some(BlockReport {
+        index: block.index,
+        hash: block.hash.clone(),
+        nonce: block.nonce,
+        iterations: result.hashes_tried,
+        mining_ms: result.elapsed.as_millis() as u64,
+        difficulty: block.difficulty,
+        transactions: block.transactions.clone(),
+    }))
+}
@@ -0,0 +1,198 @@
+use crate::blockchain::{Blockchain, ChainValidationError};
+use crate::encoding::DecodeError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Name of the file holding the chain inside a snapshot directory
+pub const SNAPSHOT_BLOCKS_FILE: &str = "blocks.bin";
+
+/// Name of the file describing the snapshot and its digest
+pub const SNAPSHOT_MANIFEST_FILE: &str = "manifest.json";
+
+/// Version of the snapshot layout written to the manifest
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Contents of `manifest.json`: what the snapshot should hold, so a copy can be
+/// checked before anything in it is trusted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub version: u32,
+    pub height: u32, // Index of the tip block
+    pub genesis_hash: String,
+    pub tip_hash: String,
+    pub payload_sha256: String, // Hex SHA-256 of the blocks file
+}
+
+/// Reasons `Blockchain::import_snapshot` can refuse a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// A snapshot file couldn't be read or written
+    Io(io::Error),
+    /// `manifest.json` isn't a manifest
+    Manifest(serde_json::Error),
+    /// The manifest is of a layout this version can't read
+    UnsupportedVersion(u32),
+    /// The blocks file doesn't hash to the manifest's digest
+    DigestMismatch { manifest: String, actual: String },
+    /// The blocks file matches the digest but can't be decoded
+    Decode(DecodeError),
+    /// The chain failed validation at block `index`
+    InvalidBlock {
+        index: u32,
+        error: ChainValidationError,
+    },
+    /// The chain's genesis isn't the one expected
+    GenesisMismatch { expected: String, actual: String },
+    /// The chain decoded but disagrees with the manifest about `field`
+    ManifestMismatch {
+        field: &'static str,
+        manifest: String,
+        chain: String,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "could not access snapshot: {}", e),
+            SnapshotError::Manifest(e) => write!(f, "could not parse snapshot manifest: {}", e),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::DigestMismatch { manifest, actual } => write!(
+                f,
+                "snapshot blocks hash to {} but the manifest lists {}",
+                actual, manifest
+            ),
+            SnapshotError::Decode(e) => write!(f, "could not decode snapshot blocks: {}", e),
+            SnapshotError::InvalidBlock { index, error } => {
+                write!(f, "snapshot block {} is invalid: {}", index, error)
+            }
+            SnapshotError::GenesisMismatch { expected, actual } => write!(
+                f,
+                "snapshot genesis is {} but {} was expected",
+                actual, expected
+            ),
+            SnapshotError::ManifestMismatch {
+                field,
+                manifest,
+                chain,
+            } => write!(
+                f,
+                "manifest {} is {} but the snapshot chain has {}",
+                field, manifest, chain
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Manifest(e)
+    }
+}
+
+impl From<DecodeError> for SnapshotError {
+    fn from(e: DecodeError) -> Self {
+        SnapshotError::Decode(e)
+    }
+}
+
+impl From<ChainValidationError> for SnapshotError {
+    fn from(error: ChainValidationError) -> Self {
+        SnapshotError::InvalidBlock {
+            index: error.index(),
+            error,
+        }
+    }
+}
+
+/// Writes `blockchain` into the directory `dir`: the binary chain file as
+/// `blocks.bin` and a `manifest.json` describing it
+pub fn export(blockchain: &Blockchain, dir: &Path) -> io::Result<SnapshotManifest> {
+    let payload = blockchain.to_bytes();
+    let manifest = SnapshotManifest {
+        version: SNAPSHOT_VERSION,
+        height: blockchain.tip().index,
+        genesis_hash: genesis_hash(blockchain).to_string(),
+        tip_hash: blockchain.tip().hash.clone(),
+        payload_sha256: digest(&payload),
+    };
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(SNAPSHOT_BLOCKS_FILE), payload)?;
+    fs::write(
+        dir.join(SNAPSHOT_MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    Ok(manifest)
+}
+
+/// Reads the snapshot in `dir`, checking the blocks against the manifest's digest
+/// before decoding them and validating the whole chain. With `expected_genesis`,
+/// a chain starting from any other genesis is refused.
+pub fn import(dir: &Path, expected_genesis: Option<&str>) -> Result<Blockchain, SnapshotError> {
+    let manifest: SnapshotManifest =
+        serde_json::from_slice(&fs::read(dir.join(SNAPSHOT_MANIFEST_FILE))?)?;
+    if manifest.version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(manifest.version));
+    }
+    let payload = fs::read(dir.join(SNAPSHOT_BLOCKS_FILE))?;
+    let actual = digest(&payload);
+    if actual != manifest.payload_sha256 {
+        return Err(SnapshotError::DigestMismatch {
+            manifest: manifest.payload_sha256,
+            actual,
+        });
+    }
+
+    let blockchain = Blockchain::from_bytes(&payload)?;
+    let genesis = genesis_hash(&blockchain);
+    if let Some(expected) = expected_genesis {
+        if genesis != expected {
+            return Err(SnapshotError::GenesisMismatch {
+                expected: expected.to_string(),
+                actual: genesis.to_string(),
+            });
+        }
+    }
+    blockchain.validate()?;
+
+    let tip = blockchain.tip();
+    for (field, listed, found) in [
+        ("genesis_hash", manifest.genesis_hash, genesis.to_string()),
+        ("tip_hash", manifest.tip_hash, tip.hash.clone()),
+        ("height", manifest.height.to_string(), tip.index.to_string()),
+    ] {
+        if listed != found {
+            return Err(SnapshotError::ManifestMismatch {
+                field,
+                manifest: listed,
+                chain: found,
+            });
+        }
+    }
+    Ok(blockchain)
+}
+
+fn genesis_hash(blockchain: &Blockchain) -> &str {
+    &blockchain
+        .get_block_by_index(0)
+        .expect("a chain always has a genesis block")
+        .hash
+}
+
+fn digest(payload: &[u8]) -> String {
+    hex::encode(Sha256::digest(payload))
+}
@@ -0,0 +1,249 @@
+use crate::block::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Mining effort behind one block. Blocks that weren't mined locally by proof of
+/// work (sealed by an authority, or saved before stats were kept) have no attempts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockStats {
+    pub index: u32,
+    pub hash: String,
+    pub difficulty: usize,
+    pub attempts: Option<u64>, // Nonces tried before the hash met the difficulty
+    pub mining_time: Option<Duration>,
+    pub interval_secs: Option<u64>, // Time since the previous block's timestamp
+}
+
+/// Average effort of the blocks mined at one difficulty
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyStats {
+    pub blocks: usize, // Blocks at this difficulty with recorded attempts
+    pub average_attempts: f64,
+}
+
+/// Summary of the mining effort behind a chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainStats {
+    pub blocks: Vec<BlockStats>,
+    pub total_attempts: u64,
+    pub total_mining_time: Duration,
+    pub average_block_interval_secs: Option<f64>, // `None` with only a genesis block
+    pub by_difficulty: BTreeMap<usize, DifficultyStats>,
+    pub hash_rate: f64, // Attempts per second of mining time
+}
+
+impl ChainStats {
+    /// Gathers the statistics of `blocks`, genesis first
+    pub fn from_blocks(blocks: &[Block]) -> ChainStats {
+        let mut stats = Vec::with_capacity(blocks.len());
+        let mut total_attempts = 0;
+        let mut total_mining_time = Duration::ZERO;
+        let mut attempts_by_difficulty: BTreeMap<usize, (usize, u64)> = BTreeMap::new();
+
+        for (position, block) in blocks.iter().enumerate() {
+            let mining = block.mining_stats.as_ref();
+            if let Some(mining) = mining {
+                total_attempts += mining.hashes_tried;
+                total_mining_time += mining.elapsed;
+                let entry = attempts_by_difficulty
+                    .entry(block.difficulty)
+                    .or_insert((0, 0));
+                entry.0 += 1;
+                entry.1 += mining.hashes_tried;
+            }
+            let interval_secs = position
+                .checked_sub(1)
+                .map(|parent| block.timestamp.saturating_sub(blocks[parent].timestamp));
+
+            stats.push(BlockStats {
+                index: block.index,
+                hash: block.hash.clone(),
+                difficulty: block.difficulty,
+                attempts: mining.map(|mining| mining.hashes_tried),
+                mining_time: mining.map(|mining| mining.elapsed),
+                interval_secs,
+            });
+        }
+
+        let average_block_interval_secs = match (blocks.first(), blocks.last()) {
+            (Some(first), Some(last)) if blocks.len() > 1 => Some(
+                last.timestamp.saturating_sub(first.timestamp) as f64 / (blocks.len() - 1) as f64,
+            ),
+            _ => None,
+        };
+        let by_difficulty = attempts_by_difficulty
+            .into_iter()
+            .map(|(difficulty, (count, attempts))| {
+                let average_attempts = attempts as f64 / count as f64;
+                (
+                    difficulty,
+                    DifficultyStats {
+                        blocks: count,
+                        average_attempts,
+                    },
+                )
+            })
+            .collect();
+        let seconds = total_mining_time.as_secs_f64();
+        let hash_rate = if seconds > 0.0 {
+            total_attempts as f64 / seconds
+        } else {
+            0.0
+        };
+
+        ChainStats {
+            blocks: stats,
+            total_attempts,
+            total_mining_time,
+            average_block_interval_secs,
+            by_difficulty,
+            hash_rate,
+        }
+    }
+}
+
+impl fmt::Display for ChainStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>5}  {:>10}  {:>10}  {:>10}  {:>8}  Hash",
+            "Block", "Difficulty", "Attempts", "Time (ms)", "Interval"
+        )?;
+        for block in &self.blocks {
+            let attempts = block
+                .attempts
+                .map_or_else(|| "-".to_string(), |attempts| attempts.to_string());
+            let time = block.mining_time.map_or_else(
+                || "-".to_string(),
+                |time| format!("{:.1}", time.as_secs_f64() * 1000.0),
+            );
+            let interval = block
+                .interval_secs
+                .map_or_else(|| "-".to_string(), |secs| format!("{}s", secs));
+            writeln!(
+                f,
+                "{:>5}  {:>10}  {:>10}  {:>10}  {:>8}  {}",
+                block.index,
+                block.difficulty,
+                attempts,
+                time,
+                interval,
+                &block.hash[..16.min(block.hash.len())]
+            )?;
+        }
+
+        writeln!(f, "Total Attempts: {}", self.total_attempts)?;
+        writeln!(
+            f,
+            "Total Mining Time: {:.1} ms",
+            self.total_mining_time.as_secs_f64() * 1000.0
+        )?;
+        if let Some(interval) = self.average_block_interval_secs {
+            writeln!(f, "Average Block Interval: {:.1} s", interval)?;
+        }
+        for (difficulty, stats) in &self.by_difficulty {
+            writeln!(
+                f,
+                "Average Attempts at Difficulty {}: {:.0} ({} blocks)",
+                difficulty, stats.average_attempts, stats.blocks
+            )?;
+        }
+        write!(f, "Estimated Hash Rate: {:.0} H/s", self.hash_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+
+    /// Blocks mined at difficulties 1, 2, 3, 1, 2, 3, ten seconds apart
+    fn mined_blocks() -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        for (position, difficulty) in [1, 2, 3, 1, 2, 3].into_iter().enumerate() {
+            let previous_hash = blocks
+                .last()
+                .map_or(String::new(), |block| block.hash.clone());
+            let transactions = vec![Transaction::coinbase("Miner", 1.0, position as u32)];
+            let mut block = Block::new(position as u32, previous_hash, transactions);
+            block.timestamp = 1_700_000_000 + 10 * position as u64;
+            block.mine_block_with_difficulty(difficulty).unwrap();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[test]
+    fn stats_are_populated_and_consistent() {
+        let blocks = mined_blocks();
+        let stats = ChainStats::from_blocks(&blocks);
+
+        assert_eq!(stats.blocks.len(), 6);
+        for (block, block_stats) in blocks.iter().zip(&stats.blocks) {
+            assert_eq!(block_stats.hash, block.hash);
+            assert_eq!(block_stats.difficulty, block.difficulty);
+            assert!(block_stats.attempts.unwrap() > 0);
+            assert_eq!(block_stats.attempts.unwrap(), block.nonce + 1);
+            assert!(block_stats.mining_time.is_some());
+        }
+        let attempts: u64 = stats.blocks.iter().filter_map(|block| block.attempts).sum();
+        assert_eq!(stats.total_attempts, attempts);
+        let time: Duration = stats
+            .blocks
+            .iter()
+            .filter_map(|block| block.mining_time)
+            .sum();
+        assert_eq!(stats.total_mining_time, time);
+
+        assert_eq!(stats.blocks[0].interval_secs, None);
+        assert!(stats.blocks[1..]
+            .iter()
+            .all(|block| block.interval_secs == Some(10)));
+        assert_eq!(stats.average_block_interval_secs, Some(10.0));
+
+        assert_eq!(stats.by_difficulty.len(), 3);
+        for (difficulty, level) in &stats.by_difficulty {
+            let at_level: Vec<u64> = stats
+                .blocks
+                .iter()
+                .filter(|block| block.difficulty == *difficulty)
+                .filter_map(|block| block.attempts)
+                .collect();
+            assert_eq!(level.blocks, 2);
+            assert_eq!(
+                level.average_attempts,
+                at_level.iter().sum::<u64>() as f64 / 2.0
+            );
+        }
+        assert!(stats.hash_rate > 0.0);
+    }
+
+    #[test]
+    fn blocks_without_mining_stats_are_skipped_in_totals() {
+        let mut blocks = mined_blocks();
+        blocks[2].mining_stats = None;
+        let stats = ChainStats::from_blocks(&blocks);
+        assert_eq!(stats.blocks[2].attempts, None);
+        assert_eq!(stats.by_difficulty[&3].blocks, 1);
+        assert_eq!(
+            stats.total_attempts,
+            blocks
+                .iter()
+                .filter_map(|block| block.mining_stats.as_ref())
+                .map(|mining| mining.hashes_tried)
+                .sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn table_lists_every_block() {
+        let blocks = mined_blocks();
+        let table = ChainStats::from_blocks(&blocks).to_string();
+        assert!(blocks.iter().all(|block| table.contains(&block.hash[..16])));
+        assert!(table.contains("Total Attempts"));
+        assert!(table.contains("Average Attempts at Difficulty 3"));
+        assert!(table.contains("Estimated Hash Rate"));
+    }
+}
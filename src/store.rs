@@ -0,0 +1,262 @@
+use crate::block::Block;
+use crate::blockchain::{Blockchain, BlockchainError, LoadError};
+use crate::difficulty::DifficultyConfig;
+use crate::encoding::{from_bytes, Decode, DecodeError, Decoder, Encode, Encoder};
+use crate::limits::BlockLimits;
+use crate::reward::RewardSchedule;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the append-only block log inside a store directory
+pub const BLOCK_LOG_FILE: &str = "blocks.dat";
+
+/// Name of the file recording the tip hash and height
+pub const HEAD_FILE: &str = "HEAD";
+
+/// First bytes of the block log's header record
+pub const BLOCK_LOG_MAGIC: [u8; 4] = *b"BSRL";
+
+/// Version byte following the magic in the header record
+pub const BLOCK_LOG_VERSION: u8 = 1;
+
+/// Bytes before each record's payload: its length and checksum
+const RECORD_HEADER_LEN: usize = 8;
+
+/// Tip of the stored chain, as recorded in the `HEAD` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Head {
+    pub tip: String,
+    pub height: u32, // Index of the tip block
+}
+
+/// Directory holding a chain as an append-only log of blocks, so accepting a block
+/// writes one record instead of the whole chain.
+///
+/// The log starts with a header record (retargeting, reward and size settings) followed by
+/// one record per stored block, parents before children. Each record is its payload
+/// length, the first four bytes of the payload's SHA-256 and the payload itself.
+#[derive(Debug)]
+pub struct ChainStore {
+    dir: PathBuf,
+    log: File,
+}
+
+impl ChainStore {
+    /// Opens the store in `dir`, creating the directory and an empty log if needed
+    pub fn open(dir: &Path) -> io::Result<ChainStore> {
+        fs::create_dir_all(dir)?;
+        let log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join(BLOCK_LOG_FILE))?;
+        Ok(ChainStore {
+            dir: dir.to_path_buf(),
+            log,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns true if nothing has been written to the log yet
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.log.metadata()?.len() == 0)
+    }
+
+    /// Replays the log into a chain, validating every block, and returns it with
+    /// this store attached. A record cut short by a crash mid-write is truncated
+    /// away, so the chain ends at the last complete block. A complete record that
+    /// fails its checksum is an error and leaves the log untouched.
+    pub fn load(mut self) -> Result<Blockchain, LoadError> {
+        let mut records = self.read_records()?.into_iter();
+        let header = records.next().ok_or(DecodeError::UnexpectedEnd)?;
+        let (difficulty_config, reward_schedule, block_limits) = decode_header(&header)?;
+        let genesis_block: Block = from_bytes(&records.next().ok_or(DecodeError::UnexpectedEnd)?)?;
+
+        let mut blockchain = Blockchain::from_genesis_block(genesis_block, difficulty_config);
+        blockchain.reward_schedule = reward_schedule;
+        blockchain.block_limits = block_limits;
+        for record in records {
+            match blockchain.add_block(from_bytes(&record)?) {
+                // A block appended twice, e.g. by two handles on the same store
+                Ok(_) | Err(BlockchainError::DuplicateBlock { .. }) => {}
+                Err(e) => return Err(LoadError::Rejected(e)),
+            }
+        }
+        blockchain.validate()?;
+
+        self.write_head(blockchain.tip())?;
+        blockchain.set_store(self);
+        Ok(blockchain)
+    }
+
+    /// Reads every record, truncating a torn one at the end of the log
+    fn read_records(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut bytes = Vec::new();
+        self.log.seek(SeekFrom::Start(0))?;
+        self.log.read_to_end(&mut bytes)?;
+
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            match next_record(&bytes[offset..]) {
+                Record::Intact(payload) => {
+                    offset += RECORD_HEADER_LEN + payload.len();
+                    records.push(payload.to_vec());
+                }
+                Record::Torn => {
+                    self.log.set_len(offset as u64)?;
+                    self.log.sync_all()?;
+                    break;
+                }
+                Record::Corrupt => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("block log record at byte {} fails its checksum", offset),
+                    ))
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// Starts an empty log with the chain's settings and every block it knows
+    pub(crate) fn write_chain(&mut self, blockchain: &Blockchain) -> io::Result<()> {
+        if !self.is_empty()? {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "block log already holds a chain",
+            ));
+        }
+        let mut encoder = Encoder::new();
+        encoder.raw(&BLOCK_LOG_MAGIC);
+        encoder.u8(BLOCK_LOG_VERSION);
+        blockchain.difficulty_config.encode(&mut encoder);
+        blockchain.reward_schedule.encode(&mut encoder);
+        blockchain.block_limits.encode(&mut encoder);
+        self.append_record(&encoder.into_bytes())?;
+
+        for block in blockchain.blocks_by_height() {
+            self.append(block)?;
+        }
+        self.write_head(blockchain.tip())
+    }
+
+    /// Appends `block` to the log and waits for it to reach the disk
+    pub fn append(&mut self, block: &Block) -> io::Result<()> {
+        let mut encoder = Encoder::new();
+        block.encode(&mut encoder);
+        self.append_record(&encoder.into_bytes())
+    }
+
+    fn append_record(&mut self, payload: &[u8]) -> io::Result<()> {
+        let length = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record is too large"))?;
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&length.to_be_bytes());
+        record.extend_from_slice(&checksum(payload));
+        record.extend_from_slice(payload);
+        self.log.write_all(&record)?;
+        self.log.sync_data()
+    }
+
+    /// Records `tip` in the `HEAD` file, replacing it in one rename
+    pub fn write_head(&self, tip: &Block) -> io::Result<()> {
+        let temp = self.dir.join(format!("{}.tmp", HEAD_FILE));
+        let mut file = File::create(&temp)?;
+        writeln!(file, "{}\n{}", tip.hash, tip.index)?;
+        file.sync_all()?;
+        fs::rename(temp, self.dir.join(HEAD_FILE))
+    }
+
+    /// Tip recorded in the `HEAD` file, or `None` if there isn't one yet. It can lag
+    /// the log by a block after a crash; `load` trusts the log and rewrites it.
+    pub fn head(&self) -> io::Result<Option<Head>> {
+        let contents = match fs::read_to_string(self.dir.join(HEAD_FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut lines = contents.lines();
+        match (lines.next(), lines.next().and_then(|h| h.parse().ok())) {
+            (Some(tip), Some(height)) => Ok(Some(Head {
+                tip: tip.to_string(),
+                height,
+            })),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "HEAD file is malformed",
+            )),
+        }
+    }
+}
+
+/// What the start of the unread log holds
+#[derive(Debug, PartialEq)]
+enum Record<'a> {
+    /// A whole record whose payload matches its checksum
+    Intact(&'a [u8]),
+    /// A record running past the end of the log, as a crash mid-write leaves it
+    Torn,
+    /// A whole record whose payload doesn't match its checksum
+    Corrupt,
+}
+
+fn next_record(bytes: &[u8]) -> Record<'_> {
+    let Some(header) = bytes.get(..RECORD_HEADER_LEN) else {
+        return Record::Torn;
+    };
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    match bytes.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN.saturating_add(length)) {
+        None => Record::Torn,
+        Some(payload) if header[4..] == checksum(payload) => Record::Intact(payload),
+        Some(_) => Record::Corrupt,
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(payload);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+fn decode_header(
+    bytes: &[u8],
+) -> Result<(DifficultyConfig, RewardSchedule, BlockLimits), DecodeError> {
+    let mut decoder = Decoder::new(bytes);
+    if decoder.take(BLOCK_LOG_MAGIC.len())? != BLOCK_LOG_MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let version = decoder.u8()?;
+    if version != BLOCK_LOG_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let difficulty_config = DifficultyConfig::decode(&mut decoder)?;
+    let reward_schedule = RewardSchedule::decode(&mut decoder)?;
+    let block_limits = BlockLimits::decode(&mut decoder)?;
+    decoder.finish()?;
+    Ok((difficulty_config, reward_schedule, block_limits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_records_are_torn_and_bad_checksums_corrupt() {
+        let payload = b"block";
+        let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&checksum(payload));
+        bytes.extend_from_slice(payload);
+        assert_eq!(next_record(&bytes), Record::Intact(&payload[..]));
+        assert_eq!(next_record(&bytes[..bytes.len() - 1]), Record::Torn);
+        assert_eq!(next_record(&bytes[..3]), Record::Torn);
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(next_record(&bytes), Record::Corrupt);
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt;
+
+/// Hex digits in a 256-bit hash
+const HASH_DIGITS: usize = 64;
+
+/// A 256-bit proof-of-work target. A block's hash, read as a big-endian integer,
+/// must not exceed it, so lower targets are harder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Target([u8; 32]); // Big-endian, so byte order is numeric order
+
+impl Target {
+    /// The easiest target: every hash meets it
+    pub const MAX: Target = Target([u8::MAX; 32]);
+
+    /// The hardest target: only the all-zero hash meets it
+    pub const ZERO: Target = Target([0; 32]);
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Target {
+        Target(bytes)
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Expands compact `bits` as Bitcoin does: the high byte is the target's length
+    /// in bytes and the low three bytes are its leading bytes, so the target is
+    /// `mantissa * 256^(length - 3)`. Bit 23 is a sign bit; negative targets, and
+    /// targets too wide for 256 bits, give `None`.
+    pub fn from_compact(bits: u32) -> Option<Target> {
+        let length = (bits >> 24) as usize;
+        let mantissa = bits & 0x007f_ffff;
+        let mut bytes = [0u8; 32];
+        for (position, &byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+            // Bytes below the least significant are shifted out
+            let Some(significance) = length.checked_sub(position + 1) else {
+                continue;
+            };
+            if significance >= bytes.len() {
+                if byte != 0 {
+                    return None;
+                }
+                continue;
+            }
+            bytes[31 - significance] = byte;
+        }
+
+        let target = Target(bytes);
+        if bits & 0x0080_0000 != 0 && target != Target::ZERO {
+            return None;
+        }
+        Some(target)
+    }
+
+    /// Compact form of the target, keeping its three leading bytes. Lower bytes are
+    /// dropped, so the compact target may be slightly lower (harder) than this one.
+    pub fn to_compact(&self) -> u32 {
+        let mut length = self.0.len() - self.0.iter().take_while(|&&byte| byte == 0).count();
+        let start = self.0.len() - length;
+        let mut mantissa = (start..start + 3).fold(0u32, |mantissa, position| {
+            mantissa << 8 | self.0.get(position).copied().unwrap_or(0) as u32
+        });
+        // A set high bit would read back as the sign, so move it into another byte
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            length += 1;
+        }
+        (length as u32) << 24 | mantissa
+    }
+
+    /// Returns true if `digest`, read as a big-endian integer, is at most the target
+    pub fn is_met_by(&self, digest: &[u8]) -> bool {
+        digest.len() == self.0.len() && digest <= &self.0[..]
+    }
+
+    /// `target * numerator / denominator`, saturating at `Target::MAX`
+    pub fn scale(&self, numerator: u64, denominator: u64) -> Target {
+        // 32 bytes times a u64 fits in 40 bytes
+        let mut product = [0u8; 40];
+        let mut carry: u128 = 0;
+        for position in (0..product.len()).rev() {
+            let byte = position
+                .checked_sub(8)
+                .map_or(0, |position| self.0[position]);
+            carry += byte as u128 * numerator as u128;
+            product[position] = carry as u8;
+            carry >>= 8;
+        }
+
+        let denominator = denominator.max(1) as u128;
+        let mut quotient = [0u8; 40];
+        let mut remainder: u128 = 0;
+        for (digit, &byte) in quotient.iter_mut().zip(&product) {
+            let value = remainder << 8 | byte as u128;
+            *digit = (value / denominator) as u8;
+            remainder = value % denominator;
+        }
+
+        let (overflow, bytes) = quotient.split_at(8);
+        if overflow.iter().any(|&byte| byte != 0) {
+            return Target::MAX;
+        }
+        Target(
+            bytes
+                .try_into()
+                .expect("32 bytes remain after the overflow"),
+        )
+    }
+
+    /// Expected number of hashes to find one at or below the target,
+    /// `2^256 / (target + 1)`
+    pub fn work(&self) -> f64 {
+        2f64.powi(256) / (self.to_f64() + 1.0)
+    }
+
+    /// Leading zero hex digits that every hash meeting the target has
+    pub fn leading_zero_digits(&self) -> usize {
+        let mut digits = 0;
+        for &byte in &self.0 {
+            match byte {
+                0 => digits += 2,
+                0x01..=0x0f => return digits + 1,
+                _ => return digits,
+            }
+        }
+        digits
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0
+            .iter()
+            .fold(0.0, |value, &byte| value * 256.0 + byte as f64)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+/// The target met by exactly the hashes with `difficulty` leading zero hex digits,
+/// `16^(64 - difficulty) - 1`
+pub fn difficulty_to_target(difficulty: usize) -> Target {
+    let mut bytes = [u8::MAX; 32];
+    let zeros = difficulty.min(HASH_DIGITS);
+    bytes[..zeros / 2].fill(0);
+    if zeros % 2 == 1 {
+        bytes[zeros / 2] = 0x0f;
+    }
+    Target(bytes)
+}
+
+/// Compact form of `difficulty_to_target`
+pub fn difficulty_to_bits(difficulty: usize) -> u32 {
+    difficulty_to_target(difficulty).to_compact()
+}
+
+/// Difficulty of `target` in leading zero hex digits, with fractions for targets
+/// between two whole difficulties. Only meant for display; consensus compares targets.
+pub fn target_to_difficulty_f64(target: &Target) -> f64 {
+    target.work().log2() / 4.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(hex: &str) -> Target {
+        let mut bytes = [0u8; 32];
+        let decoded = hex::decode(hex).unwrap();
+        bytes[32 - decoded.len()..].copy_from_slice(&decoded);
+        Target(bytes)
+    }
+
+    #[test]
+    fn compact_encoding_matches_known_vectors() {
+        // From Bitcoin's arith_uint256 tests: (bits, target, bits written back)
+        for (bits, expanded, normalized) in [
+            (0x0000_0000, "00", 0x0000_0000),
+            (0x0012_3456, "00", 0x0000_0000),
+            (0x0100_3456, "00", 0x0000_0000),
+            (0x0480_0000, "00", 0x0000_0000),
+            (0x0112_3456, "12", 0x0112_0000),
+            (0x0212_3456, "1234", 0x0212_3400),
+            (0x0312_3456, "123456", 0x0312_3456),
+            (0x0412_3456, "12345600", 0x0412_3456),
+            (0x0500_9234, "92340000", 0x0500_9234),
+            (
+                0x1d00_ffff,
+                "ffff0000000000000000000000000000000000000000000000000000",
+                0x1d00_ffff,
+            ),
+            (
+                0x2012_3456,
+                "1234560000000000000000000000000000000000000000000000000000000000",
+                0x2012_3456,
+            ),
+        ] {
+            let decoded = Target::from_compact(bits).unwrap();
+            assert_eq!(decoded, target(expanded), "bits {:#010x}", bits);
+            assert_eq!(decoded.to_compact(), normalized, "bits {:#010x}", bits);
+        }
+        assert_eq!(target("80").to_compact(), 0x0200_8000);
+    }
+
+    #[test]
+    fn negative_and_oversized_compact_targets_are_refused() {
+        assert_eq!(Target::from_compact(0x0492_3456), None);
+        assert_eq!(Target::from_compact(0x01fe_dcba), None);
+        assert_eq!(Target::from_compact(0xff12_3456), None);
+        assert_eq!(Target::from_compact(0x2112_3456), None);
+        assert!(Target::from_compact(0x2100_ffff).is_some());
+    }
+
+    #[test]
+    fn hashes_at_the_target_pass_and_above_it_fail() {
+        let target = Target::from_compact(0x1f00_ffff).unwrap();
+        let mut digest = target.to_be_bytes();
+        assert!(target.is_met_by(&digest));
+        digest[31] = 1;
+        assert!(!target.is_met_by(&digest));
+        digest[31] = 0;
+        digest[3] = 1; // Just below the target
+        assert!(target.is_met_by(&digest));
+        assert!(!target.is_met_by(&digest[1..]));
+    }
+
+    #[test]
+    fn whole_difficulties_convert_exactly() {
+        for difficulty in 0..=8 {
+            let target = difficulty_to_target(difficulty);
+            assert_eq!(target_to_difficulty_f64(&target), difficulty as f64);
+            assert_eq!(target.leading_zero_digits(), difficulty);
+            assert_eq!(target.work(), 16f64.powi(difficulty as i32));
+        }
+        assert_eq!(difficulty_to_target(0), Target::MAX);
+        assert_eq!(difficulty_to_target(64), Target::ZERO);
+        // Eight zero digits is Bitcoin's minimum difficulty target
+        assert_eq!(difficulty_to_bits(8), 0x1d00_ffff);
+        assert_eq!(difficulty_to_bits(1), 0x200f_ffff);
+    }
+
+    #[test]
+    fn fractional_targets_show_fractional_difficulty() {
+        let halfway = difficulty_to_target(2).scale(1, 4);
+        let difficulty = target_to_difficulty_f64(&halfway);
+        assert!((difficulty - 2.5).abs() < 1e-9, "{}", difficulty);
+        assert_eq!(halfway.leading_zero_digits(), 2);
+    }
+
+    #[test]
+    fn scaling_multiplies_then_divides() {
+        let small = target("0100");
+        assert_eq!(small.scale(3, 2), target("0180"));
+        assert_eq!(small.scale(1, 512), Target::ZERO);
+        assert_eq!(Target::MAX.scale(2, 1), Target::MAX);
+        assert_eq!(
+            Target::MAX.scale(1, 2),
+            target(&format!("7f{}", "ff".repeat(31)))
+        );
+    }
+}
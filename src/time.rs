@@ -0,0 +1,88 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt;
+
+/// Reasons a timestamp can't be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeError {
+    /// The input isn't an RFC 3339 date and time
+    Invalid { input: String, message: String },
+    /// The time is before the UNIX epoch, which block timestamps can't express
+    BeforeEpoch { input: String },
+}
+
+impl fmt::Display for TimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeError::Invalid { input, message } => {
+                write!(f, "{:?} is not an RFC 3339 time: {}", input, message)
+            }
+            TimeError::BeforeEpoch { input } => {
+                write!(f, "{:?} is before 1970-01-01T00:00:00Z", input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimeError {}
+
+/// UTC time like `2023-11-14T22:13:20Z`. Seconds too large for a calendar date are
+/// shown as `invalid timestamp <secs>` rather than a wrong date.
+pub fn format_timestamp(secs: u64) -> String {
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
+        .map_or_else(
+            || format!("invalid timestamp {}", secs),
+            |date_time| date_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        )
+}
+
+/// Seconds since the UNIX epoch of an RFC 3339 time such as `2023-11-14T22:13:20Z`
+/// or `2023-11-15T00:13:20+02:00`. Fractions of a second are dropped.
+pub fn parse_timestamp(s: &str) -> Result<u64, TimeError> {
+    let date_time = DateTime::parse_from_rfc3339(s).map_err(|e| TimeError::Invalid {
+        input: s.to_string(),
+        message: e.to_string(),
+    })?;
+    u64::try_from(date_time.timestamp()).map_err(|_| TimeError::BeforeEpoch {
+        input: s.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_round_trip() {
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        for secs in [0, 1_700_000_000, 4_102_444_800] {
+            assert_eq!(parse_timestamp(&format_timestamp(secs)), Ok(secs));
+        }
+        assert_eq!(
+            parse_timestamp("2023-11-15T00:13:20.75+02:00"),
+            Ok(1_700_000_000)
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_errors_or_placeholders() {
+        assert_eq!(
+            format_timestamp(u64::MAX),
+            format!("invalid timestamp {}", u64::MAX)
+        );
+        assert_eq!(
+            format_timestamp(i64::MAX as u64),
+            format!("invalid timestamp {}", i64::MAX)
+        );
+        assert!(matches!(
+            parse_timestamp("1969-12-31T23:59:59Z"),
+            Err(TimeError::BeforeEpoch { .. })
+        ));
+        assert!(matches!(
+            parse_timestamp("2023-11-14 22:13"),
+            Err(TimeError::Invalid { .. })
+        ));
+    }
+}
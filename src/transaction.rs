@@ -0,0 +1,847 @@
+use crate::address::{self, Address, AddressError};
+use crate::blockchain::{Blockchain, LoadError};
+use crate::clock::{Clock, SystemClock};
+use crate::encoding::{Encode, Encoder};
+use crate::events::{ChainEvent, EventBus};
+use crate::utxo::{TxInput, TxOutput};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+/// Sender used for block reward transactions, which create new coins
+pub const COINBASE_SENDER: &str = "COINBASE";
+
+/// A transfer of `amount` from `sender` to `recipient`, paying `fee` to the miner.
+///
+/// Under the UTXO model the transaction also lists the outputs it spends and the
+/// outputs it creates; see `crate::utxo`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub sender: Address,
+    pub recipient: Address,
+    pub amount: f64,
+    #[serde(default)]
+    pub fee: f64, // Paid by the sender to the block's miner
+    #[serde(default)]
+    pub nonce: u64, // Per-sender sequence number; the block height for coinbases
+    pub timestamp: u64, // When the transaction was created (UNIX timestamp)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<TransactionSignature>, // Required when the sender is a wallet address
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<TxInput>, // Unspent outputs consumed, all owned by the sender
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<TxOutput>, // New outputs: the payment plus any change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>, // Latest block timestamp that may include the transaction
+}
+
+/// The sender's public key and their signature over the transaction id, both hex-encoded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Reasons a transaction's signature is not accepted
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    /// The sender is a wallet address but the transaction is unsigned
+    Missing,
+    /// The signing key does not belong to the sender's address
+    WrongKey,
+    /// The signature or key is malformed, or doesn't match the transaction
+    Invalid,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::Missing => write!(f, "transaction from a wallet address is unsigned"),
+            SignatureError::WrongKey => write!(f, "transaction is signed by another key"),
+            SignatureError::Invalid => write!(f, "transaction signature is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+impl Transaction {
+    /// Creates a new transaction stamped with the current time
+    pub fn new(sender: &str, recipient: &str, amount: f64) -> Transaction {
+        Transaction::new_with_clock(sender, recipient, amount, &SystemClock)
+    }
+
+    /// Creates a new transaction stamped with `clock`'s time
+    pub fn new_with_clock(
+        sender: &str,
+        recipient: &str,
+        amount: f64,
+        clock: &dyn Clock,
+    ) -> Transaction {
+        let timestamp = clock.now_secs();
+        Transaction {
+            sender: Address::unchecked(sender),
+            recipient: Address::unchecked(recipient),
+            amount,
+            fee: 0.0,
+            nonce: 0,
+            timestamp,
+            signature: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            expires_at: None,
+        }
+    }
+
+    /// Like `new`, but rejects wallet addresses with a bad checksum
+    pub fn try_new(
+        sender: &str,
+        recipient: &str,
+        amount: f64,
+    ) -> Result<Transaction, AddressError> {
+        Address::parse_or_name(sender)?;
+        Address::parse_or_name(recipient)?;
+        Ok(Transaction::new(sender, recipient, amount))
+    }
+
+    /// Sets the sender's sequence number, so otherwise identical payments get distinct ids
+    pub fn with_nonce(mut self, nonce: u64) -> Transaction {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Makes the transaction invalid in blocks stamped after `expires_at`
+    pub fn with_expiry(mut self, expires_at: u64) -> Transaction {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Returns true if a block stamped `timestamp` may no longer include the transaction
+    pub fn is_expired_at(&self, timestamp: u64) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at < timestamp)
+    }
+
+    /// Sets the fee offered to the miner
+    pub fn with_fee(mut self, fee: f64) -> Transaction {
+        self.fee = fee;
+        self
+    }
+
+    /// Total debited from the sender: amount plus fee
+    pub fn total_cost(&self) -> f64 {
+        self.amount + self.fee
+    }
+
+    /// Creates the reward transaction for the block at `height`, paying `amount` to `recipient`
+    pub fn coinbase(recipient: &str, amount: f64, height: u32) -> Transaction {
+        Transaction::new(COINBASE_SENDER, recipient, amount).with_nonce(height as u64)
+    }
+
+    /// Returns true for block reward transactions
+    pub fn is_coinbase(&self) -> bool {
+        self.sender == COINBASE_SENDER
+    }
+
+    /// Returns true if the transaction spends and creates outputs under the UTXO model
+    pub fn is_utxo(&self) -> bool {
+        !self.inputs.is_empty() || !self.outputs.is_empty()
+    }
+
+    /// Calculates the SHA-256 hash of the transaction, used as its id.
+    /// Signatures are left out, since they sign this hash.
+    pub fn calculate_hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.id_bytes()))
+    }
+
+    /// Canonical encoding of the hashed fields: fixed-width big-endian numbers and
+    /// length-prefixed strings and lists, so distinct transactions never share bytes
+    pub fn id_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.str(&self.sender);
+        encoder.str(&self.recipient);
+        encoder.f64(self.amount);
+        encoder.f64(self.fee);
+        encoder.u64(self.nonce);
+        encoder.u64(self.timestamp);
+        encoder.u32(self.inputs.len() as u32);
+        for input in &self.inputs {
+            encoder.str(&input.prev_tx_id);
+            encoder.u32(input.output_index);
+        }
+        self.outputs.encode(&mut encoder);
+        self.expires_at.encode(&mut encoder);
+        encoder.into_bytes()
+    }
+
+    /// Checks the signature against the transaction id. Transactions from a wallet
+    /// address must be signed by its key; plain names and coinbases may be unsigned.
+    pub fn verify_signature(&self) -> Result<(), SignatureError> {
+        verify_signed_by(
+            &self.sender,
+            self.signature.as_ref(),
+            self.calculate_hash().as_bytes(),
+        )
+    }
+}
+
+/// Checks that `signature` signs `message` with the key behind `signer`. Wallet
+/// addresses must sign; plain names may leave `signature` empty.
+pub fn verify_signed_by(
+    signer: &str,
+    signature: Option<&TransactionSignature>,
+    message: &[u8],
+) -> Result<(), SignatureError> {
+    let signature = match signature {
+        Some(signature) => signature,
+        None if address::is_wallet_address(signer) => return Err(SignatureError::Missing),
+        None => return Ok(()),
+    };
+
+    let public_key: [u8; 32] = hex::decode(&signature.public_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(SignatureError::Invalid)?;
+    let public_key = VerifyingKey::from_bytes(&public_key).map_err(|_| SignatureError::Invalid)?;
+    if Address::from_public_key(&public_key) != signer {
+        return Err(SignatureError::WrongKey);
+    }
+
+    let bytes: [u8; 64] = hex::decode(&signature.signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(SignatureError::Invalid)?;
+    public_key
+        .verify(message, &Signature::from_bytes(&bytes))
+        .map_err(|_| SignatureError::Invalid)
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} sent {} to {}",
+            self.sender, self.amount, self.recipient
+        )
+    }
+}
+
+/// Reasons a transaction can be refused by the mempool
+#[derive(Debug, Clone, PartialEq)]
+pub enum MempoolError {
+    /// The amount is zero, negative or not a number
+    InvalidAmount(f64),
+    /// The fee is negative or not a number
+    InvalidFee(f64),
+    /// The pool is full and the fee doesn't beat the cheapest pending transaction
+    FeeTooLow { fee: f64, min_fee: f64 },
+    /// A transaction with the same id is already pending
+    DuplicateTransaction(String),
+    /// The sender or recipient is a wallet address with a bad checksum
+    InvalidAddress(AddressError),
+    /// The transaction is unsigned or badly signed
+    InvalidSignature(SignatureError),
+    /// The transaction expired before it could be mined
+    Expired { expires_at: u64, now: u64 },
+    /// The sender's confirmed balance, less what it already has pending, doesn't
+    /// cover the transaction
+    InsufficientBalance {
+        address: String,
+        available: f64,
+        required: f64,
+    },
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MempoolError::InvalidAmount(amount) => {
+                write!(f, "amount {} must be greater than zero", amount)
+            }
+            MempoolError::InvalidFee(fee) => write!(f, "fee {} must not be negative", fee),
+            MempoolError::FeeTooLow { fee, min_fee } => write!(
+                f,
+                "mempool is full: fee {} must be higher than {}",
+                fee, min_fee
+            ),
+            MempoolError::DuplicateTransaction(id) => {
+                write!(f, "transaction {} is already pending", id)
+            }
+            MempoolError::InvalidAddress(e) => write!(f, "{}", e),
+            MempoolError::InvalidSignature(e) => write!(f, "{}", e),
+            MempoolError::Expired { expires_at, now } => {
+                write!(f, "transaction expired at {} (now {})", expires_at, now)
+            }
+            MempoolError::InsufficientBalance {
+                address,
+                available,
+                required,
+            } => write!(
+                f,
+                "insufficient balance: {} has {} available but needs {}",
+                address, available, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+/// Pool of transactions waiting to be mined, in arrival order, holding at most
+/// `max_transactions` entries
+#[derive(Debug)]
+pub struct Mempool {
+    transactions: Vec<Transaction>,
+    ids: HashSet<String>, // Ids of the pending transactions
+    max_transactions: usize,
+    clock: Arc<dyn Clock>, // Decides which transactions have expired
+    events: EventBus,      // Told about every admitted and refused transaction
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool::with_capacity(usize::MAX)
+    }
+}
+
+impl Mempool {
+    /// Creates an empty, unbounded mempool
+    pub fn new() -> Mempool {
+        Mempool::default()
+    }
+
+    /// Creates an empty mempool holding at most `max_txs` transactions
+    pub fn with_capacity(max_txs: usize) -> Mempool {
+        Mempool {
+            transactions: Vec::new(),
+            ids: HashSet::new(),
+            max_transactions: max_txs,
+            clock: Arc::new(SystemClock),
+            events: EventBus::new(),
+        }
+    }
+
+    /// Returns a receiver for every admitted and refused transaction from now on
+    pub fn subscribe(&self) -> Receiver<ChainEvent> {
+        self.events.subscribe()
+    }
+
+    /// Publishes admissions on `events` instead, e.g. `Blockchain::events`, so one
+    /// subscriber sees blocks and transactions in order. Earlier subscribers to this
+    /// mempool stop hearing from it.
+    pub fn set_events(&mut self, events: EventBus) {
+        self.events = events;
+    }
+
+    /// Replaces the clock used to expire transactions
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Queues a transaction for the next block, rejecting non-positive amounts,
+    /// negative fees, malformed addresses, missing or bad signatures, expired
+    /// transactions and transactions that are already pending.
+    ///
+    /// When the pool is full the lowest-fee transaction is evicted if the new one
+    /// pays more; otherwise the new one is rejected. Balances are only checked when
+    /// the transaction is mined, see `Blockchain::mine_pending_transactions`, unless
+    /// it's added with `add_funded_transaction`.
+    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), MempoolError> {
+        let tx_id = transaction.calculate_hash();
+        let result = self.admit(transaction);
+        self.announce(tx_id, result)
+    }
+
+    /// Like `add_transaction`, but first checks that the sender's available balance
+    /// on `blockchain` covers the amount and fee, so two transactions that each fit
+    /// can't together spend more than the sender has
+    pub fn add_funded_transaction(
+        &mut self,
+        transaction: Transaction,
+        blockchain: &Blockchain,
+    ) -> Result<(), MempoolError> {
+        let available = blockchain.get_available_balance(&transaction.sender, self);
+        if available < transaction.total_cost() {
+            let error = MempoolError::InsufficientBalance {
+                address: transaction.sender.to_string(),
+                available,
+                required: transaction.total_cost(),
+            };
+            return self.announce(transaction.calculate_hash(), Err(error));
+        }
+        self.add_transaction(transaction)
+    }
+
+    /// Publishes the outcome of admitting `tx_id` and passes it on
+    fn announce(
+        &mut self,
+        tx_id: String,
+        result: Result<(), MempoolError>,
+    ) -> Result<(), MempoolError> {
+        self.events.publish(match &result {
+            Ok(()) => ChainEvent::TransactionAccepted(tx_id),
+            Err(e) => ChainEvent::TransactionRejected {
+                tx_id,
+                reason: e.to_string(),
+            },
+        });
+        result
+    }
+
+    fn admit(&mut self, transaction: Transaction) -> Result<(), MempoolError> {
+        if !transaction.amount.is_finite() || transaction.amount <= 0.0 {
+            return Err(MempoolError::InvalidAmount(transaction.amount));
+        }
+        if !transaction.fee.is_finite() || transaction.fee < 0.0 {
+            return Err(MempoolError::InvalidFee(transaction.fee));
+        }
+        transaction
+            .sender
+            .check()
+            .and_then(|_| transaction.recipient.check())
+            .map_err(MempoolError::InvalidAddress)?;
+        transaction
+            .verify_signature()
+            .map_err(MempoolError::InvalidSignature)?;
+        let now = self.clock.now_secs();
+        if let Some(expires_at) = transaction
+            .expires_at
+            .filter(|_| transaction.is_expired_at(now))
+        {
+            return Err(MempoolError::Expired { expires_at, now });
+        }
+        let id = transaction.calculate_hash();
+        if self.ids.contains(&id) {
+            return Err(MempoolError::DuplicateTransaction(id));
+        }
+
+        if self.transactions.len() >= self.max_transactions {
+            // Evict the most recent of the cheapest transactions
+            let cheapest = self
+                .transactions
+                .iter()
+                .enumerate()
+                .rev()
+                .min_by(|(_, a), (_, b)| a.fee.total_cmp(&b.fee))
+                .map(|(position, tx)| (position, tx.fee));
+            match cheapest {
+                Some((position, min_fee)) if transaction.fee > min_fee => {
+                    let evicted = self.transactions.remove(position);
+                    self.ids.remove(&evicted.calculate_hash());
+                }
+                Some((_, min_fee)) => {
+                    return Err(MempoolError::FeeTooLow {
+                        fee: transaction.fee,
+                        min_fee,
+                    })
+                }
+                None => {
+                    return Err(MempoolError::FeeTooLow {
+                        fee: transaction.fee,
+                        min_fee: f64::INFINITY,
+                    })
+                }
+            }
+        }
+
+        self.ids.insert(id);
+        self.transactions.push(transaction);
+        Ok(())
+    }
+
+    /// Removes and returns up to `limit` transactions, highest fee first.
+    /// Transactions with equal fees keep their arrival order. Expired
+    /// transactions are evicted first and never returned.
+    pub fn get_transactions(&mut self, limit: usize) -> Vec<Transaction> {
+        self.evict_expired(self.clock.now_secs());
+        self.transactions.sort_by(|a, b| b.fee.total_cmp(&a.fee));
+        let take = limit.min(self.transactions.len());
+        let taken: Vec<Transaction> = self.transactions.drain(..take).collect();
+        for transaction in &taken {
+            self.ids.remove(&transaction.calculate_hash());
+        }
+        taken
+    }
+
+    /// Puts back transactions taken by `get_transactions` that didn't make it into a
+    /// block, without checking or announcing them again
+    pub(crate) fn requeue(&mut self, transactions: Vec<Transaction>) {
+        for transaction in transactions {
+            self.ids.insert(transaction.calculate_hash());
+            self.transactions.push(transaction);
+        }
+    }
+
+    /// Removes and returns the transactions that expired before `now`
+    pub fn evict_expired(&mut self, now: u64) -> Vec<Transaction> {
+        let (expired, pending) = self
+            .transactions
+            .drain(..)
+            .partition(|tx| tx.is_expired_at(now));
+        self.transactions = pending;
+        let expired: Vec<Transaction> = expired;
+        for transaction in &expired {
+            self.ids.remove(&transaction.calculate_hash());
+        }
+        expired
+    }
+
+    /// Writes the pending transactions to `path` as pretty-printed JSON
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.transactions)?;
+        fs::write(path, json)
+    }
+
+    /// Reads transactions written by `save_to_file` into an unbounded mempool,
+    /// applying the usual admission checks to each. Transactions that expired since
+    /// they were saved are dropped.
+    pub fn load_from_file(path: &Path) -> Result<Mempool, LoadError> {
+        let json = fs::read_to_string(path)?;
+        let transactions: Vec<Transaction> = serde_json::from_str(&json)?;
+        let mut mempool = Mempool::new();
+        let now = mempool.clock.now_secs();
+        for transaction in transactions {
+            if !transaction.is_expired_at(now) {
+                mempool.add_transaction(transaction)?;
+            }
+        }
+        Ok(mempool)
+    }
+
+    /// Returns true if a transaction with this id is pending
+    pub fn contains(&self, tx_id: &str) -> bool {
+        self.ids.contains(tx_id)
+    }
+
+    /// Returns the pending transactions without removing them
+    pub fn pending(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// What the pending transactions would move for `address`: the amounts and fees
+    /// it sends, and the amounts it receives
+    pub fn pending_for(&self, address: &str) -> (f64, f64) {
+        let outgoing = self
+            .transactions
+            .iter()
+            .filter(|tx| tx.sender == address)
+            .map(Transaction::total_cost)
+            .sum();
+        let incoming = self
+            .transactions
+            .iter()
+            .filter(|tx| tx.recipient == address)
+            .map(|tx| tx.amount)
+            .sum();
+        (outgoing, incoming)
+    }
+
+    /// Number of pending transactions
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Returns true if nothing is waiting to be mined
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifted_field_boundaries_do_not_collide() {
+        // Amount 1, fee 0, nonce 12 once concatenated like amount 10, fee 1, nonce 2
+        let first = Transaction::new("Alice", "Bob", 1.0).with_nonce(12);
+        let mut second = Transaction::new("Alice", "Bob", 10.0)
+            .with_fee(1.0)
+            .with_nonce(2);
+        second.timestamp = first.timestamp;
+        assert_ne!(first.calculate_hash(), second.calculate_hash());
+
+        // So the rewrite no longer carries the signature along
+        let wallet = crate::wallet::Wallet::generate();
+        let mut signed = Transaction::new(&wallet.address(), "Bob", 1.0).with_nonce(12);
+        wallet.sign_transaction(&mut signed).unwrap();
+        let mut rewritten = signed.clone().with_fee(1.0).with_nonce(2);
+        rewritten.amount = 10.0;
+        assert_eq!(rewritten.verify_signature(), Err(SignatureError::Invalid));
+
+        // And "Bob" receiving 15 like "Bob1" receiving 5
+        let first = Transaction::new("Alice", "Bob", 15.0);
+        let mut second = Transaction::new("Alice", "Bob1", 5.0);
+        second.timestamp = first.timestamp;
+        assert_ne!(first.calculate_hash(), second.calculate_hash());
+    }
+
+    #[test]
+    fn expired_transactions_are_refused() {
+        let mut mempool = Mempool::new();
+        let now = SystemClock.now_secs();
+        let expired = Transaction::new("Alice", "Bob", 5.0).with_expiry(now - 1);
+        assert!(matches!(
+            mempool.add_transaction(expired),
+            Err(MempoolError::Expired { .. })
+        ));
+        let last_moment = Transaction::new("Alice", "Bob", 5.0).with_expiry(now + 1);
+        assert_eq!(mempool.add_transaction(last_moment), Ok(()));
+    }
+
+    #[test]
+    fn evict_expired_returns_exactly_the_expired() {
+        let mut mempool = Mempool::new();
+        let now = SystemClock.now_secs();
+        let soon = Transaction::new("Alice", "Bob", 1.0).with_expiry(now + 10);
+        let later = Transaction::new("Alice", "Carol", 1.0).with_expiry(now + 20);
+        let forever = Transaction::new("Alice", "Dave", 1.0);
+        for transaction in [&soon, &later, &forever] {
+            mempool.add_transaction(transaction.clone()).unwrap();
+        }
+
+        assert!(mempool.evict_expired(now + 10).is_empty());
+        assert_eq!(mempool.evict_expired(now + 11), vec![soon.clone()]);
+        assert!(!mempool.contains(&soon.calculate_hash()));
+        assert_eq!(mempool.evict_expired(now + 1000), vec![later]);
+        assert_eq!(mempool.pending(), [forever]);
+
+        // An evicted transaction may be queued again, e.g. with a later expiry
+        assert_eq!(mempool.add_transaction(soon.with_expiry(now + 30)), Ok(()));
+    }
+
+    #[test]
+    fn expiry_is_covered_by_the_id() {
+        let transaction = Transaction::new("Alice", "Bob", 1.0);
+        let expiring = transaction.clone().with_expiry(100);
+        assert_ne!(transaction.calculate_hash(), expiring.calculate_hash());
+        assert_ne!(
+            expiring.calculate_hash(),
+            transaction.with_expiry(101).calculate_hash()
+        );
+    }
+
+    #[test]
+    fn get_transactions_drains_in_order() {
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 5.0))
+            .unwrap();
+        mempool
+            .add_transaction(Transaction::new("Bob", "Carol", 2.0))
+            .unwrap();
+
+        let drained = mempool.get_transactions(10);
+        assert_eq!(drained.len(), 2);
+        assert_eq!(drained[0].sender, "Alice");
+        assert_eq!(drained[1].sender, "Bob");
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn highest_fees_are_returned_first() {
+        let mut mempool = Mempool::new();
+        for (recipient, fee) in [("Bob", 0.5), ("Carol", 2.0), ("Dave", 0.5), ("Eve", 1.0)] {
+            mempool
+                .add_transaction(Transaction::new("Alice", recipient, 1.0).with_fee(fee))
+                .unwrap();
+        }
+
+        let first: Vec<String> = mempool
+            .get_transactions(3)
+            .into_iter()
+            .map(|tx| tx.recipient.to_string())
+            .collect();
+        assert_eq!(first, ["Carol", "Eve", "Bob"]);
+        assert_eq!(mempool.len(), 1);
+        assert_eq!(mempool.pending()[0].recipient, "Dave");
+    }
+
+    #[test]
+    fn full_pool_evicts_lowest_fee_for_higher_fee() {
+        let mut mempool = Mempool::with_capacity(2);
+        mempool
+            .add_transaction(Transaction::new("Alice", "Bob", 1.0).with_fee(1.0))
+            .unwrap();
+        mempool
+            .add_transaction(Transaction::new("Alice", "Carol", 1.0).with_fee(3.0))
+            .unwrap();
+
+        assert_eq!(
+            mempool.add_transaction(Transaction::new("Alice", "Dave", 1.0).with_fee(1.0)),
+            Err(MempoolError::FeeTooLow {
+                fee: 1.0,
+                min_fee: 1.0
+            })
+        );
+
+        mempool
+            .add_transaction(Transaction::new("Alice", "Eve", 1.0).with_fee(2.0))
+            .unwrap();
+        let recipients: Vec<&str> = mempool
+            .pending()
+            .iter()
+            .map(|tx| tx.recipient.as_str())
+            .collect();
+        assert_eq!(recipients, ["Carol", "Eve"]);
+    }
+
+    #[test]
+    fn duplicate_transactions_are_rejected() {
+        let mut mempool = Mempool::new();
+        let transaction = Transaction::new("Alice", "Bob", 1.0);
+        mempool.add_transaction(transaction.clone()).unwrap();
+        assert_eq!(
+            mempool.add_transaction(transaction.clone()),
+            Err(MempoolError::DuplicateTransaction(
+                transaction.calculate_hash()
+            ))
+        );
+        assert!(mempool.contains(&transaction.calculate_hash()));
+
+        // Once mined out of the pool the id is forgotten
+        mempool.get_transactions(1);
+        assert!(!mempool.contains(&transaction.calculate_hash()));
+    }
+
+    #[test]
+    fn repeated_payments_with_different_nonces_are_distinct() {
+        let mut mempool = Mempool::new();
+        let first = Transaction::new("Alice", "Bob", 1.0).with_nonce(1);
+        let mut second = first.clone().with_nonce(2);
+        second.timestamp = first.timestamp;
+
+        assert_ne!(first.calculate_hash(), second.calculate_hash());
+        mempool.add_transaction(first).unwrap();
+        mempool.add_transaction(second).unwrap();
+        assert_eq!(mempool.len(), 2);
+    }
+
+    #[test]
+    fn negative_fees_are_rejected() {
+        let mut mempool = Mempool::new();
+        assert_eq!(
+            mempool.add_transaction(Transaction::new("Alice", "Bob", 1.0).with_fee(-1.0)),
+            Err(MempoolError::InvalidFee(-1.0))
+        );
+    }
+
+    #[test]
+    fn non_positive_amounts_are_rejected() {
+        let mut mempool = Mempool::new();
+        for amount in [0.0, -5.0, f64::NAN, f64::INFINITY] {
+            let result = mempool.add_transaction(Transaction::new("Alice", "Bob", amount));
+            assert!(matches!(result, Err(MempoolError::InvalidAmount(_))));
+        }
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn hash_changes_with_contents() {
+        let transaction = Transaction::new("Alice", "Bob", 5.0);
+        let mut changed = transaction.clone();
+        changed.amount = 6.0;
+        assert_ne!(transaction.calculate_hash(), changed.calculate_hash());
+        let with_fee = transaction.clone().with_fee(0.1);
+        assert_ne!(transaction.calculate_hash(), with_fee.calculate_hash());
+    }
+
+    #[test]
+    fn wallet_senders_must_sign() {
+        let wallet = crate::wallet::Wallet::generate();
+        let mut mempool = Mempool::new();
+        let mut transaction = Transaction::new(&wallet.address(), "Bob", 1.0);
+        assert_eq!(
+            mempool.add_transaction(transaction.clone()),
+            Err(MempoolError::InvalidSignature(SignatureError::Missing))
+        );
+
+        wallet.sign_transaction(&mut transaction).unwrap();
+        let mut tampered = transaction.clone();
+        tampered.amount = 100.0;
+        assert_eq!(
+            mempool.add_transaction(tampered),
+            Err(MempoolError::InvalidSignature(SignatureError::Invalid))
+        );
+        mempool.add_transaction(transaction).unwrap();
+    }
+
+    #[test]
+    fn try_new_rejects_mistyped_addresses() {
+        let address = crate::wallet::Wallet::generate().address();
+        assert!(Transaction::try_new("Alice", &address, 1.0).is_ok());
+        let typo = format!("{}1", &address[..address.len() - 1]);
+        let typo = if typo == address {
+            format!("{}2", &address[..address.len() - 1])
+        } else {
+            typo
+        };
+        assert_eq!(
+            Transaction::try_new("Alice", &typo, 1.0),
+            Err(AddressError::BadChecksum(typo.clone()))
+        );
+    }
+
+    /// Chain whose genesis gives Alice 100 coins
+    fn alice_holds_100() -> Blockchain {
+        Blockchain::with_genesis(
+            crate::genesis::GenesisConfig::default().with_allocation("Alice", 100.0),
+        )
+    }
+
+    #[test]
+    fn pending_debits_reduce_the_available_balance() {
+        let blockchain = alice_holds_100();
+        let mut mempool = Mempool::new();
+        mempool
+            .add_funded_transaction(Transaction::new("Alice", "Bob", 60.0), &blockchain)
+            .unwrap();
+        assert_eq!(mempool.pending_for("Alice"), (60.0, 0.0));
+        assert_eq!(blockchain.get_confirmed_balance("Alice"), 100.0);
+        assert_eq!(blockchain.get_available_balance("Alice", &mempool), 40.0);
+
+        let too_much = Transaction::new("Alice", "Carol", 50.0).with_nonce(1);
+        assert_eq!(
+            mempool.add_funded_transaction(too_much, &blockchain),
+            Err(MempoolError::InsufficientBalance {
+                address: "Alice".to_string(),
+                available: 40.0,
+                required: 50.0,
+            })
+        );
+        let fits = Transaction::new("Alice", "Carol", 40.0).with_nonce(2);
+        assert_eq!(mempool.add_funded_transaction(fits, &blockchain), Ok(()));
+        assert_eq!(blockchain.get_available_balance("Alice", &mempool), 0.0);
+    }
+
+    #[test]
+    fn pending_credits_cannot_be_spent_before_they_confirm() {
+        let blockchain = alice_holds_100();
+        let mut mempool = Mempool::new();
+        mempool
+            .add_funded_transaction(Transaction::new("Alice", "Bob", 30.0), &blockchain)
+            .unwrap();
+        assert_eq!(mempool.pending_for("Bob"), (0.0, 30.0));
+        assert_eq!(blockchain.get_available_balance("Bob", &mempool), 0.0);
+
+        let spend = Transaction::new("Bob", "Carol", 10.0);
+        assert!(matches!(
+            mempool.add_funded_transaction(spend, &blockchain),
+            Err(MempoolError::InsufficientBalance { .. })
+        ));
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn coinbase_is_recognised() {
+        assert!(Transaction::coinbase("Miner", 137.0, 1).is_coinbase());
+        assert!(!Transaction::new("Alice", "Bob", 1.0).is_coinbase());
+    }
+}
@@ -0,0 +1,667 @@
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::transaction::{verify_signed_by, SignatureError, Transaction, TransactionSignature};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Largest rounding difference tolerated when comparing sums of amounts
+const AMOUNT_TOLERANCE: f64 = 1e-9;
+
+/// Reference to one output of a confirmed transaction
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub tx_id: String,
+    pub index: u32, // Position in the transaction's outputs
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.tx_id, self.index)
+    }
+}
+
+/// An unspent output consumed by a transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxInput {
+    pub prev_tx_id: String,
+    pub output_index: u32,
+    /// The owner's signature over the spending transaction's id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<TransactionSignature>,
+}
+
+impl TxInput {
+    /// Creates an unsigned input spending `outpoint`
+    pub fn new(outpoint: &OutPoint) -> TxInput {
+        TxInput {
+            prev_tx_id: outpoint.tx_id.clone(),
+            output_index: outpoint.index,
+            signature: None,
+        }
+    }
+
+    /// The output this input spends
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint {
+            tx_id: self.prev_tx_id.clone(),
+            index: self.output_index,
+        }
+    }
+}
+
+/// Coins created by a transaction, spendable by `recipient`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxOutput {
+    pub amount: f64,
+    pub recipient: String,
+}
+
+/// Reasons a UTXO transaction is not a valid spend
+#[derive(Debug, Clone, PartialEq)]
+pub enum UtxoError {
+    /// The transaction creates outputs without spending any
+    NoInputs,
+    /// The output doesn't exist or is already spent
+    UnknownOutput(OutPoint),
+    /// The transaction spends the same output twice
+    DuplicateInput(OutPoint),
+    /// The output belongs to someone other than the sender
+    NotOwner { outpoint: OutPoint, owner: String },
+    /// The input is unsigned or badly signed
+    InvalidSignature {
+        outpoint: OutPoint,
+        reason: SignatureError,
+    },
+    /// An output amount is zero, negative or not a number
+    InvalidOutput { amount: f64 },
+    /// An output pays someone other than the recipient or the sender (as change)
+    ForeignOutput { recipient: String },
+    /// The outputs are worth more than the inputs
+    OutputsExceedInputs { inputs: f64, outputs: f64 },
+    /// The outputs paying the recipient don't add up to the declared amount
+    AmountMismatch { declared: f64, paid: f64 },
+    /// The declared fee isn't the difference between inputs and outputs
+    FeeMismatch { declared: f64, actual: f64 },
+    /// The sender's unspent outputs can't cover the payment
+    InsufficientFunds { available: f64, required: f64 },
+}
+
+impl fmt::Display for UtxoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UtxoError::NoInputs => write!(f, "transaction has outputs but no inputs"),
+            UtxoError::UnknownOutput(outpoint) => {
+                write!(f, "output {} does not exist or is already spent", outpoint)
+            }
+            UtxoError::DuplicateInput(outpoint) => {
+                write!(f, "output {} is spent twice", outpoint)
+            }
+            UtxoError::NotOwner { outpoint, owner } => {
+                write!(f, "output {} belongs to {}", outpoint, owner)
+            }
+            UtxoError::InvalidSignature { outpoint, reason } => {
+                write!(f, "input spending {}: {}", outpoint, reason)
+            }
+            UtxoError::InvalidOutput { amount } => {
+                write!(f, "output amount {} must be greater than zero", amount)
+            }
+            UtxoError::ForeignOutput { recipient } => write!(
+                f,
+                "output to {} is neither the payment nor change",
+                recipient
+            ),
+            UtxoError::OutputsExceedInputs { inputs, outputs } => write!(
+                f,
+                "outputs worth {} exceed inputs worth {}",
+                outputs, inputs
+            ),
+            UtxoError::AmountMismatch { declared, paid } => write!(
+                f,
+                "outputs pay the recipient {} but the amount is {}",
+                paid, declared
+            ),
+            UtxoError::FeeMismatch { declared, actual } => write!(
+                f,
+                "inputs minus outputs is {} but the fee is {}",
+                actual, declared
+            ),
+            UtxoError::InsufficientFunds {
+                available,
+                required,
+            } => write!(
+                f,
+                "unspent outputs worth {} cannot cover {}",
+                available, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UtxoError {}
+
+/// Outputs `transaction` adds to the UTXO set. Coinbases create a single output
+/// paying their recipient; account-model transfers create none.
+pub fn created_outputs(transaction: &Transaction) -> Vec<TxOutput> {
+    if !transaction.outputs.is_empty() {
+        transaction.outputs.clone()
+    } else if transaction.is_coinbase() {
+        vec![TxOutput {
+            amount: transaction.amount,
+            recipient: transaction.recipient.to_string(),
+        }]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Every unspent output on a chain. Only coinbases and UTXO transactions move
+/// coins here, so on a chain that mixes in account-model transfers the set can
+/// hold outputs whose coins were already sent on. Blocks also check every spend
+/// against the sender's account balance, which counts both models, so those
+/// outputs can't be spent twice.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UtxoSet {
+    outputs: HashMap<OutPoint, TxOutput>,
+}
+
+impl UtxoSet {
+    /// Creates an empty set
+    pub fn new() -> UtxoSet {
+        UtxoSet::default()
+    }
+
+    /// Recomputes the set from scratch by replaying the best chain
+    pub fn rebuild(blockchain: &Blockchain) -> UtxoSet {
+        UtxoSet::from_blocks(blockchain.best_chain())
+    }
+
+    /// Replays `blocks`, genesis first
+    pub fn from_blocks(blocks: &[Block]) -> UtxoSet {
+        let mut set = UtxoSet::new();
+        for block in blocks {
+            set.apply_block(block);
+        }
+        set
+    }
+
+    /// Spends the inputs and adds the outputs of every transaction in `block`.
+    /// The block is assumed valid, see `check_block`.
+    pub fn apply_block(&mut self, block: &Block) {
+        for transaction in &block.transactions {
+            self.apply_transaction(transaction);
+        }
+    }
+
+    /// Spends the inputs and adds the outputs of `transaction`
+    pub fn apply_transaction(&mut self, transaction: &Transaction) {
+        for input in &transaction.inputs {
+            self.outputs.remove(&input.outpoint());
+        }
+        let tx_id = transaction.calculate_hash();
+        for (index, output) in created_outputs(transaction).into_iter().enumerate() {
+            let outpoint = OutPoint {
+                tx_id: tx_id.clone(),
+                index: index as u32,
+            };
+            self.outputs.insert(outpoint, output);
+        }
+    }
+
+    /// Checks the UTXO transactions of `block` in order, so a transaction may spend
+    /// outputs created earlier in the block but no output can be spent twice.
+    /// Returns the id of the first invalid transaction and why.
+    pub fn check_block(&self, block: &Block) -> Result<(), (String, UtxoError)> {
+        let mut spent = HashSet::new();
+        let mut created = HashMap::new();
+        for transaction in &block.transactions {
+            let tx_id = transaction.calculate_hash();
+            if transaction.is_utxo() {
+                let lookup = |outpoint: &OutPoint| {
+                    if spent.contains(outpoint) {
+                        None
+                    } else {
+                        created.get(outpoint).or_else(|| self.outputs.get(outpoint))
+                    }
+                };
+                check_spend(transaction, lookup).map_err(|reason| (tx_id.clone(), reason))?;
+            }
+
+            for input in &transaction.inputs {
+                spent.insert(input.outpoint());
+            }
+            for (index, output) in created_outputs(transaction).into_iter().enumerate() {
+                let outpoint = OutPoint {
+                    tx_id: tx_id.clone(),
+                    index: index as u32,
+                };
+                created.insert(outpoint, output);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a UTXO transaction against this set and returns its fee
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<f64, UtxoError> {
+        check_spend(transaction, |outpoint| self.outputs.get(outpoint))
+    }
+
+    /// Returns the unspent output at `outpoint`
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOutput> {
+        self.outputs.get(outpoint)
+    }
+
+    /// Unspent outputs owned by `address`, in outpoint order
+    pub fn unspent_for(&self, address: &str) -> Vec<(OutPoint, TxOutput)> {
+        let mut unspent: Vec<(OutPoint, TxOutput)> = self
+            .outputs
+            .iter()
+            .filter(|(_, output)| output.recipient == address)
+            .map(|(outpoint, output)| (outpoint.clone(), output.clone()))
+            .collect();
+        unspent.sort_by(|a, b| a.0.cmp(&b.0));
+        unspent
+    }
+
+    /// Total of the unspent outputs owned by `address`
+    pub fn balance(&self, address: &str) -> f64 {
+        self.unspent_for(address)
+            .iter()
+            .map(|(_, output)| output.amount)
+            .sum()
+    }
+
+    /// Number of unspent outputs
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns true if there are no unspent outputs
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// Builds an unsigned transaction paying `amount` to `recipient` out of the
+    /// sender's unspent outputs, returning what is left over minus `fee` as change
+    pub fn build_spend(
+        &self,
+        sender: &str,
+        recipient: &str,
+        amount: f64,
+        fee: f64,
+    ) -> Result<Transaction, UtxoError> {
+        let required = amount + fee;
+        let mut inputs = Vec::new();
+        let mut available = 0.0;
+        for (outpoint, output) in self.unspent_for(sender) {
+            if available >= required {
+                break;
+            }
+            available += output.amount;
+            inputs.push(TxInput::new(&outpoint));
+        }
+        if available < required {
+            return Err(UtxoError::InsufficientFunds {
+                available,
+                required,
+            });
+        }
+
+        let mut outputs = vec![TxOutput {
+            amount,
+            recipient: recipient.to_string(),
+        }];
+        let change = available - required;
+        if change > AMOUNT_TOLERANCE {
+            outputs.push(TxOutput {
+                amount: change,
+                recipient: sender.to_string(),
+            });
+        }
+
+        let mut transaction = Transaction::new(sender, recipient, amount).with_fee(fee);
+        transaction.inputs = inputs;
+        transaction.outputs = outputs;
+        Ok(transaction)
+    }
+}
+
+/// Validates a UTXO transaction, looking up spent outputs with `lookup`, and
+/// returns the fee: inputs minus outputs
+fn check_spend<'a>(
+    transaction: &Transaction,
+    lookup: impl Fn(&OutPoint) -> Option<&'a TxOutput>,
+) -> Result<f64, UtxoError> {
+    if transaction.inputs.is_empty() {
+        return Err(UtxoError::NoInputs);
+    }
+
+    let mut paid = 0.0;
+    for output in &transaction.outputs {
+        if !output.amount.is_finite() || output.amount <= 0.0 {
+            return Err(UtxoError::InvalidOutput {
+                amount: output.amount,
+            });
+        }
+        if output.recipient == transaction.recipient {
+            paid += output.amount;
+        } else if output.recipient != transaction.sender {
+            return Err(UtxoError::ForeignOutput {
+                recipient: output.recipient.clone(),
+            });
+        }
+    }
+    if (paid - transaction.amount).abs() > AMOUNT_TOLERANCE {
+        return Err(UtxoError::AmountMismatch {
+            declared: transaction.amount,
+            paid,
+        });
+    }
+
+    let tx_id = transaction.calculate_hash();
+    let mut seen = HashSet::new();
+    let mut inputs = 0.0;
+    for input in &transaction.inputs {
+        let outpoint = input.outpoint();
+        if !seen.insert(outpoint.clone()) {
+            return Err(UtxoError::DuplicateInput(outpoint));
+        }
+        let spent = lookup(&outpoint).ok_or_else(|| UtxoError::UnknownOutput(outpoint.clone()))?;
+        if spent.recipient != transaction.sender {
+            return Err(UtxoError::NotOwner {
+                owner: spent.recipient.clone(),
+                outpoint,
+            });
+        }
+        verify_signed_by(&spent.recipient, input.signature.as_ref(), tx_id.as_bytes())
+            .map_err(|reason| UtxoError::InvalidSignature { outpoint, reason })?;
+        inputs += spent.amount;
+    }
+
+    let outputs: f64 = transaction.outputs.iter().map(|output| output.amount).sum();
+    if outputs > inputs + AMOUNT_TOLERANCE {
+        return Err(UtxoError::OutputsExceedInputs { inputs, outputs });
+    }
+    let fee = inputs - outputs;
+    if (fee - transaction.fee).abs() > AMOUNT_TOLERANCE {
+        return Err(UtxoError::FeeMismatch {
+            declared: transaction.fee,
+            actual: fee,
+        });
+    }
+    Ok(fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use crate::blockchain::BlockchainError;
+    use crate::reward::BLOCK_REWARD;
+    use crate::transaction::Mempool;
+    use crate::wallet::Wallet;
+
+    /// Chain where `miner` has mined `blocks` empty blocks
+    fn funded_chain(miner: &str, blocks: usize) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..blocks {
+            blockchain
+                .mine_pending_transactions(&mut Mempool::new(), miner)
+                .unwrap();
+        }
+        blockchain
+    }
+
+    fn mine(blockchain: &mut Blockchain, transactions: Vec<Transaction>) {
+        let mut mempool = Mempool::new();
+        for transaction in transactions {
+            mempool.add_transaction(transaction).unwrap();
+        }
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+    }
+
+    /// Unmined block on top of the tip carrying a coinbase, then `transactions`
+    fn next_block(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+        let tip = blockchain.tip();
+        let mut block_transactions =
+            vec![Transaction::coinbase("Miner", BLOCK_REWARD, tip.index + 1)];
+        block_transactions.extend(transactions);
+        let mut block = Block::new(tip.index + 1, tip.hash.clone(), block_transactions);
+        block.timestamp = tip.timestamp + 1;
+        block
+    }
+
+    #[test]
+    fn spend_pays_recipient_and_returns_change() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let spend = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Bob", 30.0, 1.0)
+            .unwrap();
+        assert_eq!(spend.inputs.len(), 1);
+        assert_eq!(
+            spend.outputs,
+            [
+                TxOutput {
+                    amount: 30.0,
+                    recipient: "Bob".to_string()
+                },
+                TxOutput {
+                    amount: BLOCK_REWARD - 31.0,
+                    recipient: "Alice".to_string()
+                },
+            ]
+        );
+        assert_eq!(blockchain.utxo_set().check_transaction(&spend), Ok(1.0));
+
+        mine(&mut blockchain, vec![spend.clone()]);
+        let utxos = blockchain.utxo_set();
+        assert_eq!(utxos.balance("Bob"), 30.0);
+        assert_eq!(utxos.balance("Alice"), BLOCK_REWARD - 31.0);
+        // The miner's coinbase collects the reward plus the fee
+        assert_eq!(utxos.balance("Miner"), BLOCK_REWARD + 1.0);
+        // Account balances agree with the UTXO set
+        for address in ["Alice", "Bob", "Miner"] {
+            assert_eq!(blockchain.get_balance(address), utxos.balance(address));
+        }
+        assert!(utxos.get(&spend.inputs[0].outpoint()).is_none());
+    }
+
+    #[test]
+    fn spends_must_balance_and_stay_with_the_owner() {
+        let blockchain = funded_chain("Alice", 1);
+        let utxos = blockchain.utxo_set();
+        let spend = utxos.build_spend("Alice", "Bob", 30.0, 1.0).unwrap();
+
+        let mut greedy = spend.clone();
+        greedy.outputs[1].amount += 5.0;
+        assert!(matches!(
+            utxos.check_transaction(&greedy),
+            Err(UtxoError::OutputsExceedInputs { .. })
+        ));
+
+        let mut underpaid = spend.clone();
+        underpaid.fee = 0.0;
+        assert_eq!(
+            utxos.check_transaction(&underpaid),
+            Err(UtxoError::FeeMismatch {
+                declared: 0.0,
+                actual: 1.0
+            })
+        );
+
+        let mut thief = spend.clone();
+        thief.sender = Address::unchecked("Mallory");
+        thief.outputs[1].recipient = "Mallory".to_string();
+        assert!(matches!(
+            utxos.check_transaction(&thief),
+            Err(UtxoError::NotOwner { owner, .. }) if owner == "Alice"
+        ));
+
+        assert_eq!(
+            utxos.build_spend("Alice", "Bob", BLOCK_REWARD, 1.0),
+            Err(UtxoError::InsufficientFunds {
+                available: BLOCK_REWARD,
+                required: BLOCK_REWARD + 1.0
+            })
+        );
+    }
+
+    #[test]
+    fn double_spend_within_one_block_is_rejected() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let to_bob = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Bob", 10.0, 0.0)
+            .unwrap();
+        let to_carol = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Carol", 10.0, 0.0)
+            .unwrap();
+        assert_eq!(to_bob.inputs[0].outpoint(), to_carol.inputs[0].outpoint());
+
+        let block = next_block(&blockchain, vec![to_bob, to_carol.clone()]);
+        assert_eq!(
+            blockchain.add_block(block).unwrap_err(),
+            BlockchainError::InvalidSpend {
+                index: 2,
+                tx_id: to_carol.calculate_hash(),
+                reason: UtxoError::UnknownOutput(to_carol.inputs[0].outpoint())
+            }
+        );
+
+        // Mining from the mempool drops the conflicting spend instead
+        let mut mempool = Mempool::new();
+        mempool.add_transaction(to_carol.clone()).unwrap();
+        let to_dave = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Dave", 10.0, 0.0)
+            .unwrap()
+            .with_nonce(1);
+        mempool.add_transaction(to_dave).unwrap();
+        let block = blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+        assert_eq!(block.transactions.len(), 2);
+    }
+
+    #[test]
+    fn double_spend_across_blocks_is_rejected() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let to_bob = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Bob", 10.0, 0.0)
+            .unwrap();
+        let to_carol = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Carol", 10.0, 0.0)
+            .unwrap();
+        mine(&mut blockchain, vec![to_bob]);
+
+        let block = next_block(&blockchain, vec![to_carol.clone()]);
+        assert!(matches!(
+            blockchain.add_block(block).unwrap_err(),
+            BlockchainError::InvalidSpend {
+                index: 3,
+                reason: UtxoError::UnknownOutput(_),
+                ..
+            }
+        ));
+
+        // Change from the first spend can be spent in a later block
+        let from_change = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Carol", 10.0, 0.0)
+            .unwrap();
+        mine(&mut blockchain, vec![from_change]);
+        assert_eq!(blockchain.utxo_set().balance("Carol"), 10.0);
+        assert_eq!(blockchain.validate(), Ok(()));
+    }
+
+    #[test]
+    fn coins_sent_by_account_transfer_cannot_be_spent_as_outputs() {
+        let mut blockchain = funded_chain("Alice", 1);
+        let coinbase_spend = blockchain
+            .utxo_set()
+            .build_spend("Alice", "Carol", BLOCK_REWARD, 0.0)
+            .unwrap();
+        mine(
+            &mut blockchain,
+            vec![Transaction::new("Alice", "Bob", BLOCK_REWARD)],
+        );
+        // The account transfer left Alice's coinbase output unspent in the set
+        assert!(blockchain
+            .utxo_set()
+            .get(&coinbase_spend.inputs[0].outpoint())
+            .is_some());
+
+        let block = next_block(&blockchain, vec![coinbase_spend.clone()]);
+        assert_eq!(
+            blockchain.add_block(block).unwrap_err(),
+            BlockchainError::Overdraft {
+                index: 3,
+                tx_id: coinbase_spend.calculate_hash(),
+                balance: 0.0,
+                required: BLOCK_REWARD
+            }
+        );
+        assert_eq!(blockchain.get_balance("Alice"), 0.0);
+        assert_eq!(blockchain.get_balance("Bob"), BLOCK_REWARD);
+        assert_eq!(blockchain.get_balance("Carol"), 0.0);
+        assert_eq!(blockchain.validate(), Ok(()));
+    }
+
+    #[test]
+    fn wallet_outputs_need_the_owners_signature() {
+        let wallet = Wallet::generate();
+        let mut blockchain = funded_chain(&wallet.address(), 1);
+        let mut spend = blockchain
+            .utxo_set()
+            .build_spend(&wallet.address(), "Bob", 10.0, 0.0)
+            .unwrap();
+
+        // The transaction's own signature doesn't stand in for the inputs'
+        wallet.sign_transaction(&mut spend).unwrap();
+        let mut unsigned_input = spend.clone();
+        unsigned_input.inputs[0].signature = None;
+        assert!(matches!(
+            blockchain.utxo_set().check_transaction(&unsigned_input),
+            Err(UtxoError::InvalidSignature {
+                reason: SignatureError::Missing,
+                ..
+            })
+        ));
+
+        mine(&mut blockchain, vec![spend]);
+        assert_eq!(blockchain.utxo_set().balance("Bob"), 10.0);
+    }
+
+    #[test]
+    fn rebuilt_set_matches_the_incremental_one() {
+        let mut blockchain = funded_chain("Alice", 2);
+        for (recipient, amount) in [("Bob", 40.0), ("Carol", 25.0), ("Bob", 100.0)] {
+            let spend = blockchain
+                .utxo_set()
+                .build_spend("Alice", recipient, amount, 0.5)
+                .unwrap();
+            mine(&mut blockchain, vec![spend]);
+        }
+        let spend = blockchain
+            .utxo_set()
+            .build_spend("Bob", "Dave", 120.0, 0.0)
+            .unwrap();
+        assert_eq!(spend.inputs.len(), 2);
+        mine(&mut blockchain, vec![spend]);
+
+        assert_eq!(UtxoSet::rebuild(&blockchain), *blockchain.utxo_set());
+
+        // So does the set rebuilt when a saved chain is loaded
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chain.json");
+        blockchain.save_to_file(&path).unwrap();
+        let loaded = Blockchain::load_from_file(&path).unwrap();
+        assert_eq!(loaded.utxo_set(), blockchain.utxo_set());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::address::{Address, ADDRESS_PREFIX};
+use crate::transaction::{Transaction, TransactionSignature};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Reasons a wallet operation can fail
+#[derive(Debug)]
+pub enum WalletError {
+    /// The keystore file couldn't be read or written
+    Io(io::Error),
+    /// The keystore file isn't valid JSON of the expected shape
+    Parse(serde_json::Error),
+    /// Decryption failed: the passphrase is wrong or the file was altered
+    WrongPassphrase,
+    /// The keystore decrypted but doesn't hold a key for its recorded address
+    CorruptKeystore,
+    /// The transaction's sender is not this wallet's address
+    NotSender { address: String, sender: String },
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalletError::Io(e) => write!(f, "could not access wallet file: {}", e),
+            WalletError::Parse(e) => write!(f, "could not parse wallet file: {}", e),
+            WalletError::WrongPassphrase => write!(f, "wrong passphrase for wallet file"),
+            WalletError::CorruptKeystore => write!(f, "wallet file does not match its address"),
+            WalletError::NotSender { address, sender } => write!(
+                f,
+                "wallet {} cannot sign a transaction sent by {}",
+                address, sender
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+impl From<io::Error> for WalletError {
+    fn from(e: io::Error) -> Self {
+        WalletError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for WalletError {
+    fn from(e: serde_json::Error) -> Self {
+        WalletError::Parse(e)
+    }
+}
+
+/// Address the keystore recorded before addresses carried a version byte: the prefix,
+/// then hex of the key hash and a checksum. Such keystores still load.
+fn legacy_hex_address(public_key: &VerifyingKey) -> String {
+    let hash = Sha256::digest(public_key.as_bytes());
+    let payload = &hash[..20];
+    let checksum = Sha256::digest(Sha256::digest(payload));
+    let mut bytes = payload.to_vec();
+    bytes.extend_from_slice(&checksum[..4]);
+    format!("{}{}", ADDRESS_PREFIX, hex::encode(bytes))
+}
+
+/// Encrypted form of a wallet as stored on disk
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    address: String,
+    salt: String,       // Hex-encoded Argon2 salt
+    nonce: String,      // Hex-encoded ChaCha20-Poly1305 nonce
+    ciphertext: String, // Hex-encoded encrypted secret key
+}
+
+/// A signing key and the address it controls
+#[derive(Debug, Clone)]
+pub struct Wallet {
+    signing_key: SigningKey,
+}
+
+impl Wallet {
+    /// Creates a wallet with a fresh random key
+    pub fn generate() -> Wallet {
+        Wallet::from_signing_key(SigningKey::from_bytes(&rand::random::<[u8; 32]>()))
+    }
+
+    /// Wraps an existing key
+    pub fn from_signing_key(signing_key: SigningKey) -> Wallet {
+        Wallet { signing_key }
+    }
+
+    pub fn public_key(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+
+    /// Address this wallet receives and sends from
+    pub fn address(&self) -> Address {
+        Address::from_public_key(&self.public_key())
+    }
+
+    /// Signs `transaction` and each of its inputs; it must be sent from this wallet's address
+    pub fn sign_transaction(&self, transaction: &mut Transaction) -> Result<(), WalletError> {
+        let address = self.address();
+        if transaction.sender != address {
+            return Err(WalletError::NotSender {
+                address: address.to_string(),
+                sender: transaction.sender.to_string(),
+            });
+        }
+
+        // Every input is owned by the sender, so they all carry the same signature
+        let signature = self.sign(transaction.calculate_hash().as_bytes());
+        for input in &mut transaction.inputs {
+            input.signature = Some(signature.clone());
+        }
+        transaction.signature = Some(signature);
+        Ok(())
+    }
+
+    fn sign(&self, message: &[u8]) -> TransactionSignature {
+        TransactionSignature {
+            public_key: hex::encode(self.public_key().as_bytes()),
+            signature: hex::encode(self.signing_key.sign(message).to_bytes()),
+        }
+    }
+
+    /// Writes the wallet to `path`, encrypted with a key derived from `passphrase`
+    pub fn save_encrypted(&self, path: &Path, passphrase: &str) -> Result<(), WalletError> {
+        let salt: [u8; 16] = rand::random();
+        let nonce: [u8; 12] = rand::random();
+        let cipher = cipher(passphrase, &salt)?;
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                self.signing_key.to_bytes().as_slice(),
+            )
+            .map_err(|_| WalletError::CorruptKeystore)?;
+
+        let keystore = Keystore {
+            address: self.address().to_string(),
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        fs::write(path, serde_json::to_string_pretty(&keystore)?)?;
+        Ok(())
+    }
+
+    /// Reads a wallet written by `save_encrypted`
+    pub fn load_encrypted(path: &Path, passphrase: &str) -> Result<Wallet, WalletError> {
+        let keystore: Keystore = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let salt = hex::decode(&keystore.salt).map_err(|_| WalletError::CorruptKeystore)?;
+        let nonce = hex::decode(&keystore.nonce)
+            .ok()
+            .filter(|nonce| nonce.len() == 12)
+            .ok_or(WalletError::CorruptKeystore)?;
+        let ciphertext =
+            hex::decode(&keystore.ciphertext).map_err(|_| WalletError::CorruptKeystore)?;
+
+        // The cipher is authenticated, so a wrong passphrase fails here instead of
+        // producing a garbage key
+        let secret = cipher(passphrase, &salt)?
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| WalletError::WrongPassphrase)?;
+        let secret: [u8; 32] = secret
+            .try_into()
+            .map_err(|_| WalletError::CorruptKeystore)?;
+
+        let wallet = Wallet::from_signing_key(SigningKey::from_bytes(&secret));
+        if wallet.address() != keystore.address
+            && legacy_hex_address(&wallet.public_key()) != keystore.address
+        {
+            return Err(WalletError::CorruptKeystore);
+        }
+        Ok(wallet)
+    }
+}
+
+/// Cipher keyed by Argon2 over `passphrase` and `salt`
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, WalletError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| WalletError::CorruptKeystore)?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keystore_round_trips_with_the_right_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.json");
+        let wallet = Wallet::generate();
+        wallet.save_encrypted(&path, "correct horse").unwrap();
+
+        let loaded = Wallet::load_encrypted(&path, "correct horse").unwrap();
+        assert_eq!(loaded.address(), wallet.address());
+        assert_eq!(loaded.public_key(), wallet.public_key());
+    }
+
+    #[test]
+    fn wrong_passphrase_is_a_clean_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.json");
+        Wallet::generate()
+            .save_encrypted(&path, "correct horse")
+            .unwrap();
+
+        assert!(matches!(
+            Wallet::load_encrypted(&path, "battery staple"),
+            Err(WalletError::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn keystores_from_before_versioned_addresses_still_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.json");
+        let wallet = Wallet::generate();
+        wallet.save_encrypted(&path, "correct horse").unwrap();
+
+        let mut keystore: Keystore =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        keystore.address = legacy_hex_address(&wallet.public_key());
+        fs::write(&path, serde_json::to_string(&keystore).unwrap()).unwrap();
+
+        let loaded = Wallet::load_encrypted(&path, "correct horse").unwrap();
+        assert_eq!(loaded.address(), wallet.address());
+    }
+
+    #[test]
+    fn wallets_only_sign_their_own_transactions() {
+        let wallet = Wallet::generate();
+        let mut transaction = Transaction::new(&wallet.address(), "Bob", 5.0);
+        wallet.sign_transaction(&mut transaction).unwrap();
+        assert_eq!(transaction.verify_signature(), Ok(()));
+
+        let mut foreign = Transaction::new("Alice", "Bob", 5.0);
+        assert!(matches!(
+            wallet.sign_transaction(&mut foreign),
+            Err(WalletError::NotSender { .. })
+        ));
+    }
+}
@@ -0,0 +1,470 @@
+use assert_cmd::Command;
+use blockchain_simulation_rust::simulation::SimulationReport;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::path::Path;
+
+/// Runs the binary with its chain and mempool files inside `dir`, taking only
+/// wallet addresses as accounts
+fn bcsim_strict(dir: &Path) -> Command {
+    let mut command = Command::cargo_bin("blockchain_simulation_rust").unwrap();
+    command
+        .arg("--chain")
+        .arg(dir.join("chain.json"))
+        .arg("--mempool")
+        .arg(dir.join("mempool.json"));
+    command
+}
+
+/// Like `bcsim_strict`, but also accepting plain names like `alice`
+fn bcsim(dir: &Path) -> Command {
+    let mut command = bcsim_strict(dir);
+    command.arg("--legacy-names");
+    command
+}
+
+/// Creates a wallet file named `name` in `dir` and returns its address
+fn new_wallet(dir: &Path, name: &str) -> String {
+    let output = bcsim_strict(dir)
+        .args(["wallet", "--out"])
+        .arg(dir.join(name))
+        .env("WALLET_PASSPHRASE", "hunter2")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+#[test]
+fn init_send_mine_balance_flow() {
+    let dir = tempfile::tempdir().unwrap();
+
+    bcsim(dir.path())
+        .arg("init")
+        .assert()
+        .success()
+        .stdout(contains("genesis block"));
+    bcsim(dir.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .success()
+        .stdout(contains("Mined block 1"));
+    bcsim(dir.path())
+        .args(["send", "--from", "alice", "--to", "bob", "--amount", "30"])
+        .assert()
+        .success()
+        .stdout(contains("Queued transaction"));
+    bcsim(dir.path())
+        .args(["mine", "--miner", "carol"])
+        .assert()
+        .success()
+        .stdout(contains("Transactions: 2"));
+
+    bcsim(dir.path())
+        .args(["balance", "bob"])
+        .assert()
+        .success()
+        .stdout("30\n");
+    bcsim(dir.path())
+        .args(["balance", "alice"])
+        .assert()
+        .success()
+        .stdout("107\n");
+    bcsim(dir.path())
+        .arg("validate")
+        .assert()
+        .success()
+        .stdout(contains("3 blocks"));
+    bcsim(dir.path())
+        .args(["show", "block", "2"])
+        .assert()
+        .success()
+        .stdout(contains("alice     bob        30"));
+    bcsim(dir.path())
+        .args(["show", "address", "bob"])
+        .assert()
+        .success()
+        .stdout(contains("Final Balance: 30"));
+}
+
+#[test]
+fn wallet_signs_sends() {
+    let dir = tempfile::tempdir().unwrap();
+    let wallet = dir.path().join("alice.json");
+
+    bcsim_strict(dir.path()).arg("init").assert().success();
+    let alice = new_wallet(dir.path(), "alice.json");
+    let bob = new_wallet(dir.path(), "bob.json");
+    assert!(alice.starts_with("bsr"));
+
+    bcsim_strict(dir.path())
+        .args(["mine", "--miner", &alice])
+        .assert()
+        .success();
+    bcsim_strict(dir.path())
+        .args(["send", "--to", &bob, "--amount", "30", "--wallet"])
+        .arg(&wallet)
+        .env("WALLET_PASSPHRASE", "wrong")
+        .assert()
+        .failure()
+        .stderr(contains("wrong passphrase"));
+    bcsim_strict(dir.path())
+        .args(["send", "--to", &bob, "--amount", "30", "--wallet"])
+        .arg(&wallet)
+        .env("WALLET_PASSPHRASE", "hunter2")
+        .assert()
+        .success();
+    bcsim_strict(dir.path())
+        .args(["mine", "--miner", &alice])
+        .assert()
+        .success();
+    bcsim_strict(dir.path())
+        .args(["balance", &bob])
+        .assert()
+        .success()
+        .stdout("30\n");
+
+    // Spending from a wallet address without its key is refused
+    bcsim_strict(dir.path())
+        .args(["send", "--from", &alice, "--to", &bob, "--amount", "1"])
+        .assert()
+        .failure()
+        .stderr(contains("unsigned"));
+}
+
+#[test]
+fn accounts_must_be_wallet_addresses_without_legacy_names() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim_strict(dir.path()).arg("init").assert().success();
+    let alice = new_wallet(dir.path(), "alice.json");
+
+    bcsim_strict(dir.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .failure()
+        .stderr(contains("pass --legacy-names"));
+
+    // One mistyped character is caught before any coins move
+    let last = alice.chars().last().unwrap();
+    let typo = format!(
+        "{}{}",
+        &alice[..alice.len() - 1],
+        if last == '2' { '3' } else { '2' }
+    );
+    bcsim_strict(dir.path())
+        .args(["send", "--from", "alice", "--to", &typo, "--amount", "1"])
+        .arg("--legacy-names")
+        .assert()
+        .failure()
+        .stderr(contains("bad checksum"));
+    bcsim_strict(dir.path())
+        .args(["balance", &alice])
+        .assert()
+        .success()
+        .stdout("0\n");
+}
+
+#[test]
+fn binary_chain_files_stay_binary() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path())
+        .args(["init", "--binary"])
+        .assert()
+        .success();
+    bcsim(dir.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .success();
+
+    let bytes = std::fs::read(dir.path().join("chain.json")).unwrap();
+    assert!(bytes.starts_with(b"BSRC"));
+    bcsim(dir.path())
+        .arg("validate")
+        .assert()
+        .success()
+        .stdout(contains("2 blocks"));
+    bcsim(dir.path())
+        .args(["balance", "alice"])
+        .assert()
+        .success()
+        .stdout("137\n");
+}
+
+#[test]
+fn mining_continues_past_a_retarget() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path()).arg("init").assert().success();
+
+    // Blocks mined back to back come in fast, so the first retarget lowers the target
+    for index in 1..=12 {
+        bcsim(dir.path())
+            .args(["mine", "--miner", "alice"])
+            .assert()
+            .success()
+            .stdout(contains(format!("Mined block {}", index)));
+    }
+    bcsim(dir.path())
+        .arg("validate")
+        .assert()
+        .success()
+        .stdout(contains("13 blocks"));
+}
+
+#[test]
+fn insufficient_balance_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path()).arg("init").assert().success();
+
+    bcsim(dir.path())
+        .args(["send", "--from", "bob", "--to", "alice", "--amount", "5"])
+        .assert()
+        .failure()
+        .code(7)
+        .stderr(contains("insufficient balance"));
+}
+
+#[test]
+fn send_takes_expiry_as_an_rfc3339_time() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path()).arg("init").assert().success();
+    bcsim(dir.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .success();
+
+    let send = ["send", "--from", "alice", "--to", "bob", "--amount", "5"];
+    bcsim(dir.path())
+        .args(send)
+        .args(["--expires", "next week"])
+        .assert()
+        .failure()
+        .stderr(contains("not an RFC 3339 time"));
+    bcsim(dir.path())
+        .args(send)
+        .args(["--expires", "2999-01-01T00:00:00Z"])
+        .assert()
+        .success();
+    bcsim(dir.path())
+        .args(["mine", "--miner", "carol"])
+        .assert()
+        .success();
+
+    let chain = std::fs::read_to_string(dir.path().join("chain.json")).unwrap();
+    assert!(chain.contains("\"expires_at\": 32472144000"));
+}
+
+#[test]
+fn transactions_that_expire_while_saved_are_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path()).arg("init").assert().success();
+    bcsim(dir.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .success();
+    bcsim(dir.path())
+        .args(["send", "--from", "alice", "--to", "bob", "--amount", "5"])
+        .args(["--expires", "2999-01-01T00:00:00Z"])
+        .assert()
+        .success();
+
+    // Let the pending transaction expire, as if the next command ran much later
+    let path = dir.path().join("mempool.json");
+    let mempool = std::fs::read_to_string(&path).unwrap();
+    assert!(mempool.contains("\"expires_at\": 32472144000"));
+    std::fs::write(&path, mempool.replace("32472144000", "1")).unwrap();
+
+    bcsim(dir.path())
+        .args(["send", "--from", "alice", "--to", "carol", "--amount", "5"])
+        .assert()
+        .success();
+    bcsim(dir.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .success();
+    bcsim(dir.path())
+        .args(["balance", "bob"])
+        .assert()
+        .success()
+        .stdout("0\n");
+    bcsim(dir.path())
+        .args(["balance", "carol"])
+        .assert()
+        .success()
+        .stdout("5\n");
+}
+
+#[test]
+fn invalid_chain_file_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("chain.json"), "{ not a chain").unwrap();
+
+    bcsim(dir.path())
+        .arg("validate")
+        .assert()
+        .failure()
+        .code(5)
+        .stderr(contains("could not parse chain file"));
+}
+
+#[test]
+fn tampered_chain_file_fails_validation() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path()).arg("init").assert().success();
+    bcsim(dir.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .success();
+
+    let path = dir.path().join("chain.json");
+    let chain = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, chain.replace("\"alice\"", "\"mallory\"")).unwrap();
+    bcsim(dir.path())
+        .arg("validate")
+        .assert()
+        .failure()
+        .code(4)
+        .stderr(contains("block 1: merkle root does not match"));
+}
+
+#[test]
+fn init_refuses_to_overwrite() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path()).arg("init").assert().success();
+    bcsim(dir.path())
+        .arg("init")
+        .assert()
+        .failure()
+        .stderr(contains("already exists"));
+}
+
+#[test]
+fn export_and_import_move_a_chain_between_directories() {
+    let source = tempfile::tempdir().unwrap();
+    let target = tempfile::tempdir().unwrap();
+    let snapshot = source.path().join("snapshot");
+    bcsim(source.path()).arg("init").assert().success();
+    bcsim(source.path())
+        .args(["mine", "--miner", "alice"])
+        .assert()
+        .success();
+    bcsim(source.path())
+        .arg("export")
+        .arg(&snapshot)
+        .assert()
+        .success()
+        .stdout(contains("Exported 2 blocks"));
+
+    bcsim(target.path())
+        .args(["import", "--genesis", "beef"])
+        .arg(&snapshot)
+        .assert()
+        .failure()
+        .stderr(contains("but beef was expected"));
+    bcsim(target.path())
+        .arg("import")
+        .arg(&snapshot)
+        .assert()
+        .success()
+        .stdout(contains("Imported 2 blocks"));
+    let balance = |dir: &Path| {
+        let output = bcsim(dir).args(["balance", "alice"]).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(balance(target.path()), balance(source.path()));
+}
+
+#[test]
+fn compete_prints_a_leaderboard() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path())
+        .args(["compete", "--rounds", "3", "--difficulty", "1"])
+        .args(["--miner", "alice", "--miner", "bob:10"])
+        .assert()
+        .success()
+        .stdout(
+            contains("Rank Miner")
+                .and(contains("alice"))
+                .and(contains("bob")),
+        );
+    bcsim(dir.path())
+        .args(["compete", "--miner", "carol:lots"])
+        .assert()
+        .failure()
+        .stderr(contains("invalid hashes per tick"));
+    bcsim(dir.path())
+        .args(["compete", "--miner", "alice", "--miner", "alice:10"])
+        .assert()
+        .code(1)
+        .stderr(contains("miner alice is entered more than once"));
+    bcsim(dir.path())
+        .args(["compete", "--difficulty", "64", "--miner", "alice"])
+        .assert()
+        .code(1)
+        .stderr(contains("difficulty 64 can never be met"));
+}
+
+#[test]
+fn simulate_runs_the_demo() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path())
+        .args(["simulate", "--miner", "Miner"])
+        .assert()
+        .success()
+        .stdout(contains("Mining Completed Successfully"));
+}
+
+#[test]
+fn simulate_with_utxo_ledger_spends_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    bcsim(dir.path())
+        .args(["simulate", "--miner", "Miner", "--ledger", "utxo"])
+        .assert()
+        .success()
+        .stdout(contains("Transaction: Eve sent 10 to Miner"))
+        .stdout(contains("rejected").not());
+}
+
+#[test]
+fn simulate_runs_scenario_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/overdraft.toml");
+    bcsim(dir.path())
+        .args(["simulate", "--scenario"])
+        .arg(&example)
+        .assert()
+        .success()
+        .stdout(contains("Alice -> Bob: 20 rejected"))
+        .stdout(contains("Alice: 35"));
+
+    let scenario = dir.path().join("typo.toml");
+    std::fs::write(
+        &scenario,
+        "difficulty = 1\nminer = \"Miner\"\n\n[[transactions]]\nfrom = \"Miner\"\nto = \"Bobb\"\namount = 1.0\n",
+    )
+    .unwrap();
+    bcsim(dir.path())
+        .args(["simulate", "--scenario"])
+        .arg(&scenario)
+        .assert()
+        .failure()
+        .stderr(contains("line 6: account Bobb is not declared"));
+}
+
+#[test]
+fn simulate_json_report_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = bcsim(dir.path())
+        .args(["simulate", "--miner", "Miner", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Mining Completed Successfully"));
+
+    let report: SimulationReport = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report.total_blocks, report.blocks.len() + 1);
+    let block = &report.blocks[2];
+    assert_eq!(block.index, 3);
+    assert!(block.hash.starts_with(&"0".repeat(block.difficulty)));
+    assert!(block.iterations > 0);
+    assert_eq!(block.transactions.len(), 2);
+}
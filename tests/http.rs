@@ -0,0 +1,87 @@
+#![cfg(feature = "http")]
+
+use blockchain_simulation_rust::network::{network_genesis, Node};
+use blockchain_simulation_rust::{http, Blockchain, Mempool, Transaction};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+/// Sends one HTTP/1.1 request and returns the status code and parsed JSON body
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+fn server() -> SocketAddr {
+    let node = Node::new(Blockchain::with_genesis(network_genesis()), Mempool::new());
+    http::serve(node, "127.0.0.1:0").unwrap()
+}
+
+#[test]
+fn submit_mine_and_read_balance() {
+    let addr = server();
+
+    let (status, _) = request(addr, "POST", "/mine?miner=alice", "");
+    assert_eq!(status, 200);
+
+    let payment = serde_json::to_string(&Transaction::new("alice", "bob", 30.0)).unwrap();
+    let (status, body) = request(addr, "POST", "/transaction", &payment);
+    assert_eq!(status, 202);
+    assert!(body["id"].is_string());
+
+    let (status, body) = request(addr, "GET", "/mempool", "");
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    let (status, block) = request(addr, "POST", "/mine?miner=carol", "");
+    assert_eq!(status, 200);
+    assert_eq!(block["index"], 2);
+
+    let (status, body) = request(addr, "GET", "/balance/bob", "");
+    assert_eq!(status, 200);
+    assert_eq!(body["balance"], 30.0);
+
+    let (status, chain) = request(addr, "GET", "/chain", "");
+    assert_eq!(status, 200);
+    assert_eq!(chain.as_array().unwrap().len(), 3);
+    let (_, fetched) = request(addr, "GET", "/block/2", "");
+    assert_eq!(fetched, block);
+}
+
+#[test]
+fn errors_have_status_codes_and_reasons() {
+    let addr = server();
+
+    let overspend = serde_json::to_string(&Transaction::new("bob", "alice", 5.0)).unwrap();
+    let (status, body) = request(addr, "POST", "/transaction", &overspend);
+    assert_eq!(status, 400);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("insufficient balance"));
+
+    let (status, body) = request(addr, "POST", "/transaction", "not json");
+    assert_eq!(status, 400);
+    assert!(body["error"].is_string());
+
+    assert_eq!(request(addr, "GET", "/block/9", "").0, 404);
+    assert_eq!(request(addr, "GET", "/block/x", "").0, 400);
+    assert_eq!(request(addr, "POST", "/mine", "").0, 400);
+    assert_eq!(request(addr, "DELETE", "/chain", "").0, 405);
+    assert_eq!(request(addr, "GET", "/nowhere", "").0, 404);
+}
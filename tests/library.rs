@@ -0,0 +1,125 @@
+use blockchain_simulation_rust::block::DIFFICULTY;
+use blockchain_simulation_rust::events::ChainEvent;
+use blockchain_simulation_rust::reward::BLOCK_REWARD;
+use blockchain_simulation_rust::{Block, Blockchain, Mempool, Transaction};
+
+#[test]
+fn blockchain_starts_with_genesis_block() {
+    let blockchain = Blockchain::new();
+    assert_eq!(blockchain.get_total_blocks(), 1);
+    assert_eq!(blockchain.get_block_by_index(0).unwrap().index, 0);
+    assert!(blockchain
+        .get_block_by_index(0)
+        .unwrap()
+        .transactions
+        .is_empty());
+}
+
+#[test]
+fn added_blocks_are_linked_and_hashed() {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .add_block(Block::new(
+            1,
+            String::new(),
+            vec![
+                Transaction::coinbase("Alice", BLOCK_REWARD, 1),
+                Transaction::new("Alice", "Bob", 1.0),
+            ],
+        ))
+        .unwrap();
+    blockchain
+        .add_block(Block::new(
+            2,
+            String::new(),
+            vec![
+                Transaction::coinbase("Miner", BLOCK_REWARD, 2),
+                Transaction::new("Bob", "Carol", 1.0),
+            ],
+        ))
+        .unwrap();
+
+    assert_eq!(blockchain.get_total_blocks(), 3);
+    let first = &blockchain.get_block_by_index(1).unwrap();
+    let second = &blockchain.get_block_by_index(2).unwrap();
+    assert_eq!(first.hash.len(), 64);
+    assert_eq!(second.previous_hash, first.hash);
+    assert_ne!(first.hash, second.hash);
+}
+
+#[test]
+fn added_blocks_satisfy_difficulty() {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .add_block(Block::new(
+            1,
+            String::new(),
+            vec![
+                Transaction::coinbase("Alice", BLOCK_REWARD, 1),
+                Transaction::new("Alice", "Bob", 1.0),
+            ],
+        ))
+        .unwrap();
+
+    let block = &blockchain.get_block_by_index(1).unwrap();
+    assert!(block.mined);
+    assert!(block.hash.starts_with(&"0".repeat(DIFFICULTY)));
+}
+
+#[test]
+fn pending_transactions_are_mined_into_blocks() {
+    let mut blockchain = Blockchain::new();
+    let mut mempool = Mempool::new();
+    blockchain
+        .mine_pending_transactions(&mut mempool, "Alice")
+        .unwrap();
+    mempool
+        .add_transaction(Transaction::new("Alice", "Bob", 3.0))
+        .unwrap();
+
+    let block = blockchain
+        .mine_pending_transactions(&mut mempool, "Miner")
+        .unwrap();
+    assert_eq!(block.transactions.len(), 2);
+    assert!(blockchain.is_valid());
+    assert_eq!(blockchain.get_balance("Bob"), 3.0);
+}
+
+#[test]
+fn subscribers_see_blocks_and_transactions_in_order() {
+    let mut blockchain = Blockchain::new();
+    let mut mempool = Mempool::new();
+    mempool.set_events(blockchain.events().clone());
+    let events = blockchain.subscribe();
+
+    let mut mine = |mempool: &mut Mempool| {
+        let block = blockchain
+            .mine_pending_transactions(mempool, "Alice")
+            .unwrap();
+        ChainEvent::BlockAdded {
+            height: block.index,
+            hash: block.hash.clone(),
+        }
+    };
+    let first = mine(&mut mempool);
+    let payment = Transaction::new("Alice", "Bob", 3.0);
+    mempool.add_transaction(payment.clone()).unwrap();
+    let refund = Transaction::new("Bob", "Alice", -1.0);
+    mempool.add_transaction(refund.clone()).unwrap_err();
+    let second = mine(&mut mempool);
+    let third = mine(&mut mempool);
+
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![
+            first,
+            ChainEvent::TransactionAccepted(payment.calculate_hash()),
+            ChainEvent::TransactionRejected {
+                tx_id: refund.calculate_hash(),
+                reason: "amount -1 must be greater than zero".to_string(),
+            },
+            second,
+            third,
+        ]
+    );
+}
@@ -0,0 +1,189 @@
+use blockchain_simulation_rust::events::ChainEvent;
+use blockchain_simulation_rust::network::{
+    network_genesis, read_message, write_message, Message, Node,
+};
+use blockchain_simulation_rust::{Block, Blockchain, Mempool, Transaction};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn node() -> Node {
+    Node::new(Blockchain::with_genesis(network_genesis()), Mempool::new())
+}
+
+/// Polls until both nodes report the same tip, or gives up after a few seconds
+fn converged(a: &Node, b: &Node) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if a.tip_hash() == b.tip_hash() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+#[test]
+fn second_node_follows_blocks_mined_on_the_first() {
+    let miner = node();
+    let follower = node();
+    let miner_addr = miner.listen("127.0.0.1:0").unwrap();
+    follower.listen("127.0.0.1:0").unwrap();
+    follower.connect(&miner_addr.to_string()).unwrap();
+
+    for _ in 0..3 {
+        miner.mine("Miner").unwrap();
+    }
+
+    assert!(converged(&miner, &follower));
+    let chain = follower.blockchain();
+    assert_eq!(chain.get_total_blocks(), 4);
+    assert!(chain.is_valid());
+    assert_eq!(
+        chain.get_balance("Miner"),
+        miner.blockchain().get_balance("Miner")
+    );
+}
+
+#[test]
+fn late_joiner_catches_up_by_syncing() {
+    let miner = node();
+    let miner_addr = miner.listen("127.0.0.1:0").unwrap();
+    for _ in 0..3 {
+        miner.mine("Miner").unwrap();
+    }
+
+    // No listener, so the miner can't push to this node; it has to pull
+    let late = node();
+    assert_eq!(late.connect(&miner_addr.to_string()).unwrap(), 3);
+    assert_eq!(late.tip_hash(), miner.tip_hash());
+
+    miner.mine("Miner").unwrap();
+    miner.mine("Miner").unwrap();
+    assert_eq!(late.sync(&miner_addr.to_string()).unwrap(), 2);
+    assert_eq!(late.tip_hash(), miner.tip_hash());
+    assert_eq!(late.best_chain(), miner.blockchain().best_chain());
+    assert_eq!(late.block(5).unwrap().hash, late.tip_hash());
+    assert_eq!(late.block(6), None);
+}
+
+#[test]
+fn unattached_block_announcement_triggers_a_sync() {
+    let miner = node();
+    let miner_addr = miner.listen("127.0.0.1:0").unwrap();
+
+    // Connect before listening, so the miner never learns to push to this node
+    let follower = node();
+    follower.connect(&miner_addr.to_string()).unwrap();
+    let follower_addr = follower.listen("127.0.0.1:0").unwrap();
+
+    miner.mine("Miner").unwrap();
+    let tip = miner.mine("Miner").unwrap();
+
+    // Announce only the tip; its parent is unknown to the follower
+    let mut stream = TcpStream::connect(follower_addr).unwrap();
+    write_message(&mut stream, &Message::NewBlock(tip)).unwrap();
+
+    assert!(converged(&miner, &follower));
+    assert_eq!(follower.blockchain().get_total_blocks(), 3);
+}
+
+#[test]
+fn node_events_include_refused_submissions() {
+    let node = node();
+    let events = node.subscribe();
+    let block = node.mine("Alice").unwrap();
+    let overdraft = Transaction::new("Alice", "Bob", 1_000.0);
+    node.submit_transaction(overdraft.clone()).unwrap_err();
+
+    let received: Vec<ChainEvent> = events.try_iter().collect();
+    assert_eq!(
+        received[0],
+        ChainEvent::BlockAdded {
+            height: 1,
+            hash: block.hash,
+        }
+    );
+    assert!(matches!(
+        &received[1..],
+        [ChainEvent::TransactionRejected { tx_id, reason }]
+            if *tx_id == overdraft.calculate_hash() && reason.contains("insufficient")
+    ));
+}
+
+#[test]
+fn unsealed_peer_blocks_are_refused_not_mined() {
+    let node = node();
+    let addr = node.listen("127.0.0.1:0").unwrap();
+    let tip = node.tip_hash();
+
+    let unsealed = Block::new(1, tip.clone(), Vec::new());
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write_message(&mut stream, &Message::NewBlock(unsealed)).unwrap();
+    drop(stream);
+
+    // A sealed block sent afterwards shows the first one has been handled
+    let mut sealed = node.blockchain().prepare_block(&mut Mempool::new(), "Peer");
+    sealed.mine_block().unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write_message(&mut stream, &Message::NewBlock(sealed.clone())).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while node.tip_hash() == tip && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(20));
+    }
+    let chain = node.blockchain();
+    assert_eq!(chain.get_total_blocks(), 2);
+    assert_eq!(chain.tip().hash, sealed.hash);
+    assert_eq!(chain.side_block_count(), 0);
+}
+
+#[test]
+fn relayed_transactions_may_not_overspend_together() {
+    let node = node();
+    let addr = node.listen("127.0.0.1:0").unwrap();
+    node.mine("Alice").unwrap();
+    let balance = node.blockchain().get_balance("Alice");
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    for message in [
+        Message::NewTransaction(Transaction::new("Alice", "Bob", balance - 10.0)),
+        Message::NewTransaction(Transaction::new("Alice", "Carol", 20.0)),
+        Message::NewTransaction(Transaction::coinbase("Mallory", 1e9, 2)),
+        Message::GetChainHeight,
+    ] {
+        write_message(&mut stream, &message).unwrap();
+    }
+    // The node answers in order, so by the reply it has handled every relay
+    assert_eq!(read_message(&mut stream).unwrap(), Message::ChainHeight(1));
+
+    let pending = node.pending_transactions();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].recipient, "Bob");
+}
+
+#[test]
+fn transactions_dropped_by_a_reorg_are_requeued_if_still_affordable() {
+    let local = node();
+    local.mine("Alice").unwrap();
+    let to_bob = Transaction::new("Alice", "Bob", 10.0);
+    local.submit_transaction(to_bob.clone()).unwrap();
+    local.mine("Dave").unwrap();
+    local
+        .submit_transaction(Transaction::new("Dave", "Carol", 5.0))
+        .unwrap();
+    local.mine("Dave").unwrap();
+    assert!(local.pending_transactions().is_empty());
+
+    // A longer branch where Alice is funded but Dave never mined anything
+    let peer = node();
+    let peer_addr = peer.listen("127.0.0.1:0").unwrap();
+    for _ in 0..4 {
+        peer.mine("Alice").unwrap();
+    }
+    local.sync(&peer_addr.to_string()).unwrap();
+
+    assert_eq!(local.tip_hash(), peer.tip_hash());
+    assert_eq!(local.pending_transactions(), vec![to_bob]);
+    assert_eq!(local.balance("Dave"), 0.0);
+}
@@ -0,0 +1,242 @@
+use blockchain_simulation_rust::blockchain::{ChainValidationError, LoadError};
+use blockchain_simulation_rust::snapshot::{SnapshotError, SNAPSHOT_BLOCKS_FILE};
+use blockchain_simulation_rust::store::{ChainStore, BLOCK_LOG_FILE};
+use blockchain_simulation_rust::{Blockchain, Mempool, Transaction};
+use std::fs;
+use std::path::PathBuf;
+
+/// Returns a path in the system temp directory unique to this test
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "blockchain_simulation_{}_{}.json",
+        name,
+        std::process::id()
+    ))
+}
+
+fn mined_chain(blocks: usize) -> Blockchain {
+    let mut blockchain = Blockchain::new();
+    let mut mempool = Mempool::new();
+    for i in 0..blocks {
+        if i > 0 {
+            mempool
+                .add_transaction(Transaction::new("Miner", "Alice", 1.0))
+                .unwrap();
+        }
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+    }
+    blockchain
+}
+
+#[test]
+fn chain_round_trips_through_json_file() {
+    let blockchain = mined_chain(5);
+    let path = temp_path("round_trip");
+
+    blockchain.save_to_file(&path).unwrap();
+    let loaded = Blockchain::load_from_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.get_total_blocks(), 6);
+    assert_eq!(loaded, blockchain);
+}
+
+#[test]
+fn tampered_hash_fails_to_load() {
+    let blockchain = mined_chain(5);
+    let path = temp_path("tampered");
+    blockchain.save_to_file(&path).unwrap();
+
+    let mut json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    json["chain"][3]["hash"] = serde_json::Value::String("00".repeat(32));
+    fs::write(&path, serde_json::to_string_pretty(&json).unwrap()).unwrap();
+
+    let result = Blockchain::load_from_file(&path);
+    fs::remove_file(&path).unwrap();
+
+    match result {
+        Err(LoadError::Invalid(error)) => {
+            assert_eq!(error, ChainValidationError::HashMismatch { index: 3 })
+        }
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_and_malformed_files_are_reported() {
+    let path = temp_path("missing");
+    assert!(matches!(
+        Blockchain::load_from_file(&path),
+        Err(LoadError::Io(_))
+    ));
+
+    fs::write(&path, "not json").unwrap();
+    let result = Blockchain::load_from_file(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(LoadError::Parse(_))));
+}
+
+fn store_dir(name: &str) -> PathBuf {
+    let dir = temp_path(name).with_extension("store");
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn attached_store_persists_every_accepted_block() {
+    let dir = store_dir("store_round_trip");
+    let mut blockchain = mined_chain(2);
+    blockchain
+        .attach_store(ChainStore::open(&dir).unwrap())
+        .unwrap();
+    let mut mempool = Mempool::new();
+    for _ in 0..2 {
+        blockchain
+            .mine_pending_transactions(&mut mempool, "Miner")
+            .unwrap();
+    }
+
+    let store = ChainStore::open(&dir).unwrap();
+    let head = store.head().unwrap().unwrap();
+    assert_eq!(
+        (head.tip.as_str(), head.height),
+        (blockchain.tip().hash.as_str(), 4)
+    );
+    let loaded = store.load().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(loaded, blockchain);
+}
+
+#[test]
+fn torn_write_recovers_to_the_last_complete_block() {
+    let dir = store_dir("store_torn_write");
+    let mut blockchain = mined_chain(3);
+    blockchain
+        .attach_store(ChainStore::open(&dir).unwrap())
+        .unwrap();
+    let complete_len = fs::metadata(dir.join(BLOCK_LOG_FILE)).unwrap().len();
+    let complete_tip = blockchain.tip().hash.clone();
+    blockchain
+        .mine_pending_transactions(&mut Mempool::new(), "Miner")
+        .unwrap();
+
+    // Cut the last record off halfway, as a crash in the middle of the write would
+    let log = dir.join(BLOCK_LOG_FILE);
+    let full_len = fs::metadata(&log).unwrap().len();
+    let file = fs::OpenOptions::new().write(true).open(&log).unwrap();
+    file.set_len((complete_len + full_len) / 2).unwrap();
+    drop(file);
+
+    let mut recovered = ChainStore::open(&dir).unwrap().load().unwrap();
+    assert_eq!(recovered.get_total_blocks(), 4);
+    assert_eq!(recovered.tip().hash, complete_tip);
+    assert_eq!(fs::metadata(&log).unwrap().len(), complete_len);
+
+    // The log keeps growing cleanly from the recovered tip
+    recovered
+        .mine_pending_transactions(&mut Mempool::new(), "Miner")
+        .unwrap();
+    let reloaded = ChainStore::open(&dir).unwrap().load().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(reloaded, recovered);
+}
+
+#[test]
+fn corrupt_records_fail_the_load_and_keep_the_log() {
+    let dir = store_dir("store_corrupt_record");
+    let mut blockchain = mined_chain(6);
+    blockchain
+        .attach_store(ChainStore::open(&dir).unwrap())
+        .unwrap();
+    let log = dir.join(BLOCK_LOG_FILE);
+    let mut bytes = fs::read(&log).unwrap();
+
+    // Flip a byte in the middle of the third block's payload, with blocks after it
+    let mut offset = 0;
+    for _ in 0..3 {
+        let length = u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        offset += 8 + length as usize;
+    }
+    let length = u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    bytes[offset + 8 + length as usize / 2] ^= 1;
+    fs::write(&log, &bytes).unwrap();
+
+    let result = ChainStore::open(&dir).unwrap().load();
+    let after = fs::read(&log).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    match result {
+        Err(LoadError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidData),
+        other => panic!("expected a checksum error, got {:?}", other),
+    }
+    assert_eq!(after, bytes);
+}
+
+#[test]
+fn stores_only_hold_one_chain() {
+    let dir = store_dir("store_occupied");
+    mined_chain(1)
+        .attach_store(ChainStore::open(&dir).unwrap())
+        .unwrap();
+    let result = mined_chain(1).attach_store(ChainStore::open(&dir).unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        result.unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+}
+
+#[test]
+fn snapshot_round_trips_with_its_manifest() {
+    let dir = store_dir("snapshot_round_trip");
+    let blockchain = mined_chain(4);
+    let manifest = blockchain.export_snapshot(&dir).unwrap();
+    assert_eq!(manifest.height, 4);
+    assert_eq!(manifest.tip_hash, blockchain.tip().hash);
+
+    let genesis = blockchain.get_block_by_index(0).unwrap().hash.clone();
+    let imported = Blockchain::import_snapshot_with_genesis(&dir, &genesis).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(imported, blockchain);
+}
+
+#[test]
+fn snapshot_bit_flip_fails_the_manifest_digest() {
+    let dir = store_dir("snapshot_bit_flip");
+    let manifest = mined_chain(3).export_snapshot(&dir).unwrap();
+    let blocks = dir.join(SNAPSHOT_BLOCKS_FILE);
+    let mut bytes = fs::read(&blocks).unwrap();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0x01;
+    fs::write(&blocks, bytes).unwrap();
+
+    let result = Blockchain::import_snapshot(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    match result {
+        Err(SnapshotError::DigestMismatch {
+            manifest: listed, ..
+        }) => {
+            assert_eq!(listed, manifest.payload_sha256)
+        }
+        other => panic!("expected a digest mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn snapshot_from_another_genesis_is_refused() {
+    let dir = store_dir("snapshot_genesis");
+    let manifest = mined_chain(2).export_snapshot(&dir).unwrap();
+    let expected = "00".repeat(32);
+
+    let result = Blockchain::import_snapshot_with_genesis(&dir, &expected);
+    fs::remove_dir_all(&dir).unwrap();
+    match result {
+        Err(SnapshotError::GenesisMismatch {
+            expected: wanted,
+            actual,
+        }) => assert_eq!((wanted, actual), (expected, manifest.genesis_hash)),
+        other => panic!("expected a genesis mismatch, got {:?}", other),
+    }
+}
@@ -0,0 +1,42 @@
+use blockchain_simulation_rust::scenario::{run_scenario, Outcome};
+use std::path::PathBuf;
+
+fn example(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join(name)
+}
+
+#[test]
+fn payments_example_runs() {
+    let result = run_scenario(&example("payments.toml")).unwrap();
+    assert!(result
+        .outcomes
+        .iter()
+        .all(|outcome| *outcome == Outcome::Accepted));
+    assert_eq!(result.blockchain.get_total_blocks(), 5);
+    assert_eq!(result.blockchain.validate(), Ok(()));
+
+    assert_eq!(result.balances["Alice"], 70.0);
+    assert_eq!(result.balances["Bob"], 75.0);
+    assert_eq!(result.balances["Charlie"], 15.0);
+    assert_eq!(result.balances["Miner"], 4.0 * 50.0 - 60.0);
+}
+
+#[test]
+fn overdraft_example_rejects_overdrafts() {
+    let result = run_scenario(&example("overdraft.toml")).unwrap();
+    assert_eq!(
+        result.outcomes,
+        vec![
+            Outcome::Accepted,
+            Outcome::Rejected,
+            Outcome::Accepted,
+            Outcome::Rejected
+        ]
+    );
+    assert_eq!(result.balances["Alice"], 35.0);
+    assert_eq!(result.balances["Bob"], 0.0);
+    // A block is mined for every transaction, even ones left out of it
+    assert_eq!(result.balances["Miner"], 4.0 * 10.0);
+}
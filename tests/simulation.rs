@@ -0,0 +1,111 @@
+use blockchain_simulation_rust::clock::{Clock, MockClock};
+use blockchain_simulation_rust::competition::{run_mining_competition, MinerConfig};
+use blockchain_simulation_rust::simulation::{
+    simulate_with_options, Ledger, SimulationOptions, SEEDED_EPOCH,
+};
+use blockchain_simulation_rust::{Block, Blockchain, Mempool};
+use std::io;
+use std::sync::Arc;
+
+/// Four traders make a 5-block run: the miner's first block plus one per transfer
+const TRADERS: [&str; 4] = ["Bob", "Alice", "Charlie", "David"];
+
+/// Tip of the seed 42 run below; update it only for deliberate consensus changes
+const GOLDEN_TIP_HASH: &str = "00888d11406991806d10e143240e842249682af51ac05126808cc4d25db8713f";
+
+fn seeded_run(seed: u64, ledger: Ledger) -> Blockchain {
+    let options = SimulationOptions {
+        ledger,
+        seed: Some(seed),
+    };
+    let (blockchain, _) =
+        simulate_with_options("Miner", &TRADERS, &options, &mut io::sink()).unwrap();
+    blockchain
+}
+
+/// Binary encoding of each block, leaving out the wall-clock mining measurements
+fn chain_content(blockchain: &Blockchain) -> Vec<Vec<u8>> {
+    blockchain
+        .iter()
+        .map(|block| {
+            let block = Block {
+                mining_stats: None,
+                ..block.clone()
+            };
+            block.to_bytes()
+        })
+        .collect()
+}
+
+#[test]
+fn seeded_runs_are_identical() {
+    for ledger in [Ledger::Account, Ledger::Utxo] {
+        let first = seeded_run(7, ledger);
+        let second = seeded_run(7, ledger);
+        assert_eq!(first.get_total_blocks(), 6);
+        assert_eq!(chain_content(&first), chain_content(&second));
+        assert_eq!(first.validate(), Ok(()));
+    }
+    assert_ne!(
+        seeded_run(7, Ledger::Account).tip().hash,
+        seeded_run(8, Ledger::Account).tip().hash
+    );
+}
+
+#[test]
+fn seeded_run_matches_the_recorded_tip() {
+    let blockchain = seeded_run(42, Ledger::Account);
+    assert_eq!(blockchain.iter().next().unwrap().timestamp, SEEDED_EPOCH);
+    assert_eq!(blockchain.tip().hash, GOLDEN_TIP_HASH);
+}
+
+#[test]
+fn chains_follow_an_injected_clock() {
+    let clock = Arc::new(MockClock::new(SEEDED_EPOCH));
+    let mut blockchain = Blockchain::with_clock(clock.clone());
+    let mut mempool = Mempool::new();
+
+    clock.advance(30);
+    let block = blockchain
+        .mine_pending_transactions(&mut mempool, "Miner")
+        .unwrap();
+    assert_eq!(block.timestamp, SEEDED_EPOCH + 30);
+    assert_eq!(block.transactions[0].timestamp, SEEDED_EPOCH + 30);
+
+    // Blocks stamped by the real clock are now too far ahead of the mock one
+    let mut ahead = Block::new(2, block.hash.clone(), Vec::new());
+    ahead.mine_block().unwrap();
+    assert!(blockchain.add_block(ahead).is_err());
+    assert_eq!(clock.now_secs(), SEEDED_EPOCH + 30);
+}
+
+#[test]
+fn mining_competition_awards_each_round_to_one_miner() {
+    let miners = [
+        MinerConfig::new("Alice"),
+        MinerConfig::new("Bob").with_hash_budget(200),
+        MinerConfig::new("Carol").with_hash_budget(50),
+    ];
+    let names = ["Alice", "Bob", "Carol"];
+    let result = run_mining_competition(&miners, 6, 2).unwrap();
+
+    let mined: Vec<&Block> = result.blockchain.iter().skip(1).collect();
+    assert_eq!(mined.len(), 6);
+    for block in &mined {
+        assert_eq!(block.difficulty, 2);
+        let coinbase = &block.transactions[0];
+        assert!(coinbase.is_coinbase());
+        assert!(names.contains(&coinbase.recipient.as_str()));
+    }
+    assert!(result.blockchain.is_valid());
+
+    let wins: usize = result.standings.iter().map(|standing| standing.wins).sum();
+    assert_eq!(wins, 6);
+    for standing in &result.standings {
+        assert_eq!(
+            standing.balance,
+            result.blockchain.get_balance(&standing.name)
+        );
+        assert_eq!(standing.balance > 0.0, standing.wins > 0);
+    }
+}